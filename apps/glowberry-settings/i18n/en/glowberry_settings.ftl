@@ -13,8 +13,22 @@ category-shaders = Live Wallpapers
 
 # Settings
 same-on-all = Same on all displays
+config-problems = Configuration problems
 fit = Fit
 frame-rate = Frame Rate
+pause-on-suspend = Pause while suspended
+
+# Slideshow
+slideshow = Slideshow
+slideshow-previous = Previous
+slideshow-next = Next
+slideshow-favorite = Favorite
+slideshow-block = Never show again
+blocked-images = { $count ->
+    [one] 1 blocked image
+   *[other] { $count } blocked images
+}
+clear-blocklist = Clear
 
 # Fit options
 fit-fill = Fill
@@ -25,6 +39,24 @@ fps-15 = 15 FPS
 fps-30 = 30 FPS
 fps-60 = 60 FPS
 
+# Custom color editor
+custom-color = Custom Color
+color-kind = Type
+color-kind-solid = Solid
+color-kind-linear = Linear gradient
+color-kind-radial = Radial gradient
+gradient-stops = Color stops
+add-stop = Add stop
+remove-stop = Remove stop
+gradient-angle = Angle
+color-red = Red
+color-green = Green
+color-blue = Blue
+color-hex = Hex
+apply-color = Apply
+save-color = Save to palette
+remove-color = Remove from palette
+
 # Loading states
 loading-wallpapers = Loading wallpapers...
 no-shaders = No shaders found. Install shaders to ~/.local/share/glowberry/shaders/
@@ -47,12 +79,24 @@ resource-high = High
 settings = Settings
 background-service = Background Service
 use-glowberry = Use GlowBerry as default
+mirror-cosmic-bg = Keep wallpaper when disabled
 path-order-warning = Warning: ~/.local/bin must come before /usr/bin in PATH for this to work
 appearance = Appearance
 window-opacity = Window Opacity
 performance = Performance
 prefer-low-power = Prefer low power GPU
 
+# Profiles section
+profiles = Profiles
+profile = Profile
+save-profile = Save current setup
+profile-name = Name
+save = Save
+import-profile = Import…
+export-profile = Export…
+delete-profile = Delete
+profile-archive = Profile archive
+
 # Power saving section
 power-saving = Power Saving
 on-battery = On battery power
@@ -6,6 +6,7 @@ use crate::fl;
 use crate::shader_params::{ParamType, ParamValue, ParsedShader};
 use cosmic::app::context_drawer::{self, ContextDrawer};
 use cosmic::app::{Core, Task};
+use cosmic::dialog::file_chooser::{self, FileFilter};
 use cosmic::iced::Subscription;
 use cosmic::iced::{Alignment, Length};
 use cosmic::iced_runtime::core::image::Handle as ImageHandle;
@@ -14,9 +15,16 @@ use cosmic::widget::{
 };
 use cosmic::{ApplicationExt, Apply, Element};
 use cosmic_config::CosmicConfigEntry;
+use glowberry_config::diagnostics::Diagnostic;
 use glowberry_config::power_saving::{OnBatteryAction, PowerSavingConfig};
+use glowberry_config::profiles::Profile;
 use glowberry_config::state::State;
-use glowberry_config::{Color, Config, Context as ConfigContext, Entry, Gradient, Source};
+use glowberry_config::{
+    Color, Config, Context as ConfigContext, Entry, Gradient, GradientKind, Monitor, ShaderClock,
+    Source,
+};
+use glowberry_lib::control::{self, ControlCommand};
+use glowberry_lib::profile_archive;
 use glowberry_lib::shader_analysis::{self, Complexity};
 use image::{ImageBuffer, Rgba};
 use slotmap::{DefaultKey, SecondaryMap, SlotMap};
@@ -60,8 +68,12 @@ pub struct GlowBerrySettings {
     outputs: segmented_button::SingleSelectModel,
     /// The display that is currently being configured (None means "all")
     active_output: Option<String>,
+    /// Monitors of the connected displays, by output name
+    monitors: HashMap<String, Monitor>,
     /// Whether to show the tab bar (more than one display)
     show_tab_bar: bool,
+    /// Problems the daemon found in the config
+    config_diagnostics: Vec<Diagnostic>,
 
     /// Category dropdown model
     categories: dropdown::multi::Model<String, Category>,
@@ -93,6 +105,8 @@ pub struct GlowBerrySettings {
 
     /// Whether GlowBerry is currently set as the default background service
     glowberry_is_default: bool,
+    /// Whether backgrounds are written back to the cosmic-bg config
+    mirror_cosmic_bg: bool,
 
     /// Current shader parameter values (shader_index -> param_name -> value)
     shader_param_values: HashMap<usize, HashMap<String, ParamValue>>,
@@ -115,6 +129,22 @@ pub struct GlowBerrySettings {
 
     /// Window background opacity (0.0 = transparent, 1.0 = opaque)
     window_opacity: f32,
+
+    /// User-saved color and gradient swatches
+    custom_colors: Vec<Color>,
+    /// Custom color and gradient editor state
+    color_editor: ColorEditor,
+    /// Color editor kind options (Solid, Linear, Radial)
+    color_kind_options: Vec<String>,
+
+    /// Names of the saved profiles
+    profile_names: Vec<String>,
+    /// Index of the profile that was switched to last
+    active_profile: Option<usize>,
+    /// Name for saving the current configuration as a profile
+    profile_name: String,
+    /// Whether the selected shader pauses while the system is suspended
+    shader_pauses_on_suspend: bool,
 }
 
 /// Information about an available shader
@@ -149,6 +179,134 @@ struct SelectionContext {
     selection_handles: SecondaryMap<DefaultKey, ImageHandle>,
 }
 
+/// State of the custom color and gradient editor
+#[derive(Clone, Debug)]
+struct ColorEditor {
+    /// Selected kind index (0 = solid, 1 = linear gradient, 2 = radial gradient)
+    kind: usize,
+    /// Color stops; a solid color only uses the first stop
+    stops: Vec<[f32; 3]>,
+    /// Index of the stop being edited
+    active_stop: usize,
+    /// Angle of a linear gradient in degrees
+    angle: f32,
+    /// Hex text of the active stop
+    hex: String,
+}
+
+impl Default for ColorEditor {
+    fn default() -> Self {
+        let stops = vec![[0.243, 0.533, 1.000], [0.976, 0.227, 0.514]];
+        let hex = color_to_hex(stops[0]);
+        Self {
+            kind: 0,
+            stops,
+            active_stop: 0,
+            angle: 180.0,
+            hex,
+        }
+    }
+}
+
+impl ColorEditor {
+    /// Load an existing color or gradient into the editor.
+    fn load(&mut self, color: &Color) {
+        match color {
+            Color::Single(rgb) => {
+                self.kind = 0;
+                self.stops[0] = *rgb;
+            }
+            Color::Gradient(gradient) => {
+                self.kind = match gradient.kind {
+                    GradientKind::Linear => 1,
+                    GradientKind::Radial => 2,
+                };
+                self.stops = gradient.colors.to_vec();
+                self.angle = gradient.angle;
+            }
+        }
+        self.active_stop = 0;
+        self.sync_hex();
+    }
+
+    /// The color described by the editor.
+    fn color(&self) -> Color {
+        match self.kind {
+            0 => Color::Single(self.stops[0]),
+            kind => Color::Gradient(Gradient {
+                colors: Cow::Owned(self.stops.clone()),
+                angle: self.angle,
+                kind: if kind == 2 {
+                    GradientKind::Radial
+                } else {
+                    GradientKind::Linear
+                },
+            }),
+        }
+    }
+
+    fn is_gradient(&self) -> bool {
+        self.kind != 0
+    }
+
+    fn active(&self) -> [f32; 3] {
+        self.stops[self.active_stop]
+    }
+
+    fn set_kind(&mut self, kind: usize) {
+        self.kind = kind;
+        // Gradients need at least two stops
+        if self.is_gradient() && self.stops.len() < 2 {
+            let last = self.stops[self.stops.len() - 1];
+            self.stops.push(last);
+        }
+        if !self.is_gradient() {
+            self.active_stop = 0;
+        }
+        self.sync_hex();
+    }
+
+    fn set_channel(&mut self, channel: usize, value: f32) {
+        if let Some(component) = self.stops[self.active_stop].get_mut(channel) {
+            *component = value.clamp(0.0, 1.0);
+        }
+        self.sync_hex();
+    }
+
+    fn set_hex(&mut self, hex: String) {
+        if let Some(rgb) = color_from_hex(&hex) {
+            self.stops[self.active_stop] = rgb;
+        }
+        self.hex = hex;
+    }
+
+    fn select_stop(&mut self, idx: usize) {
+        if idx < self.stops.len() {
+            self.active_stop = idx;
+            self.sync_hex();
+        }
+    }
+
+    fn add_stop(&mut self) {
+        let color = self.active();
+        self.stops.insert(self.active_stop + 1, color);
+        self.active_stop += 1;
+        self.sync_hex();
+    }
+
+    fn remove_stop(&mut self) {
+        if self.stops.len() > 2 {
+            self.stops.remove(self.active_stop);
+            self.active_stop = self.active_stop.min(self.stops.len() - 1);
+            self.sync_hex();
+        }
+    }
+
+    fn sync_hex(&mut self) {
+        self.hex = color_to_hex(self.active());
+    }
+}
+
 /// Category options for the dropdown
 #[derive(Clone, Debug, PartialEq)]
 pub enum Category {
@@ -172,6 +330,8 @@ pub enum Message {
     ShaderThumbnail(usize, Option<ImageHandle>),
     /// Frame rate changed
     ShaderFrameRate(usize),
+    /// Shader clock changed to pause while suspended, or not
+    ShaderPausesOnSuspend(bool),
     /// Fit mode changed
     Fit(usize),
     /// Wallpaper event from subscription
@@ -192,6 +352,8 @@ pub enum Message {
     SetGlowBerryDefault(bool),
     /// Result of setting GlowBerry as default
     SetGlowBerryDefaultResult(Result<bool, String>),
+    /// Toggle writing backgrounds back to the cosmic-bg config
+    SetMirrorCosmicBg(bool),
     /// Shader parameter changed (shader_index, param_name, value) - updates UI only
     ShaderParamChanged(usize, String, ParamValue),
     /// Shader parameter slider released - applies to config
@@ -215,6 +377,54 @@ pub enum Message {
     SetWindowOpacity(f32),
     /// Window opacity slider released (save to config)
     WindowOpacityReleased,
+
+    // Color editor messages
+    /// Change the kind of color being edited (solid, linear, radial)
+    ColorEditorKind(usize),
+    /// Change one RGB channel of the active stop (channel index, value)
+    ColorEditorChannel(usize, f32),
+    /// Hex text of the active stop edited
+    ColorEditorHex(String),
+    /// Select a gradient stop for editing
+    ColorEditorSelectStop(usize),
+    /// Add a gradient stop after the active stop
+    ColorEditorAddStop,
+    /// Remove the active gradient stop
+    ColorEditorRemoveStop,
+    /// Change the gradient angle
+    ColorEditorAngle(f32),
+    /// Apply the edited color as the wallpaper
+    ColorEditorApply,
+    /// Save the edited color to the custom palette
+    SaveCustomColor,
+    /// Remove a color from the custom palette
+    RemoveCustomColor(Color),
+
+    // Slideshow messages
+    /// Send a slideshow command to the daemon
+    SlideshowControl(ControlCommand),
+    /// Result of a slideshow command
+    SlideshowControlResult(Result<(), String>),
+    /// Allow blocked images in slideshows again
+    ClearBlocklist,
+
+    // Profile messages
+    /// Replace the configuration with a saved profile
+    SwitchProfile(usize),
+    /// Name for a new profile edited
+    ProfileNameInput(String),
+    /// Save the current configuration as a profile
+    SaveProfile,
+    /// Delete the active profile
+    DeleteProfile,
+    /// Export the active profile to an archive
+    ExportProfile,
+    /// Import a profile from an archive
+    ImportProfile,
+    /// Result of exporting a profile
+    ProfileExported(Result<(), String>),
+    /// Result of importing a profile
+    ProfileImported(Result<Profile, String>),
 }
 
 /// Default colors available in the color picker
@@ -235,23 +445,28 @@ pub const DEFAULT_COLORS: &[Color] = &[
     Color::Single([0.584, 0.769, 0.988]),
     Color::Gradient(Gradient {
         colors: Cow::Borrowed(&[[1.000, 0.678, 0.000], [0.282, 0.725, 0.78]]),
-        radius: 180.0,
+        angle: 180.0,
+        kind: GradientKind::Linear,
     }),
     Color::Gradient(Gradient {
         colors: Cow::Borrowed(&[[1.000, 0.843, 0.631], [0.58, 0.922, 0.922]]),
-        radius: 180.0,
+        angle: 180.0,
+        kind: GradientKind::Linear,
     }),
     Color::Gradient(Gradient {
         colors: Cow::Borrowed(&[[1.000, 0.612, 0.867], [0.976, 0.29, 0.514]]),
-        radius: 180.0,
+        angle: 180.0,
+        kind: GradientKind::Linear,
     }),
     Color::Gradient(Gradient {
         colors: Cow::Borrowed(&[[0.584, 0.769, 0.988], [0.063, 0.165, 0.298]]),
-        radius: 180.0,
+        angle: 180.0,
+        kind: GradientKind::Linear,
     }),
     Color::Gradient(Gradient {
         colors: Cow::Borrowed(&[[0.969, 0.878, 0.384], [0.333, 0.278, 0.259]]),
-        radius: 180.0,
+        angle: 180.0,
+        kind: GradientKind::Linear,
     }),
 ];
 
@@ -327,7 +542,9 @@ impl cosmic::Application for GlowBerrySettings {
             about,
             outputs: segmented_button::SingleSelectModel::default(),
             active_output: None,
+            monitors: HashMap::new(),
             show_tab_bar: false,
+            config_diagnostics: Vec::new(),
             categories,
             selection: SelectionContext::default(),
             available_shaders,
@@ -340,6 +557,7 @@ impl cosmic::Application for GlowBerrySettings {
             current_folder,
             prefer_low_power: true, // Will be set below
             glowberry_is_default: is_glowberry_default(),
+            mirror_cosmic_bg: false, // Will be set below from config
             shader_param_values: HashMap::new(),
             shader_details_expanded: false,
             power_saving: PowerSavingConfig::default(),
@@ -359,30 +577,28 @@ impl cosmic::Application for GlowBerrySettings {
             ],
             selected_low_battery_threshold: 1, // 20% default
             window_opacity: 1.0,               // Will be set below from config
+            custom_colors: Vec::new(),
+            color_editor: ColorEditor::default(),
+            color_kind_options: vec![
+                fl!("color-kind-solid"),
+                fl!("color-kind-linear"),
+                fl!("color-kind-radial"),
+            ],
+            profile_names: Vec::new(),
+            active_profile: None,
+            profile_name: String::new(),
+            shader_pauses_on_suspend: false,
         };
 
         // Load prefer_low_power, power saving, and window opacity from config
         if let Some(ctx) = &app.config_context {
             app.prefer_low_power = ctx.prefer_low_power();
-            app.power_saving = ctx.power_saving_config();
+            app.mirror_cosmic_bg = ctx.mirror_cosmic_bg();
             app.window_opacity = ctx.window_opacity();
-
-            // Set dropdown indices based on loaded config
-            app.selected_on_battery_action = match app.power_saving.on_battery_action {
-                OnBatteryAction::Nothing => 0,
-                OnBatteryAction::Pause => 1,
-                OnBatteryAction::ReduceTo15Fps => 2,
-                OnBatteryAction::ReduceTo10Fps => 3,
-                OnBatteryAction::ReduceTo5Fps => 4,
-            };
-            app.selected_low_battery_threshold = match app.power_saving.low_battery_threshold {
-                10 => 0,
-                20 => 1,
-                30 => 2,
-                50 => 3,
-                _ => 1, // Default to 20%
-            };
+            app.custom_colors = ctx.custom_colors();
         }
+        app.load_power_saving();
+        app.load_profiles();
 
         // Populate outputs from config first - these are the outputs that have been configured
         // The daemon adds outputs to config as it discovers them via Wayland
@@ -406,8 +622,11 @@ impl cosmic::Application for GlowBerrySettings {
     fn subscription(&self) -> Subscription<Self::Message> {
         let mut subscriptions = vec![
             // Wallpaper loading subscription
-            wallpaper_subscription::wallpapers(self.current_folder.clone())
-                .map(Message::WallpaperEvent),
+            wallpaper_subscription::wallpapers(
+                self.current_folder.clone(),
+                self.config.max_image_pixels,
+            )
+            .map(Message::WallpaperEvent),
         ];
 
         // Watch for state changes from daemon (connected outputs, wallpaper state)
@@ -467,6 +686,7 @@ impl cosmic::Application for GlowBerrySettings {
             }
 
             Message::ColorSelect(color) => {
+                self.color_editor.load(&color);
                 self.selection.active = Choice::Color(color);
                 self.cached_display_handle = None;
                 self.apply_selection();
@@ -493,6 +713,11 @@ impl cosmic::Application for GlowBerrySettings {
                 self.apply_selection();
             }
 
+            Message::ShaderPausesOnSuspend(value) => {
+                self.shader_pauses_on_suspend = value;
+                self.apply_selection();
+            }
+
             Message::Fit(idx) => {
                 self.selected_fit = idx;
                 self.cache_display_image();
@@ -528,7 +753,7 @@ impl cosmic::Application for GlowBerrySettings {
                     let entry = if self.config.same_on_all {
                         Some(&self.config.default_background)
                     } else if let Some(ref output_name) = self.active_output {
-                        self.config.entry(output_name)
+                        self.output_entry(output_name)
                     } else {
                         Some(&self.config.default_background)
                     };
@@ -593,11 +818,12 @@ impl cosmic::Application for GlowBerrySettings {
             Message::OutputChanged(entity) => {
                 self.outputs.activate(entity);
                 if let Some(name) = self.outputs.data::<OutputName>(entity) {
-                    self.active_output = Some(name.0.clone());
+                    let name = name.0.clone();
+                    self.active_output = Some(name.clone());
 
                     // Load the wallpaper for this specific output if it exists
-                    if let Some(entry) = self.config.entry(&name.0) {
-                        self.select_entry_source(&entry.source.clone());
+                    if let Some(source) = self.output_entry(&name).map(|e| e.source.clone()) {
+                        self.select_entry_source(&source);
                     }
                 }
                 self.cache_display_image();
@@ -618,10 +844,13 @@ impl cosmic::Application for GlowBerrySettings {
                         self.config = config;
                         self.init_from_config();
 
-                        // Update prefer_low_power from config
+                        // Update prefer_low_power and custom colors from config
                         if let Some(ctx) = &self.config_context {
                             self.prefer_low_power = ctx.prefer_low_power();
+                            self.custom_colors = ctx.custom_colors();
                         }
+                        self.load_power_saving();
+                        self.load_profiles();
 
                         // Re-cache display image if needed
                         if matches!(self.selection.active, Choice::Wallpaper(_)) {
@@ -659,6 +888,13 @@ impl cosmic::Application for GlowBerrySettings {
                 }
             }
 
+            Message::SetMirrorCosmicBg(value) => {
+                self.mirror_cosmic_bg = value;
+                if let Some(ctx) = &self.config_context {
+                    let _ = ctx.set_mirror_cosmic_bg(value);
+                }
+            }
+
             Message::ShaderParamChanged(shader_idx, param_name, value) => {
                 // Store the new value in memory only (don't write to config yet)
                 self.shader_param_values
@@ -748,6 +984,222 @@ impl cosmic::Application for GlowBerrySettings {
                     let _ = ctx.set_window_opacity(self.window_opacity);
                 }
             }
+
+            Message::ColorEditorKind(kind) => {
+                self.color_editor.set_kind(kind);
+            }
+
+            Message::ColorEditorChannel(channel, value) => {
+                self.color_editor.set_channel(channel, value);
+            }
+
+            Message::ColorEditorHex(hex) => {
+                self.color_editor.set_hex(hex);
+            }
+
+            Message::ColorEditorSelectStop(idx) => {
+                self.color_editor.select_stop(idx);
+            }
+
+            Message::ColorEditorAddStop => {
+                self.color_editor.add_stop();
+            }
+
+            Message::ColorEditorRemoveStop => {
+                self.color_editor.remove_stop();
+            }
+
+            Message::ColorEditorAngle(angle) => {
+                self.color_editor.angle = angle.clamp(0.0, 360.0);
+            }
+
+            Message::ColorEditorApply => {
+                self.selection.active = Choice::Color(self.color_editor.color());
+                self.cached_display_handle = None;
+                self.apply_selection();
+            }
+
+            Message::SaveCustomColor => {
+                let color = self.color_editor.color();
+                if !DEFAULT_COLORS.contains(&color) && !self.custom_colors.contains(&color) {
+                    self.custom_colors.push(color.clone());
+                    if let Some(ctx) = &self.config_context {
+                        if let Err(why) = ctx.set_custom_colors(&self.custom_colors) {
+                            tracing::error!(?why, "Failed to save custom colors");
+                        }
+                    }
+                }
+                self.selection.active = Choice::Color(color);
+                self.cached_display_handle = None;
+                self.apply_selection();
+            }
+
+            Message::RemoveCustomColor(color) => {
+                self.custom_colors.retain(|c| *c != color);
+                if let Some(ctx) = &self.config_context {
+                    if let Err(why) = ctx.set_custom_colors(&self.custom_colors) {
+                        tracing::error!(?why, "Failed to save custom colors");
+                    }
+                }
+            }
+
+            Message::SlideshowControl(command) => {
+                // Without a per-display configuration, every display is changed.
+                let output = if self.config.same_on_all {
+                    None
+                } else {
+                    self.active_output.clone()
+                };
+
+                return Task::perform(
+                    async move {
+                        control::send(command, output.as_deref())
+                            .await
+                            .map_err(|why| why.to_string())
+                    },
+                    |result| cosmic::Action::App(Message::SlideshowControlResult(result)),
+                );
+            }
+
+            Message::SlideshowControlResult(result) => {
+                if let Err(why) = result {
+                    tracing::error!("Failed to control slideshow: {}", why);
+                }
+            }
+
+            Message::ClearBlocklist => {
+                if let Some(ctx) = &self.config_context {
+                    if let Err(why) = ctx.set_blocklist(&[]) {
+                        tracing::error!(?why, "Failed to clear blocklist");
+                    } else {
+                        self.config.blocklist.clear();
+                    }
+                }
+            }
+
+            Message::SwitchProfile(idx) => {
+                if let (Some(ctx), Some(name)) = (&self.config_context, self.profile_names.get(idx))
+                {
+                    if let Err(why) = ctx.switch_profile(name) {
+                        tracing::error!(?why, "Failed to switch profile");
+                    } else {
+                        if let Ok(config) = Config::load(ctx) {
+                            self.config = config;
+                        }
+                        self.prefer_low_power = ctx.prefer_low_power();
+                        self.load_power_saving();
+                        self.load_profiles();
+                        self.populate_outputs_from_config();
+                        self.init_from_config();
+                    }
+                }
+            }
+
+            Message::ProfileNameInput(name) => {
+                self.profile_name = name;
+            }
+
+            Message::SaveProfile => {
+                let name = self.profile_name.trim().to_string();
+                if let Some(ctx) = &self.config_context
+                    && !name.is_empty()
+                {
+                    if let Err(why) = ctx.save_profile(&name) {
+                        tracing::error!(?why, "Failed to save profile");
+                    } else {
+                        self.profile_name.clear();
+                        self.load_profiles();
+                    }
+                }
+            }
+
+            Message::DeleteProfile => {
+                if let (Some(ctx), Some(name)) = (&self.config_context, self.active_profile_name())
+                {
+                    if let Err(why) = ctx.delete_profile(name) {
+                        tracing::error!(?why, "Failed to delete profile");
+                    }
+                    self.load_profiles();
+                }
+            }
+
+            Message::ExportProfile => {
+                let Some(profile) = self.config_context.as_ref().and_then(|ctx| {
+                    let name = self.active_profile_name()?;
+                    ctx.profiles()
+                        .ok()?
+                        .into_iter()
+                        .find(|profile| profile.name == name)
+                }) else {
+                    return Task::none();
+                };
+
+                let title = fl!("export-profile");
+                return Task::perform(
+                    async move {
+                        let response = file_chooser::save::Dialog::new()
+                            .title(title)
+                            .current_name(format!("{}.tar.gz", profile.name))
+                            .save_file()
+                            .await
+                            .map_err(|why| why.to_string())?;
+                        let Some(path) = response.url().and_then(|url| url.to_file_path().ok())
+                        else {
+                            return Ok(());
+                        };
+                        // Archiving reads every file of the profile.
+                        tokio::task::spawn_blocking(move || {
+                            profile_archive::export(&profile, &path)
+                        })
+                        .await
+                        .map_err(|why| why.to_string())?
+                        .map_err(|why| why.to_string())
+                    },
+                    |result| cosmic::Action::App(Message::ProfileExported(result)),
+                );
+            }
+
+            Message::ImportProfile => {
+                let title = fl!("import-profile");
+                let filter = FileFilter::new(fl!("profile-archive")).glob("*.tar.gz");
+                return Task::perform(
+                    async move {
+                        let response = file_chooser::open::Dialog::new()
+                            .title(title)
+                            .filter(filter)
+                            .open_file()
+                            .await
+                            .map_err(|why| why.to_string())?;
+                        let path = response
+                            .url()
+                            .to_file_path()
+                            .map_err(|()| "not a local file".to_string())?;
+                        tokio::task::spawn_blocking(move || profile_archive::import(&path, None))
+                            .await
+                            .map_err(|why| why.to_string())?
+                            .map_err(|why| why.to_string())
+                    },
+                    |result| cosmic::Action::App(Message::ProfileImported(result)),
+                );
+            }
+
+            Message::ProfileExported(result) => {
+                if let Err(why) = result {
+                    tracing::error!("Failed to export profile: {}", why);
+                }
+            }
+
+            Message::ProfileImported(result) => match result {
+                Ok(profile) => {
+                    if let Some(ctx) = &self.config_context {
+                        if let Err(why) = ctx.add_profile(profile) {
+                            tracing::error!(?why, "Failed to save imported profile");
+                        }
+                        self.load_profiles();
+                    }
+                }
+                Err(why) => tracing::error!("Failed to import profile: {}", why),
+            },
         }
 
         Task::none()
@@ -792,6 +1244,24 @@ impl cosmic::Application for GlowBerrySettings {
                 .into(),
         );
 
+        // Problems in the config, which otherwise only show up as unexpected backgrounds
+        if !self.config_diagnostics.is_empty() {
+            let section = self.config_diagnostics.iter().fold(
+                widget::settings::section().title(fl!("config-problems")),
+                |section, diagnostic| {
+                    section.add(widget::text(diagnostic.to_string()).size(12).class(
+                        cosmic::theme::Text::Color(cosmic::iced::Color::from_rgb(0.9, 0.6, 0.2)),
+                    ))
+                },
+            );
+            children.push(
+                container(section)
+                    .width(Length::Fill)
+                    .align_x(Alignment::Center)
+                    .into(),
+            );
+        }
+
         // 3. Category dropdown - centered
         let category_dropdown =
             dropdown::multi::dropdown(&self.categories, Message::ChangeCategory);
@@ -950,6 +1420,10 @@ impl GlowBerrySettings {
             .add(settings::item(
                 fl!("use-glowberry"),
                 toggler(self.glowberry_is_default).on_toggle(Message::SetGlowBerryDefault),
+            ))
+            .add(settings::item(
+                fl!("mirror-cosmic-bg"),
+                toggler(self.mirror_cosmic_bg).on_toggle(Message::SetMirrorCosmicBg),
             ));
 
         // Add PATH order warning if incorrect
@@ -980,7 +1454,56 @@ impl GlowBerrySettings {
                     .align_y(Alignment::Center),
                 ));
 
+        // Build profiles section
+        let mut profile_actions: Vec<Element<'_, Message>> = vec![
+            widget::button::standard(fl!("import-profile"))
+                .on_press(Message::ImportProfile)
+                .into(),
+        ];
+        if self.active_profile.is_some() {
+            profile_actions.push(
+                widget::button::standard(fl!("export-profile"))
+                    .on_press(Message::ExportProfile)
+                    .into(),
+            );
+            profile_actions.push(
+                widget::button::destructive(fl!("delete-profile"))
+                    .on_press(Message::DeleteProfile)
+                    .into(),
+            );
+        }
+
+        let profiles_section = widget::settings::section()
+            .title(fl!("profiles"))
+            .add(settings::item(
+                fl!("profile"),
+                dropdown(
+                    &self.profile_names,
+                    self.active_profile,
+                    Message::SwitchProfile,
+                ),
+            ))
+            .add(settings::item(
+                fl!("save-profile"),
+                widget::row::with_children(vec![
+                    widget::text_input(fl!("profile-name"), &self.profile_name)
+                        .on_input(Message::ProfileNameInput)
+                        .width(Length::Fixed(150.0))
+                        .into(),
+                    widget::button::suggested(fl!("save"))
+                        .on_press_maybe(
+                            (!self.profile_name.trim().is_empty()).then_some(Message::SaveProfile),
+                        )
+                        .into(),
+                ])
+                .spacing(8)
+                .align_y(Alignment::Center),
+            ))
+            .add(widget::row::with_children(profile_actions).spacing(8));
+
         widget::settings::view_column(vec![
+            // Profiles section
+            profiles_section.into(),
             // Default background service section
             bg_service_section.into(),
             // Appearance section
@@ -999,14 +1522,62 @@ impl GlowBerrySettings {
         .into()
     }
 
+    /// Load the power saving settings and their dropdown indices from config.
+    fn load_power_saving(&mut self) {
+        let Some(ctx) = &self.config_context else {
+            return;
+        };
+
+        self.power_saving = ctx.power_saving_config();
+        self.selected_on_battery_action = match self.power_saving.on_battery_action {
+            OnBatteryAction::Nothing => 0,
+            OnBatteryAction::Pause => 1,
+            OnBatteryAction::ReduceTo15Fps => 2,
+            OnBatteryAction::ReduceTo10Fps => 3,
+            OnBatteryAction::ReduceTo5Fps => 4,
+        };
+        self.selected_low_battery_threshold = match self.power_saving.low_battery_threshold {
+            10 => 0,
+            20 => 1,
+            30 => 2,
+            50 => 3,
+            _ => 1, // Default to 20%
+        };
+    }
+
+    /// Load the names of the saved profiles from config.
+    fn load_profiles(&mut self) {
+        let Some(ctx) = &self.config_context else {
+            return;
+        };
+
+        self.profile_names = match ctx.profiles() {
+            Ok(profiles) => profiles.into_iter().map(|profile| profile.name).collect(),
+            Err(why) => {
+                tracing::error!(?why, "Failed to load profiles");
+                Vec::new()
+            }
+        };
+        let active = ctx.active_profile();
+        self.active_profile = self
+            .profile_names
+            .iter()
+            .position(|name| Some(name) == active.as_ref());
+    }
+
+    fn active_profile_name(&self) -> Option<&str> {
+        self.active_profile
+            .and_then(|idx| self.profile_names.get(idx))
+            .map(String::as_str)
+    }
+
     fn init_from_config(&mut self) {
         // Determine which entry to use based on same_on_all and active_output
         let entry = if self.config.same_on_all {
             &self.config.default_background
         } else if let Some(ref output_name) = self.active_output {
             // Try to find a per-output entry
-            self.config
-                .entry(output_name)
+            self.output_entry(output_name)
                 .unwrap_or(&self.config.default_background)
         } else {
             &self.config.default_background
@@ -1101,6 +1672,11 @@ impl GlowBerrySettings {
                         background_image: None,
                         language: glowberry_config::ShaderLanguage::Wgsl,
                         frame_rate,
+                        clock: if self.shader_pauses_on_suspend {
+                            ShaderClock::Monotonic
+                        } else {
+                            ShaderClock::Boot
+                        },
                     })
                 } else {
                     return;
@@ -1117,7 +1693,13 @@ impl GlowBerrySettings {
             "all".to_string()
         };
 
-        let entry = Entry::new(output, source);
+        // An entry follows the monitor of the display, keeping the output it was saved for.
+        let monitor = self.monitors.get(&output).cloned();
+        let output = self
+            .output_entry(&output)
+            .map_or(output, |entry| entry.output.clone());
+
+        let entry = Entry::new(output, source).monitor(monitor);
         if let Err(e) = self.config.set_entry(ctx, entry) {
             tracing::error!("Failed to set wallpaper: {}", e);
         }
@@ -1139,6 +1721,12 @@ impl GlowBerrySettings {
                 self.selection.active = Choice::Color(color.clone());
                 self.categories.selected = Some(Category::Colors);
             }
+            Source::Playlist(items) => {
+                // Playlists are edited in the config file, the first item is selected here.
+                if let Some(item) = items.first() {
+                    self.select_entry_source(&item.source.clone());
+                }
+            }
             Source::Shader(shader_source) => {
                 // Determine which path to use for matching:
                 // - If source_path is set (customized shader), use that
@@ -1217,23 +1805,31 @@ impl GlowBerrySettings {
                     23..=45 => 1,
                     _ => 2,
                 };
+                self.shader_pauses_on_suspend = shader_source.clock == ShaderClock::Monotonic;
                 self.categories.selected = Some(Category::Shaders);
             }
         }
         self.cache_display_image();
     }
 
+    /// The per-display entry of `output`, following its monitor across outputs.
+    fn output_entry(&self, output: &str) -> Option<&Entry> {
+        self.config.entry_for(output, self.monitors.get(output))
+    }
+
     /// Populate the outputs tab bar from state (connected outputs)
     /// The daemon updates the state with currently connected outputs
     fn populate_outputs_from_config(&mut self) {
         self.outputs.clear();
 
         // Get connected outputs from state - these are the currently connected displays
-        let connected_outputs: Vec<String> = State::state()
+        let state = State::state()
             .ok()
             .and_then(|state_helper| State::get_entry(&state_helper).ok())
-            .map(|state| state.connected_outputs)
             .unwrap_or_default();
+        let connected_outputs = state.connected_outputs;
+        self.monitors = state.connected_monitors.into_iter().collect();
+        self.config_diagnostics = state.config_diagnostics;
 
         // If no connected outputs in state, fall back to config outputs
         // (This handles the case where daemon hasn't written state yet)
@@ -1249,12 +1845,14 @@ impl GlowBerrySettings {
         for name in output_names {
             let is_internal = name == "eDP-1";
 
-            // Use the output name directly (e.g., "DP-1", "HDMI-A-1", "eDP-1")
-            let entity = self
-                .outputs
-                .insert()
-                .text(name.clone())
-                .data(OutputName(name));
+            // Describe the monitor, keeping the output name (e.g., "DP-1") to tell
+            // identical monitors apart
+            let label = match self.monitors.get(&name) {
+                Some(monitor) => format!("{} ({name})", monitor.description()),
+                None => name.clone(),
+            };
+
+            let entity = self.outputs.insert().text(label).data(OutputName(name));
 
             if is_internal || first.is_none() {
                 first = Some(entity.id());
@@ -1394,6 +1992,44 @@ impl GlowBerrySettings {
             ));
         }
 
+        // Slideshow controls (only for directory sources)
+        let entry = if self.config.same_on_all {
+            &self.config.default_background
+        } else if let Some(ref output_name) = self.active_output {
+            self.output_entry(output_name)
+                .unwrap_or(&self.config.default_background)
+        } else {
+            &self.config.default_background
+        };
+
+        if matches!(&entry.source, Source::Path(path) if path.is_dir()) {
+            list = list.add(settings::item(
+                fl!("slideshow"),
+                widget::row::with_children(vec![
+                    widget::button::standard(fl!("slideshow-previous"))
+                        .on_press(Message::SlideshowControl(ControlCommand::Previous))
+                        .into(),
+                    widget::button::standard(fl!("slideshow-next"))
+                        .on_press(Message::SlideshowControl(ControlCommand::Next))
+                        .into(),
+                    widget::button::standard(fl!("slideshow-favorite"))
+                        .on_press(Message::SlideshowControl(ControlCommand::Favorite))
+                        .into(),
+                    widget::button::destructive(fl!("slideshow-block"))
+                        .on_press(Message::SlideshowControl(ControlCommand::Block))
+                        .into(),
+                ])
+                .spacing(8),
+            ));
+        }
+
+        if !self.config.blocklist.is_empty() {
+            list = list.add(settings::item(
+                fl!("blocked-images", count = self.config.blocklist.len()),
+                widget::button::standard(fl!("clear-blocklist")).on_press(Message::ClearBlocklist),
+            ));
+        }
+
         // Frame rate dropdown and shader parameters (only for shaders)
         if let Choice::Shader(shader_idx) = self.selection.active {
             // Frame rate is always visible
@@ -1406,6 +2042,11 @@ impl GlowBerrySettings {
                 ),
             ));
 
+            list = list.add(settings::item(
+                fl!("pause-on-suspend"),
+                toggler(self.shader_pauses_on_suspend).on_toggle(Message::ShaderPausesOnSuspend),
+            ));
+
             // Show Details button (centered, pull-down style with chevron icon)
             let (details_label, chevron_icon) = if self.shader_details_expanded {
                 (fl!("hide-details"), "go-up-symbolic")
@@ -1625,6 +2266,7 @@ impl GlowBerrySettings {
 
         let buttons: Vec<Element<'_, Message>> = DEFAULT_COLORS
             .iter()
+            .chain(&self.custom_colors)
             .map(|color| {
                 let content = color_image(color.clone(), 70, 70);
                 button::custom_image_button(content, None::<Message>)
@@ -1636,9 +2278,144 @@ impl GlowBerrySettings {
             })
             .collect();
 
-        widget::flex_row(buttons)
-            .column_spacing(12)
-            .row_spacing(16)
+        widget::column::with_children(vec![
+            widget::flex_row(buttons)
+                .column_spacing(12)
+                .row_spacing(16)
+                .into(),
+            self.view_color_editor(),
+        ])
+        .spacing(22)
+        .into()
+    }
+
+    /// Editor for creating custom colors and gradients
+    fn view_color_editor(&self) -> Element<'_, Message> {
+        let editor = &self.color_editor;
+        let mut section = widget::settings::section().title(fl!("custom-color"));
+
+        section = section.add(settings::item(
+            fl!("color-kind"),
+            dropdown(
+                &self.color_kind_options,
+                Some(editor.kind),
+                Message::ColorEditorKind,
+            ),
+        ));
+
+        // Gradient stops, angle, and stop management
+        if editor.is_gradient() {
+            let stops: Vec<Element<'_, Message>> = editor
+                .stops
+                .iter()
+                .enumerate()
+                .map(|(idx, rgb)| {
+                    button::custom_image_button(
+                        color_image(Color::Single(*rgb), 32, 32),
+                        None::<Message>,
+                    )
+                    .padding(0)
+                    .selected(idx == editor.active_stop)
+                    .class(button::ButtonClass::Image)
+                    .on_press(Message::ColorEditorSelectStop(idx))
+                    .into()
+                })
+                .collect();
+
+            let remove_stop = widget::button::standard(fl!("remove-stop"))
+                .on_press_maybe((editor.stops.len() > 2).then_some(Message::ColorEditorRemoveStop));
+
+            section = section
+                .add(settings::item(
+                    fl!("gradient-stops"),
+                    widget::row::with_children(stops).spacing(8),
+                ))
+                .add(
+                    widget::row::with_children(vec![
+                        widget::button::standard(fl!("add-stop"))
+                            .on_press(Message::ColorEditorAddStop)
+                            .into(),
+                        remove_stop.into(),
+                    ])
+                    .spacing(8),
+                );
+
+            if editor.kind == 1 {
+                section = section.add(settings::item(
+                    fl!("gradient-angle"),
+                    widget::row::with_children(vec![
+                        slider(0.0..=360.0, editor.angle, Message::ColorEditorAngle)
+                            .step(1.0)
+                            .width(Length::Fixed(150.0))
+                            .into(),
+                        widget::text(format!("{:.0}°", editor.angle))
+                            .width(Length::Fixed(50.0))
+                            .into(),
+                    ])
+                    .spacing(8)
+                    .align_y(Alignment::Center),
+                ));
+            }
+        }
+
+        // RGB sliders and hex input for the active stop
+        let active = editor.active();
+        for (channel, label) in [fl!("color-red"), fl!("color-green"), fl!("color-blue")]
+            .into_iter()
+            .enumerate()
+        {
+            section = section.add(settings::item(
+                label,
+                widget::row::with_children(vec![
+                    slider(0.0..=1.0, active[channel], move |v| {
+                        Message::ColorEditorChannel(channel, v)
+                    })
+                    .step(1.0 / 255.0)
+                    .width(Length::Fixed(150.0))
+                    .into(),
+                    widget::text(format!("{:.0}", active[channel] * 255.0))
+                        .width(Length::Fixed(50.0))
+                        .into(),
+                ])
+                .spacing(8)
+                .align_y(Alignment::Center),
+            ));
+        }
+
+        section = section.add(settings::item(
+            fl!("color-hex"),
+            widget::text_input("#000000", &editor.hex)
+                .on_input(Message::ColorEditorHex)
+                .width(Length::Fixed(208.0)),
+        ));
+
+        // Preview and actions
+        let mut actions: Vec<Element<'_, Message>> = vec![
+            color_image(editor.color(), 70, 70),
+            widget::button::standard(fl!("apply-color"))
+                .on_press(Message::ColorEditorApply)
+                .into(),
+            widget::button::suggested(fl!("save-color"))
+                .on_press(Message::SaveCustomColor)
+                .into(),
+        ];
+
+        if let Choice::Color(selected) = &self.selection.active {
+            if self.custom_colors.contains(selected) {
+                actions.push(
+                    widget::button::destructive(fl!("remove-color"))
+                        .on_press(Message::RemoveCustomColor(selected.clone()))
+                        .into(),
+                );
+            }
+        }
+
+        section
+            .add(
+                widget::row::with_children(actions)
+                    .spacing(12)
+                    .align_y(Alignment::Center),
+            )
             .into()
     }
 
@@ -1699,10 +2476,11 @@ fn color_image<'a, M: 'a>(color: Color, width: u16, height: u16) -> Element<'a,
                     Color::Single([r, g, b]) => {
                         Background::Color(cosmic::iced::Color::from_rgb(*r, *g, *b))
                     }
-                    Color::Gradient(Gradient { colors, radius }) => {
+                    // iced has no radial gradient, so radial previews use a linear one
+                    Color::Gradient(Gradient { colors, angle, .. }) => {
                         let stop_increment = 1.0 / (colors.len() - 1) as f32;
                         let mut stop = 0.0;
-                        let mut linear = Linear::new(Degrees(*radius));
+                        let mut linear = Linear::new(Degrees(*angle));
                         for &[r, g, b] in &**colors {
                             linear = linear.add_stop(stop, cosmic::iced::Color::from_rgb(r, g, b));
                             stop += stop_increment;
@@ -1720,6 +2498,28 @@ fn color_image<'a, M: 'a>(color: Color, width: u16, height: u16) -> Element<'a,
         .into()
 }
 
+/// Format an RGB color as a `#rrggbb` hex string.
+fn color_to_hex([r, g, b]: [f32; 3]) -> String {
+    let to_u8 = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", to_u8(r), to_u8(g), to_u8(b))
+}
+
+/// Parse a `#rrggbb` or `rrggbb` hex string into an RGB color.
+fn color_from_hex(hex: &str) -> Option<[f32; 3]> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |range: std::ops::Range<usize>| {
+        u8::from_str_radix(&hex[range], 16)
+            .ok()
+            .map(|c| f32::from(c) / 255.0)
+    };
+
+    Some([channel(0..2)?, channel(2..4)?, channel(4..6)?])
+}
+
 fn shader_placeholder<'a, M: 'a>(width: u16, height: u16) -> Element<'a, M> {
     use cosmic::iced_core::{Background, Degrees, gradient::Linear};
 
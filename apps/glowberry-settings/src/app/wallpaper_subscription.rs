@@ -27,16 +27,21 @@ pub enum WallpaperEvent {
 }
 
 /// Create a subscription that loads wallpapers from the given directory
-pub fn wallpapers(current_dir: PathBuf) -> Subscription<WallpaperEvent> {
-    Subscription::run_with_id(current_dir.clone(), async_stream(current_dir))
+///
+/// Images with more than `max_pixels` pixels are skipped, unless it is 0.
+pub fn wallpapers(current_dir: PathBuf, max_pixels: u64) -> Subscription<WallpaperEvent> {
+    Subscription::run_with_id(
+        (current_dir.clone(), max_pixels),
+        async_stream(current_dir, max_pixels),
+    )
 }
 
-fn async_stream(current_dir: PathBuf) -> impl Stream<Item = WallpaperEvent> {
-    futures_lite::stream::unfold(LoadState::Init(current_dir), |state| async move {
+fn async_stream(current_dir: PathBuf, max_pixels: u64) -> impl Stream<Item = WallpaperEvent> {
+    futures_lite::stream::unfold(LoadState::Init(current_dir), move |state| async move {
         match state {
             LoadState::Init(path) => Some((WallpaperEvent::Loading, LoadState::Loading(path))),
             LoadState::Loading(path) => {
-                let stream = load_wallpapers_from_path(path).await;
+                let stream = load_wallpapers_from_path(path, max_pixels).await;
                 // Get first item or signal done
                 let mut stream = stream;
                 if let Some((path, display, selection)) = stream.next().await {
@@ -81,6 +86,7 @@ enum LoadState {
 /// Load wallpapers from a directory
 async fn load_wallpapers_from_path(
     path: PathBuf,
+    max_pixels: u64,
 ) -> Pin<Box<dyn Send + Stream<Item = (PathBuf, RgbaImage, RgbaImage)>>> {
     let candidate_paths: Vec<_> = WalkDir::new(&path)
         .max_depth(3)
@@ -90,9 +96,9 @@ async fn load_wallpapers_from_path(
         .map(|entry| entry.path().to_path_buf())
         .collect();
 
-    let stream = futures_lite::stream::iter(candidate_paths).filter_map(|path| async move {
+    let stream = futures_lite::stream::iter(candidate_paths).filter_map(move |path| async move {
         if is_image_file(&path) {
-            load_image_with_thumbnail(path).await
+            load_image_with_thumbnail(path, max_pixels).await
         } else {
             None
         }
@@ -102,19 +108,14 @@ async fn load_wallpapers_from_path(
 }
 
 fn is_image_file(path: &PathBuf) -> bool {
-    let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
-        return false;
-    };
-
-    let ext_lower = ext.to_lowercase();
-    matches!(
-        ext_lower.as_str(),
-        "jpg" | "jpeg" | "png" | "webp" | "gif" | "bmp" | "jxl" | "avif"
-    )
+    glowberry_lib::loader::is_supported_image(path)
 }
 
-async fn load_image_with_thumbnail(path: PathBuf) -> Option<(PathBuf, RgbaImage, RgbaImage)> {
-    tokio::task::spawn_blocking(move || load_image_with_thumbnail_sync(&path))
+async fn load_image_with_thumbnail(
+    path: PathBuf,
+    max_pixels: u64,
+) -> Option<(PathBuf, RgbaImage, RgbaImage)> {
+    tokio::task::spawn_blocking(move || load_image_with_thumbnail_sync(&path, max_pixels))
         .await
         .ok()
         .flatten()
@@ -122,17 +123,14 @@ async fn load_image_with_thumbnail(path: PathBuf) -> Option<(PathBuf, RgbaImage,
 
 fn load_image_with_thumbnail_sync(
     path: &PathBuf,
+    max_pixels: u64,
 ) -> Option<(
     PathBuf,
     ImageBuffer<Rgba<u8>, Vec<u8>>,
     ImageBuffer<Rgba<u8>, Vec<u8>>,
 )> {
-    // Try to load the image
-    let image = if path.extension().is_some_and(|e| e == "jxl") {
-        decode_jpegxl(path).ok()?
-    } else {
-        image::open(path).ok()?
-    };
+    // Try to load the image, decoding only as much as the thumbnail needs
+    let image = glowberry_lib::loader::decode(path, Some((300, 169)), max_pixels).ok()?;
 
     // Create display thumbnail (300x169)
     let display_thumbnail = resize_thumbnail(&image, 300, 169);
@@ -174,15 +172,6 @@ fn resize_thumbnail(img: &image::DynamicImage, new_width: u32, new_height: u32)
     new_image.to_rgba8()
 }
 
-fn decode_jpegxl(path: &PathBuf) -> eyre::Result<image::DynamicImage> {
-    use jxl_oxide::integration::JxlDecoder;
-    use std::fs::File;
-
-    let file = File::open(path)?;
-    let decoder = JxlDecoder::new(file)?;
-    Ok(image::DynamicImage::from_decoder(decoder)?)
-}
-
 // Rounded corner implementation from cosmic-settings-wallpaper
 fn round(img: &mut RgbaImage, radius: [u32; 4]) {
     let (width, height) = img.dimensions();
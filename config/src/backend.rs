@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Storage of config keys.
+//!
+//! On COSMIC the config is stored in cosmic-config. Other compositors have no
+//! COSMIC settings daemon, so GlowBerry runs as a standalone wallpaper daemon
+//! there and stores each key as a RON file in `$XDG_CONFIG_HOME/glowberry/`,
+//! with the same keys and values.
+//!
+//! [`BACKEND_VAR`] overrides the choice. Every process of a session must agree
+//! on it, so it never depends on files that one of them might create.
+
+use cosmic_config::{Config as CosmicConfig, ConfigGet, ConfigSet};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Extension of the config files of the file backend.
+const EXTENSION: &str = "ron";
+
+/// Environment variable choosing where the config is stored: `cosmic` or `files`.
+pub const BACKEND_VAR: &str = "GLOWBERRY_CONFIG_BACKEND";
+
+/// Where the config of a session is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackendKind {
+    Cosmic,
+    Files,
+}
+
+impl BackendKind {
+    /// The backend named by [`BACKEND_VAR`], or else the one of the session:
+    /// cosmic-config on COSMIC and the files elsewhere.
+    ///
+    /// `var` reads environment variables.
+    pub fn select(var: impl Fn(&str) -> Option<OsString>) -> Self {
+        match var(BACKEND_VAR).as_ref().and_then(|value| value.to_str()) {
+            Some("cosmic") => return Self::Cosmic,
+            Some("files") => return Self::Files,
+            Some(value) => tracing::warn!(value, "unknown {BACKEND_VAR}, expected cosmic or files"),
+            None => {}
+        }
+
+        if on_cosmic(&var) {
+            Self::Cosmic
+        } else {
+            Self::Files
+        }
+    }
+}
+
+/// Whether the session is a COSMIC desktop.
+fn on_cosmic(var: impl Fn(&str) -> Option<OsString>) -> bool {
+    var("XDG_CURRENT_DESKTOP")
+        .and_then(|desktops| desktops.into_string().ok())
+        .is_some_and(|desktops| {
+            desktops
+                .split(':')
+                .any(|desktop| desktop.eq_ignore_ascii_case("cosmic"))
+        })
+}
+
+/// Storage of config values by key.
+pub trait ConfigBackend {
+    /// Get the value of `key`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`cosmic_config::Error::NotFound`] if the key is unset, or
+    /// with another error if it fails to parse.
+    fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T, cosmic_config::Error>;
+
+    /// Set the value of `key`.
+    fn set<T: Serialize>(&self, key: &str, value: T) -> Result<(), cosmic_config::Error>;
+}
+
+impl ConfigBackend for CosmicConfig {
+    fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T, cosmic_config::Error> {
+        ConfigGet::get(self, key)
+    }
+
+    fn set<T: Serialize>(&self, key: &str, value: T) -> Result<(), cosmic_config::Error> {
+        ConfigSet::set(self, key, value)
+    }
+}
+
+/// Config stored as one RON file per key in a directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileConfig {
+    dir: PathBuf,
+    /// Whether the directory is created when a value is set
+    create_dir: bool,
+}
+
+impl FileConfig {
+    #[must_use]
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            create_dir: true,
+        }
+    }
+
+    /// The config in `$XDG_CONFIG_HOME/glowberry/`, if [`BackendKind::select`] chooses it.
+    ///
+    /// On COSMIC the directory is never created, so it only holds files the user
+    /// put there on purpose. `var` reads environment variables.
+    #[must_use]
+    pub fn standalone(var: impl Fn(&str) -> Option<OsString>) -> Option<Self> {
+        if BackendKind::select(&var) != BackendKind::Files {
+            return None;
+        }
+
+        let config_home = var("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(Self {
+            dir: config_home.join("glowberry"),
+            create_dir: !on_cosmic(&var),
+        })
+    }
+
+    /// The directory holding the config files.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Create the directory holding the config files, unless it must not be created.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the directory doesn't exist and can't or must not be created.
+    pub fn create_dir(&self) -> io::Result<()> {
+        if self.create_dir {
+            fs::create_dir_all(&self.dir)
+        } else if self.dir.is_dir() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} doesn't exist", self.dir.display()),
+            ))
+        }
+    }
+
+    /// The file storing the value of `key`.
+    #[must_use]
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.{EXTENSION}"))
+    }
+
+    /// The key stored in the file at `path`, if it is a config file.
+    #[must_use]
+    pub fn key(&self, path: &Path) -> Option<String> {
+        if path.parent() != Some(self.dir.as_path())
+            || path.extension().is_none_or(|ext| ext != EXTENSION)
+        {
+            return None;
+        }
+
+        path.file_stem()?.to_str().map(str::to_string)
+    }
+}
+
+impl ConfigBackend for FileConfig {
+    fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T, cosmic_config::Error> {
+        let path = self.path(key);
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(why) if why.kind() == io::ErrorKind::NotFound => {
+                return Err(cosmic_config::Error::NotFound);
+            }
+            Err(why) => return Err(cosmic_config::Error::Io(why)),
+        };
+
+        ron::from_str(&data).map_err(|why| {
+            cosmic_config::Error::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {why}", path.display()),
+            ))
+        })
+    }
+
+    fn set<T: Serialize>(&self, key: &str, value: T) -> Result<(), cosmic_config::Error> {
+        let data = ron::ser::to_string_pretty(&value, ron::ser::PrettyConfig::default()).map_err(
+            |why| cosmic_config::Error::Io(io::Error::new(io::ErrorKind::InvalidData, why)),
+        )?;
+
+        // Written to a temporary file first, so that readers never see a partial value.
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{EXTENSION}.tmp"));
+        self.create_dir()
+            .and_then(|()| fs::write(&tmp, data))
+            .and_then(|()| fs::rename(&tmp, &path))
+            .map_err(cosmic_config::Error::Io)
+    }
+}
+
+/// Where the GlowBerry config is stored.
+#[derive(Clone, Debug)]
+pub enum Backend {
+    Cosmic(CosmicConfig),
+    File(FileConfig),
+}
+
+impl ConfigBackend for Backend {
+    fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T, cosmic_config::Error> {
+        match self {
+            Self::Cosmic(config) => ConfigBackend::get(config, key),
+            Self::File(config) => config.get(key),
+        }
+    }
+
+    fn set<T: Serialize>(&self, key: &str, value: T) -> Result<(), cosmic_config::Error> {
+        match self {
+            Self::Cosmic(config) => ConfigBackend::set(config, key, value),
+            Self::File(config) => config.set(key, value),
+        }
+    }
+}
@@ -0,0 +1,451 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Sharing backgrounds with the cosmic-bg config.
+//!
+//! cosmic-bg stores the same keys in its own namespace, so switching to
+//! GlowBerry would otherwise lose the configured wallpapers. They are imported
+//! once while GlowBerry's config is empty. In mirror mode, backgrounds that
+//! cosmic-bg can show are written back, so disabling GlowBerry keeps them.
+
+use cosmic_config::Config as CosmicConfig;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, path::PathBuf};
+
+use crate::{
+    BACKGROUNDS, Color, Context, DEFAULT_BACKGROUND, Entry, FilterMethod, GradientKind,
+    SAME_ON_ALL, SamplingMethod, ScalingMode, Source,
+    backend::{Backend, ConfigBackend},
+    migrations::v1,
+};
+
+/// cosmic-bg config namespace
+pub const NAME: &str = "com.system76.CosmicBackground";
+pub const MIRROR_COSMIC_BG: &str = "mirror-cosmic-bg";
+
+/// Create a context to the cosmic-bg config.
+///
+/// # Errors
+///
+/// Fails if config paths are missing or cannot be created.
+pub fn context() -> Result<Context, cosmic_config::Error> {
+    CosmicConfig::new(NAME, 1).map(|config| Context(Backend::Cosmic(config)))
+}
+
+/// A background in the schema of cosmic-bg, which rejects GlowBerry's additional fields.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+struct MirroredEntry {
+    output: String,
+    source: MirroredSource,
+    filter_by_theme: bool,
+    rotation_frequency: u64,
+    filter_method: FilterMethod,
+    scaling_mode: ScalingMode,
+    sampling_method: MirroredSamplingMethod,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+enum MirroredSource {
+    Path(PathBuf),
+    Color(MirroredColor),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+enum MirroredColor {
+    Single([f32; 3]),
+    Gradient(MirroredGradient),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+struct MirroredGradient {
+    colors: Cow<'static, [[f32; 3]]>,
+    radius: f32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+enum MirroredSamplingMethod {
+    Alphanumeric,
+    Random,
+}
+
+impl MirroredEntry {
+    /// The background as cosmic-bg shows it, or `None` for shaders, playlists
+    /// and radial gradients, which cosmic-bg can't show.
+    fn new(entry: &Entry) -> Option<Self> {
+        let source = match &entry.source {
+            Source::Path(path) => MirroredSource::Path(path.clone()),
+            Source::Color(Color::Single(rgb)) => MirroredSource::Color(MirroredColor::Single(*rgb)),
+            Source::Color(Color::Gradient(gradient)) if gradient.kind == GradientKind::Linear => {
+                MirroredSource::Color(MirroredColor::Gradient(MirroredGradient {
+                    colors: gradient.colors.clone(),
+                    radius: gradient.angle,
+                }))
+            }
+            Source::Color(Color::Gradient(_)) | Source::Shader(_) | Source::Playlist(_) => {
+                return None;
+            }
+        };
+
+        // Date based orderings fall back to the order of file names.
+        let sampling_method = match entry.sampling_method {
+            SamplingMethod::Random | SamplingMethod::Shuffle | SamplingMethod::Weighted => {
+                MirroredSamplingMethod::Random
+            }
+            SamplingMethod::Alphanumeric
+            | SamplingMethod::Modified(_)
+            | SamplingMethod::DateTaken(_) => MirroredSamplingMethod::Alphanumeric,
+        };
+
+        Some(Self {
+            output: entry.output.clone(),
+            source,
+            filter_by_theme: entry.filter_by_theme,
+            rotation_frequency: entry.rotation_frequency,
+            filter_method: entry.filter_method.clone(),
+            scaling_mode: entry.scaling_mode.clone(),
+            sampling_method,
+        })
+    }
+}
+
+impl Context {
+    /// Whether backgrounds are written back to the cosmic-bg config.
+    #[must_use]
+    pub fn mirror_cosmic_bg(&self) -> bool {
+        self.0.get::<bool>(MIRROR_COSMIC_BG).unwrap_or(false)
+    }
+
+    /// Set whether backgrounds are written back to the cosmic-bg config.
+    pub fn set_mirror_cosmic_bg(&self, value: bool) -> Result<(), cosmic_config::Error> {
+        if self.mirror_cosmic_bg() != value {
+            return self.0.set(MIRROR_COSMIC_BG, value);
+        }
+        Ok(())
+    }
+
+    /// Whether no background has been configured yet.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        let default_background = self.0.get::<Entry>(DEFAULT_BACKGROUND);
+        matches!(default_background, Err(why) if !why.is_err()) && self.backgrounds().is_empty()
+    }
+
+    /// Copy the backgrounds of the cosmic-bg config at `cosmic_bg`.
+    ///
+    /// cosmic-bg names the angle of a gradient `radius`, as GlowBerry did before
+    /// schema version 2, so its backgrounds are read in that format.
+    ///
+    /// Returns `false` if cosmic-bg has no background configured either.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the config could not be set in cosmic-config.
+    pub fn import_cosmic_bg(&self, cosmic_bg: &Context) -> Result<bool, cosmic_config::Error> {
+        let Ok(default_background) = cosmic_bg.cosmic_bg_entry(DEFAULT_BACKGROUND) else {
+            return Ok(false);
+        };
+
+        let mut outputs = Vec::new();
+        for output in cosmic_bg.backgrounds() {
+            let key = ["output.", &output].concat();
+            match cosmic_bg.cosmic_bg_entry(&key) {
+                Ok(entry) => {
+                    self.0.set(&key, entry)?;
+                    outputs.push(output);
+                }
+                Err(why) => tracing::warn!(?why, key, "skipping cosmic-bg background"),
+            }
+        }
+
+        self.0.set(BACKGROUNDS, outputs)?;
+        self.0.set(DEFAULT_BACKGROUND, default_background)?;
+        if let Ok(same_on_all) = cosmic_bg.0.get::<bool>(SAME_ON_ALL) {
+            self.0.set(SAME_ON_ALL, same_on_all)?;
+        }
+
+        Ok(true)
+    }
+
+    fn cosmic_bg_entry(&self, key: &str) -> Result<Entry, cosmic_config::Error> {
+        self.0.get::<v1::Entry>(key).map(Entry::from)
+    }
+
+    /// Write the backgrounds that cosmic-bg can show to the cosmic-bg config at `cosmic_bg`.
+    ///
+    /// Backgrounds that cosmic-bg can't show, such as shaders, leave its previous choice in place.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the config could not be set in cosmic-config.
+    pub fn mirror_to_cosmic_bg(&self, cosmic_bg: &Context) -> Result<(), cosmic_config::Error> {
+        let set = |key: &str, entry: MirroredEntry| {
+            if cosmic_bg.0.get::<MirroredEntry>(key).ok().as_ref() != Some(&entry) {
+                cosmic_bg.0.set(key, entry)?;
+            }
+            Ok(())
+        };
+
+        if let Some(entry) = self
+            .entry(DEFAULT_BACKGROUND)
+            .ok()
+            .and_then(|entry| MirroredEntry::new(&entry))
+        {
+            set(DEFAULT_BACKGROUND, entry)?;
+        }
+
+        let mut outputs = cosmic_bg.backgrounds();
+        for output in self.backgrounds() {
+            let key = ["output.", &output].concat();
+            if let Some(entry) = self
+                .entry(&key)
+                .ok()
+                .and_then(|entry| MirroredEntry::new(&entry))
+            {
+                set(&key, entry)?;
+                if !outputs.contains(&output) {
+                    outputs.push(output);
+                }
+            }
+        }
+
+        if cosmic_bg.backgrounds() != outputs {
+            cosmic_bg.0.set(BACKGROUNDS, outputs)?;
+        }
+
+        let same_on_all = self.same_on_all();
+        if cosmic_bg.0.get::<bool>(SAME_ON_ALL).ok() != Some(same_on_all) {
+            cosmic_bg.0.set(SAME_ON_ALL, same_on_all)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DateOrder, Gradient, PlaylistItem, ShaderClock, ShaderContent, ShaderLanguage,
+        ShaderSource, backend::FileConfig,
+    };
+    use std::fs;
+
+    fn gradient(kind: GradientKind) -> Source {
+        Source::Color(Color::Gradient(Gradient {
+            colors: Cow::Owned(vec![[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]),
+            angle: 45.0,
+            kind,
+        }))
+    }
+
+    fn shader() -> Source {
+        Source::Shader(ShaderSource {
+            shader: ShaderContent::Code(String::from("fn main() {}")),
+            source_path: None,
+            params: std::collections::HashMap::new(),
+            background_image: None,
+            language: ShaderLanguage::Wgsl,
+            frame_rate: 30,
+            clock: ShaderClock::default(),
+        })
+    }
+
+    fn entry(output: &str, source: Source, sampling_method: SamplingMethod) -> Entry {
+        let mut entry = Entry::new(String::from(output), source);
+        entry.sampling_method = sampling_method;
+        entry
+    }
+
+    /// A context backed by files in a fresh directory named after `name`.
+    fn context(name: &str) -> (Context, FileConfig) {
+        let dir = std::env::temp_dir().join(format!("glowberry-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let files = FileConfig::new(dir);
+        (Context(Backend::File(files.clone())), files)
+    }
+
+    #[test]
+    fn mirrors_only_what_cosmic_bg_can_show() {
+        let walls = Source::Path(PathBuf::from("/walls"));
+        let mirrored = |source: &Source, sampling_method: SamplingMethod| {
+            MirroredEntry::new(&entry("DP-1", source.clone(), sampling_method))
+        };
+
+        for sampling_method in [SamplingMethod::Shuffle, SamplingMethod::Weighted] {
+            let entry = mirrored(&walls, sampling_method).unwrap();
+            assert_eq!(entry.sampling_method, MirroredSamplingMethod::Random);
+        }
+
+        for sampling_method in [
+            SamplingMethod::Modified(DateOrder::NewestFirst),
+            SamplingMethod::DateTaken(DateOrder::OldestFirst),
+        ] {
+            let entry = mirrored(&walls, sampling_method).unwrap();
+            assert_eq!(entry.sampling_method, MirroredSamplingMethod::Alphanumeric);
+        }
+
+        let linear = mirrored(
+            &gradient(GradientKind::Linear),
+            SamplingMethod::Alphanumeric,
+        );
+        assert!(matches!(
+            linear.unwrap().source,
+            MirroredSource::Color(MirroredColor::Gradient(MirroredGradient {
+                radius: 45.0,
+                ..
+            }))
+        ));
+
+        let playlist = Source::Playlist(vec![PlaylistItem {
+            source: walls.clone(),
+            duration: None,
+        }]);
+        for source in [gradient(GradientKind::Radial), shader(), playlist] {
+            assert_eq!(mirrored(&source, SamplingMethod::Alphanumeric), None);
+        }
+    }
+
+    #[test]
+    fn imports_cosmic_bg_backgrounds() {
+        let (glowberry, glowberry_files) = context("import");
+        let (cosmic_bg, cosmic_bg_files) = context("import-cosmic-bg");
+
+        // Nothing to import yet.
+        assert!(!glowberry.import_cosmic_bg(&cosmic_bg).unwrap());
+
+        // cosmic-bg still names the angle of gradients `radius`.
+        cosmic_bg_files.create_dir().unwrap();
+        fs::write(
+            cosmic_bg_files.path(DEFAULT_BACKGROUND),
+            "(output: \"all\", source: Color(Gradient((colors: [(1.0, 0.0, 0.0), (0.0, 0.0, 1.0)], \
+             radius: 45.0))), filter_by_theme: false, rotation_frequency: 300)",
+        )
+        .unwrap();
+        cosmic_bg
+            .0
+            .set(
+                "output.DP-1",
+                MirroredEntry::new(&entry(
+                    "DP-1",
+                    Source::Path(PathBuf::from("/walls")),
+                    SamplingMethod::Random,
+                ))
+                .unwrap(),
+            )
+            .unwrap();
+        cosmic_bg.0.set(BACKGROUNDS, vec!["DP-1"]).unwrap();
+        cosmic_bg.0.set(SAME_ON_ALL, false).unwrap();
+
+        assert!(glowberry.import_cosmic_bg(&cosmic_bg).unwrap());
+        assert_eq!(
+            glowberry.entry(DEFAULT_BACKGROUND).unwrap().source,
+            gradient(GradientKind::Linear)
+        );
+        assert_eq!(glowberry.backgrounds(), ["DP-1"]);
+        let output = glowberry.entry("output.DP-1").unwrap();
+        assert_eq!(output.source, Source::Path(PathBuf::from("/walls")));
+        assert_eq!(output.sampling_method, SamplingMethod::Random);
+        assert!(!glowberry.same_on_all());
+
+        let _ = fs::remove_dir_all(glowberry_files.dir());
+        let _ = fs::remove_dir_all(cosmic_bg_files.dir());
+    }
+
+    #[test]
+    fn mirrors_backgrounds_to_cosmic_bg_and_back() {
+        let (glowberry, glowberry_files) = context("mirror");
+        let (cosmic_bg, cosmic_bg_files) = context("mirror-cosmic-bg");
+
+        let walls = Source::Path(PathBuf::from("/walls"));
+        let set = |key: &str, entry: Entry| glowberry.0.set(key, entry).unwrap();
+        set(
+            DEFAULT_BACKGROUND,
+            entry(
+                "all",
+                gradient(GradientKind::Linear),
+                SamplingMethod::Alphanumeric,
+            ),
+        );
+        set(
+            "output.DP-1",
+            entry("DP-1", shader(), SamplingMethod::Alphanumeric),
+        );
+        set(
+            "output.DP-2",
+            entry("DP-2", walls.clone(), SamplingMethod::Shuffle),
+        );
+        set(
+            "output.DP-3",
+            entry(
+                "DP-3",
+                walls.clone(),
+                SamplingMethod::DateTaken(DateOrder::NewestFirst),
+            ),
+        );
+        set(
+            "output.HDMI-A-1",
+            entry(
+                "HDMI-A-1",
+                gradient(GradientKind::Radial),
+                SamplingMethod::Alphanumeric,
+            ),
+        );
+        glowberry
+            .0
+            .set(BACKGROUNDS, vec!["DP-1", "DP-2", "DP-3", "HDMI-A-1"])
+            .unwrap();
+        glowberry.0.set(SAME_ON_ALL, false).unwrap();
+
+        // cosmic-bg keeps showing its own choice on the output with a shader.
+        let previous = MirroredEntry::new(&entry(
+            "DP-1",
+            Source::Path(PathBuf::from("/previous")),
+            SamplingMethod::Alphanumeric,
+        ))
+        .unwrap();
+        cosmic_bg.0.set("output.DP-1", previous.clone()).unwrap();
+        cosmic_bg.0.set(BACKGROUNDS, vec!["DP-1"]).unwrap();
+
+        glowberry.mirror_to_cosmic_bg(&cosmic_bg).unwrap();
+
+        assert_eq!(
+            cosmic_bg.0.get::<MirroredEntry>("output.DP-1").unwrap(),
+            previous
+        );
+        assert_eq!(cosmic_bg.backgrounds(), ["DP-1", "DP-2", "DP-3"]);
+        assert!(!cosmic_bg.same_on_all());
+        let sampling_method = |key: &str| {
+            cosmic_bg
+                .0
+                .get::<MirroredEntry>(key)
+                .unwrap()
+                .sampling_method
+        };
+        assert_eq!(
+            sampling_method("output.DP-2"),
+            MirroredSamplingMethod::Random
+        );
+        assert_eq!(
+            sampling_method("output.DP-3"),
+            MirroredSamplingMethod::Alphanumeric
+        );
+
+        // The mirrored backgrounds read back as they were, less what cosmic-bg lacks.
+        let (imported, imported_files) = context("mirror-imported");
+        assert!(imported.import_cosmic_bg(&cosmic_bg).unwrap());
+        assert_eq!(
+            imported.entry(DEFAULT_BACKGROUND).unwrap(),
+            glowberry.entry(DEFAULT_BACKGROUND).unwrap()
+        );
+        let output = imported.entry("output.DP-2").unwrap();
+        assert_eq!(output.source, walls);
+        assert_eq!(output.sampling_method, SamplingMethod::Random);
+        assert_eq!(
+            imported.entry("output.DP-3").unwrap().sampling_method,
+            SamplingMethod::Alphanumeric
+        );
+
+        for files in [glowberry_files, cosmic_bg_files, imported_files] {
+            let _ = fs::remove_dir_all(files.dir());
+        }
+    }
+}
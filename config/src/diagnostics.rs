@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Problems found in the GlowBerry configuration.
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::PathBuf};
+
+/// A problem with the value of a config key.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Diagnostic {
+    /// The config key, such as `all` or `output.DP-1`
+    pub key: String,
+    pub problem: Problem,
+}
+
+/// What is wrong with a config value.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum Problem {
+    /// The value failed to parse, so a default is used instead.
+    Parse(String),
+    /// A referenced file or directory doesn't exist.
+    MissingPath(PathBuf),
+    /// A shader failed to compile.
+    InvalidShader {
+        /// Path of the shader, or `None` for inline code
+        path: Option<PathBuf>,
+        error: String,
+    },
+    /// A value is outside of the range the daemon accepts.
+    OutOfRange {
+        field: String,
+        value: String,
+        expected: String,
+    },
+}
+
+impl Diagnostic {
+    #[must_use]
+    pub fn new(key: impl Into<String>, problem: Problem) -> Self {
+        Self {
+            key: key.into(),
+            problem,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.problem)
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(error) => write!(f, "failed to parse, using the default: {error}"),
+            Self::MissingPath(path) => write!(f, "{} does not exist", path.display()),
+            Self::InvalidShader {
+                path: Some(path),
+                error,
+            } => write!(f, "invalid shader {}: {error}", path.display()),
+            Self::InvalidShader { path: None, error } => write!(f, "invalid shader: {error}"),
+            Self::OutOfRange {
+                field,
+                value,
+                expected,
+            } => write!(f, "{field} is {value}, expected {expected}"),
+        }
+    }
+}
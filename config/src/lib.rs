@@ -1,12 +1,19 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod backend;
+pub mod cosmic_bg;
+pub mod diagnostics;
+pub mod migrations;
 pub mod power_saving;
+pub mod profiles;
+pub mod rules;
 pub mod state;
 
-use cosmic_config::{Config as CosmicConfig, ConfigGet, ConfigSet};
+use backend::{Backend, ConfigBackend, FileConfig};
+use cosmic_config::Config as CosmicConfig;
 use derive_setters::Setters;
 use serde::{Deserialize, Serialize};
-use std::{borrow::Cow, collections::HashSet, path::PathBuf};
+use std::{borrow::Cow, collections::HashSet, ffi::OsString, path::PathBuf};
 use thiserror::Error;
 
 /// GlowBerry config namespace
@@ -16,25 +23,54 @@ pub const DEFAULT_BACKGROUND: &str = "all";
 pub const SAME_ON_ALL: &str = "same-on-all";
 pub const PREFER_LOW_POWER: &str = "prefer-low-power";
 pub const WINDOW_OPACITY: &str = "window-opacity";
+pub const CUSTOM_COLORS: &str = "custom-colors";
+pub const MAX_IMAGE_PIXELS: &str = "max-image-pixels";
+pub const BLOCKLIST: &str = "blocklist";
+pub const FAVORITES: &str = "favorites";
+pub const GPU_GRADIENTS: &str = "gpu-gradients";
+
+/// Default limit on the pixel count of wallpaper images (256 megapixels).
+pub const DEFAULT_MAX_IMAGE_PIXELS: u64 = 256_000_000;
 
 /// Errors that can occur during config operations
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("config error: {0}")]
     Config(#[from] cosmic_config::Error),
+    #[error("no profile named {0}")]
+    UnknownProfile(String),
+    #[error(
+        "config schema version {0} is newer than version {supported} of this build",
+        supported = migrations::SCHEMA_VERSION
+    )]
+    NewerSchema(u32),
+    #[error("failed to back up the config: {0}")]
+    Backup(#[source] std::io::Error),
 }
 
 /// Create a context to the GlowBerry config.
 ///
+/// Uses the plain files in `$XDG_CONFIG_HOME/glowberry/` outside of COSMIC and
+/// cosmic-config on COSMIC, unless [`backend::BACKEND_VAR`] chooses otherwise.
+///
 /// # Errors
 ///
 /// Fails if config paths are missing or cannot be created.
 pub fn context() -> Result<Context, cosmic_config::Error> {
-    CosmicConfig::new(NAME, 1).map(Context)
+    context_from(|key| std::env::var_os(key))
+}
+
+/// Create a context to the config chosen by the environment variables that `var` reads.
+fn context_from(var: impl Fn(&str) -> Option<OsString>) -> Result<Context, cosmic_config::Error> {
+    if let Some(files) = FileConfig::standalone(var) {
+        return Ok(Context(Backend::File(files)));
+    }
+
+    CosmicConfig::new(NAME, 1).map(|config| Context(Backend::Cosmic(config)))
 }
 
 #[derive(Clone, Debug)]
-pub struct Context(pub CosmicConfig);
+pub struct Context(pub Backend);
 
 impl Context {
     /// Get all stored backgrounds from cosmic-config.
@@ -117,6 +153,78 @@ impl Context {
         }
         Ok(())
     }
+
+    /// Get the user's custom color and gradient swatches.
+    /// Returns an empty vector if none have been saved yet.
+    #[must_use]
+    pub fn custom_colors(&self) -> Vec<Color> {
+        self.0.get::<Vec<Color>>(CUSTOM_COLORS).unwrap_or_default()
+    }
+
+    /// Set the user's custom color and gradient swatches.
+    pub fn set_custom_colors(&self, colors: &[Color]) -> Result<(), cosmic_config::Error> {
+        if self.custom_colors() != colors {
+            return self.0.set(CUSTOM_COLORS, colors);
+        }
+        Ok(())
+    }
+
+    /// Get the largest pixel count of images the daemon will decode.
+    /// Larger images are rejected before decoding. A value of 0 disables the limit.
+    #[must_use]
+    pub fn max_image_pixels(&self) -> u64 {
+        self.0
+            .get::<u64>(MAX_IMAGE_PIXELS)
+            .unwrap_or(DEFAULT_MAX_IMAGE_PIXELS)
+    }
+
+    /// Set the largest pixel count of images the daemon will decode.
+    pub fn set_max_image_pixels(&self, value: u64) -> Result<(), cosmic_config::Error> {
+        if self.max_image_pixels() != value {
+            return self.0.set(MAX_IMAGE_PIXELS, value);
+        }
+        Ok(())
+    }
+
+    /// Images that are never shown in slideshows.
+    pub fn blocklist(&self) -> Vec<PathBuf> {
+        self.0.get::<Vec<PathBuf>>(BLOCKLIST).unwrap_or_default()
+    }
+
+    /// Set the images that are never shown in slideshows.
+    pub fn set_blocklist(&self, paths: &[PathBuf]) -> Result<(), cosmic_config::Error> {
+        if self.blocklist() != paths {
+            return self.0.set(BLOCKLIST, paths);
+        }
+        Ok(())
+    }
+
+    /// Whether gradients are rendered by the GPU when one is available.
+    #[must_use]
+    pub fn gpu_gradients(&self) -> bool {
+        self.0.get::<bool>(GPU_GRADIENTS).unwrap_or(false)
+    }
+
+    /// Set whether gradients are rendered by the GPU when one is available.
+    pub fn set_gpu_gradients(&self, value: bool) -> Result<(), cosmic_config::Error> {
+        if self.gpu_gradients() != value {
+            return self.0.set(GPU_GRADIENTS, value);
+        }
+        Ok(())
+    }
+
+    /// Images that are shown more often by the weighted sampling method.
+    pub fn favorites(&self) -> Vec<PathBuf> {
+        self.0.get::<Vec<PathBuf>>(FAVORITES).unwrap_or_default()
+    }
+
+    /// Set the images that are shown more often by the weighted sampling method.
+    pub fn set_favorites(&self, paths: &[PathBuf]) -> Result<(), cosmic_config::Error> {
+        if self.favorites() != paths {
+            return self.0.set(FAVORITES, paths);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Setters)]
@@ -126,6 +234,9 @@ pub struct Entry {
     /// the configured output
     #[setters(skip)]
     pub output: String,
+    /// the monitor the entry follows to any output, matching `output` by name if unset
+    #[serde(default)]
+    pub monitor: Option<Monitor>,
     /// the configured image source
     #[setters(skip)]
     pub source: Source,
@@ -133,6 +244,9 @@ pub struct Entry {
     pub filter_by_theme: bool,
     /// frequency at which the wallpaper is rotated in seconds
     pub rotation_frequency: u64,
+    /// when the wallpaper is rotated, defaulting to every `rotation_frequency` seconds
+    #[serde(default)]
+    pub rotation_schedule: RotationSchedule,
     /// filter used to scale images
     #[serde(default)]
     pub filter_method: FilterMethod,
@@ -141,6 +255,46 @@ pub struct Entry {
     pub scaling_mode: ScalingMode,
     #[serde(default)]
     pub sampling_method: SamplingMethod,
+    /// levels of subdirectories to search for slideshow images
+    ///
+    /// When unset, wallpaper directories in `$XDG_DATA_DIRS/backgrounds/` are searched
+    /// at any depth and other directories only at the top level.
+    #[serde(default)]
+    pub recursion_depth: Option<usize>,
+}
+
+/// A monitor, identified independently of the output it is connected to.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct Monitor {
+    pub make: String,
+    pub model: String,
+    /// Serial number, telling apart monitors of the same model.
+    #[serde(default)]
+    pub serial: Option<String>,
+}
+
+impl Monitor {
+    /// Whether `other` is the same monitor. Serial numbers are only compared if both are known.
+    #[must_use]
+    pub fn matches(&self, other: &Monitor) -> bool {
+        self.make == other.make
+            && self.model == other.model
+            && match (&self.serial, &other.serial) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            }
+    }
+
+    /// A human-readable description, such as `Dell Inc. DELL U2720Q`.
+    #[must_use]
+    pub fn description(&self) -> String {
+        [self.make.as_str(), self.model.as_str()]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 }
 
 /// A background image which is colored.
@@ -154,7 +308,23 @@ pub enum Color {
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, PartialOrd)]
 pub struct Gradient {
     pub colors: Cow<'static, [[f32; 3]]>,
-    pub radius: f32,
+    /// Angle of a linear gradient in degrees.
+    ///
+    /// Named `radius` before schema version 2, and still in the cosmic-bg config.
+    pub angle: f32,
+    /// Shape of the gradient.
+    #[serde(default)]
+    pub kind: GradientKind,
+}
+
+/// The shape of a gradient.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, PartialOrd)]
+pub enum GradientKind {
+    /// Colors blend along a straight line at the gradient's angle.
+    #[default]
+    Linear,
+    /// Colors blend outwards from the center of the output.
+    Radial,
 }
 
 /// The source of a background image.
@@ -166,6 +336,20 @@ pub enum Source {
     Color(Color),
     /// A GPU-rendered shader for live wallpapers.
     Shader(ShaderSource),
+    /// Sources shown one after another, starting over after the last one.
+    Playlist(Vec<PlaylistItem>),
+}
+
+/// A source in a playlist.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PlaylistItem {
+    /// What is shown. A directory is shown as a slideshow for the duration of the item.
+    /// Playlists can't be nested.
+    pub source: Source,
+    /// How long the item is shown in seconds, defaulting to the `rotation_frequency` of the entry.
+    #[serde(default)]
+    pub duration: Option<u64>,
 }
 
 /// Configuration for a shader-based live wallpaper.
@@ -191,6 +375,9 @@ pub struct ShaderSource {
     /// Target frame rate (1-60, default 30).
     #[serde(default = "default_frame_rate")]
     pub frame_rate: u8,
+    /// Clock advancing the animation time.
+    #[serde(default)]
+    pub clock: ShaderClock,
 }
 
 fn default_frame_rate() -> u8 {
@@ -206,6 +393,19 @@ pub enum ShaderContent {
     Code(String),
 }
 
+/// Clock advancing the animation time of a shader.
+///
+/// The animation time of each output is saved, so shaders continue where they
+/// left off when they are recreated or the daemon restarts.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShaderClock {
+    /// Keeps running while the system is suspended.
+    #[default]
+    Boot,
+    /// Pauses while the system is suspended.
+    Monotonic,
+}
+
 /// Supported shader languages.
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
 pub enum ShaderLanguage {
@@ -222,9 +422,12 @@ impl Entry {
             source,
             filter_by_theme: false,
             rotation_frequency: 900,
+            rotation_schedule: RotationSchedule::default(),
             filter_method: FilterMethod::default(),
             scaling_mode: ScalingMode::default(),
             sampling_method: SamplingMethod::default(),
+            recursion_depth: None,
+            monitor: None,
         }
     }
 
@@ -245,9 +448,12 @@ impl Entry {
             source: Source::Path(source_path),
             filter_by_theme: true,
             rotation_frequency: 3600,
+            rotation_schedule: RotationSchedule::default(),
             filter_method: FilterMethod::default(),
             scaling_mode: ScalingMode::default(),
             sampling_method: SamplingMethod::default(),
+            recursion_depth: None,
+            monitor: None,
         }
     }
 }
@@ -282,9 +488,42 @@ pub enum SamplingMethod {
     Alphanumeric,
     // Rotate through images in Random order
     Random,
+    /// Rotate through images by file modification time
+    Modified(DateOrder),
+    /// Rotate through images by the date they were taken, falling back to the
+    /// modification time for images without EXIF data
+    DateTaken(DateOrder),
+    /// Show every image once in random order before any is repeated, even across restarts
+    Shuffle,
+    /// Pick each image at random, showing favorites more often
+    Weighted,
     // TODO GnomeWallpapers
 }
 
+/// Direction of date based image ordering
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DateOrder {
+    #[default]
+    NewestFirst,
+    OldestFirst,
+}
+
+/// When slideshow images are rotated
+///
+/// Clock aligned schedules follow the local time zone.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub enum RotationSchedule {
+    /// Every `rotation_frequency` seconds after the previous rotation
+    #[default]
+    Interval,
+    /// At the start of every hour
+    Hourly,
+    /// At midnight
+    Daily,
+    /// At the times matched by a cron expression, such as `"30 7 * * MON-FRI"`
+    Cron(String),
+}
+
 /// Image scaling mode
 #[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
 pub enum ScalingMode {
@@ -311,6 +550,16 @@ pub struct Config {
     pub outputs: HashSet<String>,
     pub backgrounds: Vec<Entry>,
     pub default_background: Entry,
+    /// Largest pixel count of images to decode, or 0 for no limit.
+    pub max_image_pixels: u64,
+    /// Images that are never shown in slideshows.
+    pub blocklist: HashSet<PathBuf>,
+    /// Images shown more often by the weighted sampling method.
+    pub favorites: HashSet<PathBuf>,
+    /// Rules choosing the background of an output, in order of priority.
+    pub rules: Vec<rules::Rule>,
+    /// Whether gradients are rendered by the GPU when one is available.
+    pub gpu_gradients: bool,
 }
 
 impl Default for Config {
@@ -320,6 +569,11 @@ impl Default for Config {
             outputs: HashSet::new(),
             backgrounds: Vec::new(),
             default_background: Entry::fallback(),
+            max_image_pixels: DEFAULT_MAX_IMAGE_PIXELS,
+            blocklist: HashSet::new(),
+            favorites: HashSet::new(),
+            rules: Vec::new(),
+            gpu_gradients: false,
         }
     }
 }
@@ -333,6 +587,11 @@ impl Config {
     pub fn load(context: &Context) -> Result<Self, cosmic_config::Error> {
         let mut config = Self {
             same_on_all: context.same_on_all(),
+            max_image_pixels: context.max_image_pixels(),
+            blocklist: context.blocklist().into_iter().collect(),
+            favorites: context.favorites().into_iter().collect(),
+            rules: context.rules(),
+            gpu_gradients: context.gpu_gradients(),
             ..Default::default()
         };
 
@@ -368,6 +627,32 @@ impl Config {
         self.backgrounds.iter().find(|entry| entry.output == output)
     }
 
+    /// Get the entry for the output named `output`, which shows `monitor`.
+    ///
+    /// An entry for a monitor follows it to any output. Other entries match by output name.
+    /// Identical monitors without serial numbers keep the entries of their own outputs.
+    #[must_use]
+    pub fn entry_for(&self, output: &str, monitor: Option<&Monitor>) -> Option<&Entry> {
+        let shows = |entry: &&Entry| {
+            monitor.is_some_and(|monitor| {
+                entry
+                    .monitor
+                    .as_ref()
+                    .is_some_and(|other| other.matches(monitor))
+            })
+        };
+
+        self.backgrounds
+            .iter()
+            .find(|entry| entry.output == output && shows(entry))
+            .or_else(|| self.backgrounds.iter().find(shows))
+            .or_else(|| {
+                self.backgrounds.iter().find(|entry| {
+                    entry.output == output && (entry.monitor.is_none() || monitor.is_none())
+                })
+            })
+    }
+
     /// get a mutable entry for a given output.
     #[must_use]
     pub fn entry_mut(&mut self, output: &str) -> Option<&mut Entry> {
@@ -414,3 +699,133 @@ impl Config {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::BACKEND_VAR;
+    use std::fs;
+
+    /// Reads environment variables from `vars` instead of the process environment.
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<OsString> + 'a {
+        move |key| {
+            vars.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| OsString::from(value))
+        }
+    }
+
+    #[test]
+    fn context_backend_follows_the_session() {
+        let home = std::env::temp_dir().join(format!("glowberry-context-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        let config_home = home.to_str().unwrap();
+
+        // Outside of COSMIC the files are used, creating their directory.
+        let sway = [
+            ("XDG_CONFIG_HOME", config_home),
+            ("XDG_CURRENT_DESKTOP", "sway"),
+        ];
+        let context = context_from(env(&sway)).unwrap();
+        assert!(
+            matches!(&context.0, Backend::File(files) if files.dir() == home.join("glowberry"))
+        );
+        context.0.set(SAME_ON_ALL, true).unwrap();
+
+        // On COSMIC, cosmic-config is used even though the directory exists.
+        let cosmic = [
+            ("XDG_CONFIG_HOME", config_home),
+            ("XDG_CURRENT_DESKTOP", "pop:COSMIC"),
+        ];
+        assert!(matches!(
+            context_from(env(&cosmic)).unwrap().0,
+            Backend::Cosmic(_)
+        ));
+
+        // Files chosen on COSMIC are used, but their directory is never created.
+        let _ = fs::remove_dir_all(&home);
+        let files = [cosmic[0], cosmic[1], (BACKEND_VAR, "files")];
+        let context = context_from(env(&files)).unwrap();
+        assert!(matches!(context.0, Backend::File(_)));
+        assert!(context.0.set(SAME_ON_ALL, true).is_err());
+        assert!(!home.exists());
+
+        let cosmic_on_sway = [sway[0], sway[1], (BACKEND_VAR, "cosmic")];
+        assert!(matches!(
+            context_from(env(&cosmic_on_sway)).unwrap().0,
+            Backend::Cosmic(_)
+        ));
+    }
+
+    fn monitor(model: &str, serial: Option<&str>) -> Monitor {
+        Monitor {
+            make: String::from("Dell Inc."),
+            model: String::from(model),
+            serial: serial.map(String::from),
+        }
+    }
+
+    /// A config with an entry for each `(output, monitor)`.
+    fn config(entries: &[(&str, Option<Monitor>)]) -> Config {
+        let mut config = Config::default();
+        for (output, monitor) in entries {
+            let mut entry = Entry::new(String::from(*output), Source::Path(PathBuf::new()));
+            entry.monitor.clone_from(monitor);
+            config.backgrounds.push(entry);
+        }
+        config
+    }
+
+    /// The output of the entry used for `output` showing `monitor`.
+    fn entry_output<'a>(
+        config: &'a Config,
+        output: &str,
+        monitor: Option<&Monitor>,
+    ) -> Option<&'a str> {
+        config
+            .entry_for(output, monitor)
+            .map(|entry| entry.output.as_str())
+    }
+
+    #[test]
+    fn monitors_match_by_serial_when_both_are_known() {
+        let serial = monitor("U2720Q", Some("ABC123"));
+
+        assert!(serial.matches(&monitor("U2720Q", Some("ABC123"))));
+        assert!(!serial.matches(&monitor("U2720Q", Some("XYZ789"))));
+        assert!(serial.matches(&monitor("U2720Q", None)));
+        assert!(!serial.matches(&monitor("P2419H", Some("ABC123"))));
+    }
+
+    #[test]
+    fn entries_follow_renumbered_connectors() {
+        let dell = monitor("U2720Q", Some("ABC123"));
+        let config = config(&[("DP-1", Some(dell.clone())), ("HDMI-A-1", None)]);
+
+        assert_eq!(entry_output(&config, "DP-3", Some(&dell)), Some("DP-1"));
+        assert_eq!(entry_output(&config, "HDMI-A-1", Some(&dell)), Some("DP-1"));
+        // Outputs whose monitor is unknown match by name.
+        assert_eq!(entry_output(&config, "DP-1", None), Some("DP-1"));
+    }
+
+    #[test]
+    fn other_monitors_on_a_connector_get_no_entry() {
+        let config = config(&[("DP-1", Some(monitor("U2720Q", Some("ABC123"))))]);
+
+        let other = monitor("U2720Q", Some("XYZ789"));
+        assert_eq!(entry_output(&config, "DP-1", Some(&other)), None);
+        assert_eq!(
+            entry_output(&config, "DP-1", Some(&monitor("P2419H", None))),
+            None
+        );
+    }
+
+    #[test]
+    fn identical_monitors_without_serials_keep_their_entries() {
+        let twin = monitor("P2419H", None);
+        let config = config(&[("DP-1", Some(twin.clone())), ("DP-2", Some(twin.clone()))]);
+
+        assert_eq!(entry_output(&config, "DP-1", Some(&twin)), Some("DP-1"));
+        assert_eq!(entry_output(&config, "DP-2", Some(&twin)), Some("DP-2"));
+    }
+}
@@ -0,0 +1,466 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Upgrading the stored config to the current schema.
+//!
+//! Entries reject unknown fields, so renaming or restructuring a field makes
+//! values stored by older versions fail to parse. Such a change bumps
+//! [`SCHEMA_VERSION`] and adds a [`Migration`] to [`MIGRATIONS`], which reads
+//! the affected keys with their old types and writes them in the new format.
+//! The old values are backed up before they are rewritten.
+//!
+//! Backgrounds are also stored in the rules, the profiles and the custom
+//! colors, so a step changing them rewrites these keys as well.
+
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    CUSTOM_COLORS, Color, ConfigError, Context, DEFAULT_BACKGROUND, Entry,
+    backend::ConfigBackend,
+    profiles::{PROFILES, Profile},
+    rules::{RULES, Rule},
+};
+
+pub(crate) mod v1;
+
+/// Config key holding the schema version of the stored values.
+pub const CONFIG_VERSION: &str = "config-version";
+
+/// Schema version of the config written by this build.
+///
+/// Config stored before versioning was introduced has no version, and is version 1.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Migrations from older schema versions, in ascending order.
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: 2,
+    description: "rename the radius of gradients to angle",
+    run: gradient_radius_to_angle,
+}];
+
+/// Rewrite every stored background with [`v1`] types, whose gradients have a `radius`.
+fn gradient_radius_to_angle(migrator: &mut Migrator<'_>) -> Result<(), ConfigError> {
+    for key in migrator.background_keys() {
+        migrator.rewrite(&key, |entry: v1::Entry| Entry::from(entry))?;
+    }
+
+    migrator.rewrite(RULES, |rules: Vec<v1::Rule>| {
+        rules.into_iter().map(Rule::from).collect::<Vec<_>>()
+    })?;
+    migrator.rewrite(PROFILES, |profiles: Vec<v1::Profile>| {
+        profiles.into_iter().map(Profile::from).collect::<Vec<_>>()
+    })?;
+    migrator.rewrite(CUSTOM_COLORS, |colors: Vec<v1::Color>| {
+        colors.into_iter().map(Color::from).collect::<Vec<_>>()
+    })
+}
+
+/// A step upgrading the stored config by one schema version.
+#[derive(Clone, Copy)]
+pub struct Migration {
+    /// The schema version after this step.
+    pub version: u32,
+    pub description: &'static str,
+    pub run: fn(&mut Migrator<'_>) -> Result<(), ConfigError>,
+}
+
+/// The outcome of [`Context::migrate`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Schema version of the stored config before migrating
+    pub from: u32,
+    /// Schema version of the stored config after migrating
+    pub to: u32,
+    /// Keys whose values were rewritten
+    pub keys: Vec<String>,
+    /// File holding the previous values of the rewritten keys
+    pub backup: Option<PathBuf>,
+}
+
+/// Rewrites config values during a [`Migration`].
+pub struct Migrator<'a> {
+    context: &'a Context,
+    backup_path: PathBuf,
+    /// Previous values in RON, by key
+    backup: BTreeMap<String, String>,
+    keys: Vec<String>,
+}
+
+impl Migrator<'_> {
+    /// The config being migrated.
+    #[must_use]
+    pub fn context(&self) -> &Context {
+        self.context
+    }
+
+    /// Keys holding a single background: the default background and those of individual outputs.
+    ///
+    /// Backgrounds in [`RULES`], [`PROFILES`] and [`CUSTOM_COLORS`] are not included.
+    #[must_use]
+    pub fn background_keys(&self) -> Vec<String> {
+        std::iter::once(DEFAULT_BACKGROUND.to_string())
+            .chain(
+                self.context
+                    .backgrounds()
+                    .into_iter()
+                    .map(|output| ["output.", &output].concat()),
+            )
+            .collect()
+    }
+
+    /// Rewrite the value of `key` from its previous type `Old`.
+    ///
+    /// Keys that are unset or don't parse as `Old`, such as values already in
+    /// the new format, are left alone, as are values the conversion doesn't change.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the previous value could not be backed up or the new value could not be set.
+    pub fn rewrite<Old, New>(
+        &mut self,
+        key: &str,
+        convert: impl FnOnce(Old) -> New,
+    ) -> Result<(), ConfigError>
+    where
+        Old: DeserializeOwned + Serialize,
+        New: Serialize,
+    {
+        let Ok(old) = self.context.0.get::<Old>(key) else {
+            return Ok(());
+        };
+
+        let previous = to_ron(&old)?;
+        let new = convert(old);
+        if to_ron(&new)? == previous {
+            return Ok(());
+        }
+
+        // Earlier steps may already have rewritten the key, its original value is kept.
+        if !self.backup.contains_key(key) {
+            self.backup.insert(key.to_string(), previous);
+            self.save_backup()?;
+        }
+
+        self.context.0.set(key, new)?;
+
+        if !self.keys.iter().any(|k| k == key) {
+            self.keys.push(key.to_string());
+        }
+
+        Ok(())
+    }
+
+    fn save_backup(&self) -> Result<(), ConfigError> {
+        let data = to_ron(&self.backup)?;
+
+        self.backup_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&self.backup_path, data))
+            .map_err(ConfigError::Backup)
+    }
+}
+
+fn to_ron(value: &impl Serialize) -> Result<String, ConfigError> {
+    ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|why| ConfigError::Backup(std::io::Error::other(why)))
+}
+
+/// Directory of the backups made before migrating, in `$XDG_DATA_HOME/glowberry/backups/`.
+#[must_use]
+pub fn backup_dir() -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("glowberry")
+        .get_data_home()
+        .map(|dir| dir.join("backups"))
+}
+
+impl Context {
+    /// Schema version of the stored config.
+    #[must_use]
+    pub fn schema_version(&self) -> u32 {
+        self.0.get::<u32>(CONFIG_VERSION).unwrap_or(1)
+    }
+
+    /// Upgrade the stored config to [`SCHEMA_VERSION`] with [`MIGRATIONS`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the config was written by a newer version, or if a migration failed.
+    pub fn migrate(&self) -> Result<Report, ConfigError> {
+        let backup_dir = backup_dir().ok_or_else(|| {
+            ConfigError::Backup(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no data directory for config backups",
+            ))
+        })?;
+
+        self.migrate_with(MIGRATIONS, SCHEMA_VERSION, &backup_dir)
+    }
+
+    /// Upgrade the stored config to `target` with `migrations`, backing up
+    /// rewritten values in `backup_dir`.
+    ///
+    /// The version is stored after every step, so a failed migration resumes
+    /// with the step that failed.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the stored version is newer than `target`, or if a migration failed.
+    pub fn migrate_with(
+        &self,
+        migrations: &[Migration],
+        target: u32,
+        backup_dir: &Path,
+    ) -> Result<Report, ConfigError> {
+        let stored = self.0.get::<u32>(CONFIG_VERSION).ok();
+        let from = stored.unwrap_or(1);
+
+        if from > target {
+            return Err(ConfigError::NewerSchema(from));
+        }
+
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut migrator = Migrator {
+            context: self,
+            backup_path: backup_dir.join(format!("config-v{from}-{secs}.ron")),
+            backup: BTreeMap::new(),
+            keys: Vec::new(),
+        };
+
+        for migration in migrations
+            .iter()
+            .filter(|migration| migration.version > from && migration.version <= target)
+        {
+            tracing::info!(
+                version = migration.version,
+                description = migration.description,
+                "Migrating config"
+            );
+            (migration.run)(&mut migrator)?;
+            self.0.set(CONFIG_VERSION, migration.version)?;
+        }
+
+        // Empty configs and those from before versioning are tagged as well.
+        if stored != Some(target) {
+            self.0.set(CONFIG_VERSION, target)?;
+        }
+
+        Ok(Report {
+            from,
+            to: target,
+            backup: (!migrator.backup.is_empty()).then_some(migrator.backup_path),
+            keys: migrator.keys,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        BACKGROUNDS, Gradient, Source,
+        backend::{Backend, FileConfig},
+    };
+    use serde::Deserialize;
+
+    /// Format of `speed` before the first dummy step.
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    enum OldSpeed {
+        Slow,
+        Fast,
+    }
+
+    /// Format of `speed` after the first dummy step.
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Speed {
+        factor: u32,
+    }
+
+    fn speed_to_factor(migrator: &mut Migrator<'_>) -> Result<(), ConfigError> {
+        for key in ["speed", "other-speed"] {
+            migrator.rewrite(key, |old: OldSpeed| Speed {
+                factor: match old {
+                    OldSpeed::Slow => 1,
+                    OldSpeed::Fast => 4,
+                },
+            })?;
+        }
+        Ok(())
+    }
+
+    fn double_factor(migrator: &mut Migrator<'_>) -> Result<(), ConfigError> {
+        // The previous step has already stored its version.
+        assert_eq!(migrator.context().schema_version(), 2);
+        migrator.rewrite("speed", |old: Speed| Speed {
+            factor: old.factor * 2,
+        })
+    }
+
+    const STEPS: &[Migration] = &[
+        Migration {
+            version: 2,
+            description: "speed to factor",
+            run: speed_to_factor,
+        },
+        Migration {
+            version: 3,
+            description: "double factor",
+            run: double_factor,
+        },
+    ];
+
+    #[test]
+    fn migrates_in_order_with_backups() {
+        let dir = std::env::temp_dir().join(format!("glowberry-migrations-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let context = Context(Backend::File(FileConfig::new(dir.join("config"))));
+        let backup_dir = dir.join("backups");
+
+        context.0.set("speed", OldSpeed::Fast).unwrap();
+        // Already in the new format, so the first step skips it.
+        context.0.set("other-speed", Speed { factor: 7 }).unwrap();
+
+        let report = context.migrate_with(STEPS, 3, &backup_dir).unwrap();
+        assert_eq!((report.from, report.to), (1, 3));
+        assert_eq!(report.keys, ["speed"]);
+        assert_eq!(context.schema_version(), 3);
+        assert_eq!(
+            context.0.get::<Speed>("speed").unwrap(),
+            Speed { factor: 8 }
+        );
+        assert_eq!(
+            context.0.get::<Speed>("other-speed").unwrap(),
+            Speed { factor: 7 }
+        );
+
+        // The backup holds the value from before the first step.
+        let backup: BTreeMap<String, String> =
+            ron::from_str(&fs::read_to_string(report.backup.unwrap()).unwrap()).unwrap();
+        assert_eq!(backup.keys().collect::<Vec<_>>(), ["speed"]);
+        assert_eq!(
+            ron::from_str::<OldSpeed>(&backup["speed"]).unwrap(),
+            OldSpeed::Fast
+        );
+
+        // Nothing is left to migrate.
+        let report = context.migrate_with(STEPS, 3, &backup_dir).unwrap();
+        assert!(report.keys.is_empty());
+        assert_eq!(report.backup, None);
+
+        assert!(matches!(
+            context.migrate_with(STEPS, 2, &backup_dir),
+            Err(ConfigError::NewerSchema(3))
+        ));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn gradients_get_an_angle() {
+        let dir = std::env::temp_dir().join(format!("glowberry-gradients-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let context = Context(Backend::File(FileConfig::new(dir.join("config"))));
+
+        let gradient = "Gradient((colors: [(1.0, 0.0, 0.0), (0.0, 0.0, 1.0)], radius: 90.0))";
+        let entry = |output: &str, source: &str| {
+            format!(
+                "(output: {output:?}, source: {source}, filter_by_theme: false, rotation_frequency: 300)"
+            )
+        };
+        // Values in the format of version 1, which can't be built from the current types.
+        let files = FileConfig::new(dir.join("config"));
+        let set = |key: &str, value: &str| {
+            files.create_dir().unwrap();
+            fs::write(files.path(key), value).unwrap();
+        };
+
+        set(
+            DEFAULT_BACKGROUND,
+            &entry("all", &format!("Color({gradient})")),
+        );
+        // Doesn't hold a gradient, so it isn't rewritten.
+        set("output.DP-1", &entry("DP-1", "Path(\"/walls\")"));
+        set("output.DP-2", &entry("DP-2", &format!("Color({gradient})")));
+        context.0.set(BACKGROUNDS, vec!["DP-1", "DP-2"]).unwrap();
+        set(
+            RULES,
+            &format!(
+                "[(when: [DarkTheme], background: {})]",
+                entry("all", &format!("Playlist([(source: Color({gradient}))])"))
+            ),
+        );
+        set(
+            PROFILES,
+            &format!(
+                "[(name: \"Night\", same_on_all: true, default_background: {}, rules: [(when: [], background: {})])]",
+                entry("all", "Path(\"/walls\")"),
+                entry("all", &format!("Color({gradient})")),
+            ),
+        );
+        set(
+            CUSTOM_COLORS,
+            &format!("[Single((0.0, 1.0, 0.0)), {gradient}]"),
+        );
+
+        let report = context
+            .migrate_with(MIGRATIONS, SCHEMA_VERSION, &dir.join("backups"))
+            .unwrap();
+        assert_eq!(
+            report.keys,
+            [
+                DEFAULT_BACKGROUND,
+                "output.DP-2",
+                RULES,
+                PROFILES,
+                CUSTOM_COLORS
+            ]
+        );
+
+        let expected = Color::Gradient(Gradient {
+            colors: vec![[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]].into(),
+            angle: 90.0,
+            kind: crate::GradientKind::Linear,
+        });
+        let color = |source: &Source| match source {
+            Source::Color(color) => color.clone(),
+            Source::Playlist(items) => match &items[0].source {
+                Source::Color(color) => color.clone(),
+                source => panic!("unexpected source {source:?}"),
+            },
+            source => panic!("unexpected source {source:?}"),
+        };
+
+        assert_eq!(
+            color(&context.entry(DEFAULT_BACKGROUND).unwrap().source),
+            expected
+        );
+        assert_eq!(
+            context.entry("output.DP-1").unwrap().source,
+            Source::Path("/walls".into())
+        );
+        assert_eq!(
+            color(&context.entry("output.DP-2").unwrap().source),
+            expected
+        );
+        assert_eq!(color(&context.rules()[0].background.source), expected);
+        let profiles = context.0.get::<Vec<Profile>>(PROFILES).unwrap();
+        assert_eq!(color(&profiles[0].rules[0].background.source), expected);
+        assert_eq!(context.custom_colors()[1], expected);
+
+        // The backup holds the values with a radius.
+        let backup: BTreeMap<String, String> =
+            ron::from_str(&fs::read_to_string(report.backup.unwrap()).unwrap()).unwrap();
+        assert!(backup[CUSTOM_COLORS].contains("radius: 90.0"));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Backgrounds as stored by schema version 1, which named the angle of a gradient `radius`.
+//!
+//! Only the types containing a gradient are kept here, the others are unchanged.
+
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, path::PathBuf};
+
+use crate::{
+    FilterMethod, GradientKind, Monitor, RotationSchedule, SamplingMethod, ScalingMode,
+    ShaderSource, power_saving::PowerSavingConfig, rules::Condition,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    output: String,
+    #[serde(default)]
+    monitor: Option<Monitor>,
+    source: Source,
+    filter_by_theme: bool,
+    rotation_frequency: u64,
+    #[serde(default)]
+    rotation_schedule: RotationSchedule,
+    #[serde(default)]
+    filter_method: FilterMethod,
+    #[serde(default)]
+    scaling_mode: ScalingMode,
+    #[serde(default)]
+    sampling_method: SamplingMethod,
+    #[serde(default)]
+    recursion_depth: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Source {
+    Path(PathBuf),
+    Color(Color),
+    Shader(ShaderSource),
+    Playlist(Vec<PlaylistItem>),
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaylistItem {
+    source: Source,
+    #[serde(default)]
+    duration: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Color {
+    Single([f32; 3]),
+    Gradient(Gradient),
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Gradient {
+    colors: Cow<'static, [[f32; 3]]>,
+    radius: f32,
+    #[serde(default)]
+    kind: GradientKind,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    when: Vec<Condition>,
+    background: Entry,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    name: String,
+    same_on_all: bool,
+    default_background: Entry,
+    #[serde(default)]
+    backgrounds: Vec<Entry>,
+    #[serde(default)]
+    rules: Vec<Rule>,
+    #[serde(default)]
+    power_saving: PowerSavingConfig,
+    #[serde(default = "default_prefer_low_power")]
+    prefer_low_power: bool,
+}
+
+fn default_prefer_low_power() -> bool {
+    true
+}
+
+impl From<Entry> for crate::Entry {
+    fn from(entry: Entry) -> Self {
+        Self {
+            output: entry.output,
+            monitor: entry.monitor,
+            source: entry.source.into(),
+            filter_by_theme: entry.filter_by_theme,
+            rotation_frequency: entry.rotation_frequency,
+            rotation_schedule: entry.rotation_schedule,
+            filter_method: entry.filter_method,
+            scaling_mode: entry.scaling_mode,
+            sampling_method: entry.sampling_method,
+            recursion_depth: entry.recursion_depth,
+        }
+    }
+}
+
+impl From<Source> for crate::Source {
+    fn from(source: Source) -> Self {
+        match source {
+            Source::Path(path) => Self::Path(path),
+            Source::Color(color) => Self::Color(color.into()),
+            Source::Shader(shader) => Self::Shader(shader),
+            Source::Playlist(items) => Self::Playlist(
+                items
+                    .into_iter()
+                    .map(|item| crate::PlaylistItem {
+                        source: item.source.into(),
+                        duration: item.duration,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<Color> for crate::Color {
+    fn from(color: Color) -> Self {
+        match color {
+            Color::Single(rgb) => Self::Single(rgb),
+            Color::Gradient(gradient) => Self::Gradient(crate::Gradient {
+                colors: gradient.colors,
+                angle: gradient.radius,
+                kind: gradient.kind,
+            }),
+        }
+    }
+}
+
+impl From<Rule> for crate::rules::Rule {
+    fn from(rule: Rule) -> Self {
+        Self {
+            when: rule.when,
+            background: rule.background.into(),
+        }
+    }
+}
+
+impl From<Profile> for crate::profiles::Profile {
+    fn from(profile: Profile) -> Self {
+        Self {
+            name: profile.name,
+            same_on_all: profile.same_on_all,
+            default_background: profile.default_background.into(),
+            backgrounds: profile.backgrounds.into_iter().map(Into::into).collect(),
+            rules: profile.rules.into_iter().map(Into::into).collect(),
+            power_saving: profile.power_saving,
+            prefer_low_power: profile.prefer_low_power,
+        }
+    }
+}
@@ -2,7 +2,7 @@
 
 //! Power saving configuration for GlowBerry shader animations.
 
-use cosmic_config::{ConfigGet, ConfigSet};
+use crate::backend::ConfigBackend;
 use serde::{Deserialize, Serialize};
 
 use crate::Context;
@@ -57,7 +57,8 @@ impl OnBatteryAction {
 }
 
 /// Power saving configuration.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PowerSavingConfig {
     /// Adjust animation when on battery power
     pub adjust_on_battery: bool,
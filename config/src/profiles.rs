@@ -0,0 +1,231 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Named snapshots of the whole GlowBerry configuration.
+
+use crate::backend::ConfigBackend;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BACKGROUNDS, Config, ConfigError, Context, DEFAULT_BACKGROUND, Entry,
+    power_saving::PowerSavingConfig, rules::Rule,
+};
+
+// Config keys
+pub const PROFILES: &str = "profiles";
+pub const ACTIVE_PROFILE: &str = "active-profile";
+
+/// A named snapshot of the backgrounds, rules and power saving settings.
+///
+/// Shader parameters are part of the shader sources of the backgrounds.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub name: String,
+    pub same_on_all: bool,
+    pub default_background: Entry,
+    /// Backgrounds of individual outputs
+    #[serde(default)]
+    pub backgrounds: Vec<Entry>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    #[serde(default)]
+    pub power_saving: PowerSavingConfig,
+    #[serde(default = "default_prefer_low_power")]
+    pub prefer_low_power: bool,
+}
+
+fn default_prefer_low_power() -> bool {
+    true
+}
+
+impl Profile {
+    /// Snapshot of the current configuration, named `name`.
+    ///
+    /// Backgrounds of individual outputs are kept even while the same
+    /// background is shown on all outputs.
+    pub fn capture(name: &str, context: &Context) -> Result<Self, cosmic_config::Error> {
+        let mut config = Config::load(context)?;
+        config.load_backgrounds(context);
+
+        Ok(Self {
+            name: name.to_string(),
+            same_on_all: config.same_on_all,
+            default_background: config.default_background,
+            backgrounds: config.backgrounds,
+            rules: config.rules,
+            power_saving: context.power_saving_config(),
+            prefer_low_power: context.prefer_low_power(),
+        })
+    }
+
+    /// Replaces the current configuration with the profile.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the config could not be set in cosmic-config.
+    pub fn apply(&self, context: &Context) -> Result<(), cosmic_config::Error> {
+        let mut outputs = Vec::with_capacity(self.backgrounds.len());
+        for entry in &self.backgrounds {
+            let key = ["output.", &entry.output].concat();
+            if context.0.get::<Entry>(&key).ok().as_ref() != Some(entry) {
+                context.0.set(&key, entry)?;
+            }
+            outputs.push(entry.output.clone());
+        }
+
+        if context.backgrounds() != outputs {
+            context.0.set(BACKGROUNDS, outputs)?;
+        }
+
+        let mut default_background = self.default_background.clone();
+        default_background.output = DEFAULT_BACKGROUND.to_string();
+        if context.entry(DEFAULT_BACKGROUND).ok() != Some(default_background.clone()) {
+            context.0.set(DEFAULT_BACKGROUND, default_background)?;
+        }
+
+        context.set_rules(&self.rules)?;
+        if context.power_saving_config() != self.power_saving {
+            self.power_saving.save(context)?;
+        }
+        context.set_prefer_low_power(self.prefer_low_power)?;
+
+        // Switched last, so that the daemon finds the backgrounds it switches to.
+        context.set_same_on_all(self.same_on_all)?;
+        context.set_active_profile(Some(&self.name))
+    }
+}
+
+impl Context {
+    /// Get all saved profiles.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the saved profiles could not be read or parsed. Changes to the
+    /// profiles must not be written then, as they would replace those that failed.
+    pub fn profiles(&self) -> Result<Vec<Profile>, cosmic_config::Error> {
+        match self.0.get::<Vec<Profile>>(PROFILES) {
+            Ok(profiles) => Ok(profiles),
+            // This is expected when no profiles have been saved
+            Err(why) if !why.is_err() => Ok(Vec::new()),
+            Err(why) => Err(why),
+        }
+    }
+
+    /// Set the saved profiles.
+    pub fn set_profiles(&self, profiles: &[Profile]) -> Result<(), cosmic_config::Error> {
+        if self.profiles().ok().as_deref() != Some(profiles) {
+            return self.0.set(PROFILES, profiles);
+        }
+        Ok(())
+    }
+
+    /// Get the name of the profile that was switched to last.
+    #[must_use]
+    pub fn active_profile(&self) -> Option<String> {
+        self.0.get::<Option<String>>(ACTIVE_PROFILE).ok().flatten()
+    }
+
+    /// Set the name of the profile that was switched to last.
+    pub fn set_active_profile(&self, name: Option<&str>) -> Result<(), cosmic_config::Error> {
+        if self.active_profile().as_deref() != name {
+            return self.0.set(ACTIVE_PROFILE, name);
+        }
+        Ok(())
+    }
+
+    /// Add a profile, replacing any saved profile of the same name.
+    pub fn add_profile(&self, profile: Profile) -> Result<(), cosmic_config::Error> {
+        let mut profiles = self.profiles()?;
+        match profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => profiles.push(profile),
+        }
+        self.set_profiles(&profiles)
+    }
+
+    /// Save the current configuration as the profile named `name`.
+    pub fn save_profile(&self, name: &str) -> Result<(), cosmic_config::Error> {
+        self.add_profile(Profile::capture(name, self)?)?;
+        self.set_active_profile(Some(name))
+    }
+
+    /// Replace the current configuration with the profile named `name`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such profile or the config could not be set.
+    pub fn switch_profile(&self, name: &str) -> Result<(), ConfigError> {
+        let profile = self
+            .profiles()?
+            .into_iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| ConfigError::UnknownProfile(name.to_string()))?;
+
+        Ok(profile.apply(self)?)
+    }
+
+    /// Delete the profile named `name`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such profile or the config could not be set.
+    pub fn delete_profile(&self, name: &str) -> Result<(), ConfigError> {
+        let mut profiles = self.profiles()?;
+        let len = profiles.len();
+        profiles.retain(|profile| profile.name != name);
+        if profiles.len() == len {
+            return Err(ConfigError::UnknownProfile(name.to_string()));
+        }
+
+        self.set_profiles(&profiles)?;
+        if self.active_profile().as_deref() == Some(name) {
+            self.set_active_profile(None)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Backend, FileConfig};
+    use std::fs;
+
+    #[test]
+    fn unreadable_profiles_are_not_replaced() {
+        let dir = std::env::temp_dir().join(format!("glowberry-profiles-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let files = FileConfig::new(dir.clone());
+        let context = Context(Backend::File(files.clone()));
+
+        // No profiles saved yet
+        assert_eq!(context.profiles().unwrap(), []);
+
+        files.create_dir().unwrap();
+        fs::write(
+            files.path(PROFILES),
+            "[(name: \"Night\", from_a_newer_release: true)]",
+        )
+        .unwrap();
+        let profile = Profile {
+            name: "Day".into(),
+            same_on_all: true,
+            default_background: Entry::fallback(),
+            backgrounds: Vec::new(),
+            rules: Vec::new(),
+            power_saving: PowerSavingConfig::default(),
+            prefer_low_power: true,
+        };
+
+        assert!(context.profiles().is_err());
+        assert!(context.add_profile(profile).is_err());
+        assert!(context.delete_profile("Night").is_err());
+        assert!(
+            fs::read_to_string(files.path(PROFILES))
+                .unwrap()
+                .contains("Night")
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
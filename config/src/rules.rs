@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Rules choosing the background of an output from the state of the system.
+
+use crate::backend::ConfigBackend;
+use serde::{Deserialize, Serialize};
+
+use crate::{Context, Entry};
+
+// Config keys
+pub const RULES: &str = "rules";
+
+/// A background shown instead of the configured one while all of its conditions hold.
+///
+/// Rules are checked in order and the first one that holds for an output applies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Conditions that must all hold for the rule to apply.
+    pub when: Vec<Condition>,
+    /// The background to show. Its `output` is ignored; use an `Output` condition instead.
+    pub background: Entry,
+}
+
+/// A condition on the state of the system or on the output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Condition {
+    /// The system runs on battery power.
+    OnBattery,
+    /// The laptop lid is closed.
+    LidClosed,
+    /// The desktop uses a dark theme.
+    DarkTheme,
+    /// Today is one of these days.
+    Weekdays(Vec<Weekday>),
+    /// The local time is at or after `from` and before `to`, as `(hour, minute)`.
+    ///
+    /// The window wraps past midnight if `to` is not after `from`.
+    Time { from: (u8, u8), to: (u8, u8) },
+    /// The output has this connector name, such as `eDP-1`.
+    Output(String),
+    /// The manufacturer of the output contains this text, ignoring case.
+    Make(String),
+    /// The condition does not hold.
+    Not(Box<Condition>),
+}
+
+/// A day of the week.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Condition {
+    /// Returns true if the condition depends on the time of day or the day of the week.
+    #[must_use]
+    pub fn depends_on_time(&self) -> bool {
+        match self {
+            Self::Weekdays(_) | Self::Time { .. } => true,
+            Self::Not(condition) => condition.depends_on_time(),
+            _ => false,
+        }
+    }
+}
+
+impl Context {
+    /// Rules choosing the background of an output, in order of priority.
+    #[must_use]
+    pub fn rules(&self) -> Vec<Rule> {
+        match self.0.get::<Vec<Rule>>(RULES) {
+            Ok(rules) => rules,
+            Err(why) => {
+                // This is expected when no rules are configured
+                tracing::debug!(?why, "no background rules configured");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Set the rules choosing the background of an output.
+    pub fn set_rules(&self, rules: &[Rule]) -> Result<(), cosmic_config::Error> {
+        if self.rules() != rules {
+            return self.0.set(RULES, rules);
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
 use cosmic_config::{Config, CosmicConfigEntry, cosmic_config_derive::CosmicConfigEntry};
 use derive_setters::Setters;
 use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use crate::{NAME, Source};
+use crate::{Monitor, NAME, Source, diagnostics::Diagnostic};
 
 #[derive(Default, Debug, Deserialize, Serialize, Clone, PartialEq, Setters, CosmicConfigEntry)]
 #[serde(deny_unknown_fields)]
@@ -13,9 +17,128 @@ pub struct State {
     pub wallpapers: Vec<(String, Source)>,
     /// Currently connected outputs (updated by daemon)
     pub connected_outputs: Vec<String>,
+    /// Monitors shown on the connected outputs (updated by daemon)
+    /// (output_name, monitor)
+    #[serde(default)]
+    pub connected_monitors: Vec<(String, Monitor)>,
+    /// Previously shown slideshow images for each configured output, oldest first
+    /// (output_name, image paths)
+    #[serde(default)]
+    pub slideshow_history: Vec<(String, Vec<PathBuf>)>,
+    /// Images not yet shown in the current round of each shuffled slideshow
+    /// (output_name, image paths in the order they will be shown)
+    #[serde(default)]
+    pub shuffle_bags: Vec<(String, Vec<PathBuf>)>,
+    /// When each configured output last rotated its slideshow
+    /// (output_name, seconds since the Unix epoch)
+    #[serde(default)]
+    pub last_rotations: Vec<(String, u64)>,
+    /// Problems found in the config (updated by daemon)
+    #[serde(default)]
+    pub config_diagnostics: Vec<Diagnostic>,
+    /// Whether the backgrounds of cosmic-bg were imported on the first run
+    #[serde(default)]
+    pub cosmic_bg_imported: bool,
+    /// Animation time of the shader on each output, continued when it is recreated
+    /// (output_name, seconds)
+    #[serde(default)]
+    pub shader_times: Vec<(String, f64)>,
 }
 
 impl State {
+    /// The monitor connected to `output`, if known.
+    pub fn monitor(&self, output: &str) -> Option<&Monitor> {
+        self.connected_monitors
+            .iter()
+            .find(|(name, _)| name == output)
+            .map(|(_, monitor)| monitor)
+    }
+
+    /// Previously shown slideshow images of `output`, oldest first.
+    pub fn history(&self, output: &str) -> &[PathBuf] {
+        self.slideshow_history
+            .iter()
+            .find(|(name, _)| name == output)
+            .map_or(&[], |(_, history)| history)
+    }
+
+    /// Replace the slideshow history of `output`.
+    pub fn set_history(&mut self, output: &str, history: Vec<PathBuf>) {
+        match self
+            .slideshow_history
+            .iter_mut()
+            .find(|(name, _)| name == output)
+        {
+            Some((_, existing)) => *existing = history,
+            None => self.slideshow_history.push((output.to_string(), history)),
+        }
+    }
+
+    /// Images not yet shown in the current shuffle round of `output`.
+    pub fn shuffle_bag(&self, output: &str) -> &[PathBuf] {
+        self.shuffle_bags
+            .iter()
+            .find(|(name, _)| name == output)
+            .map_or(&[], |(_, bag)| bag)
+    }
+
+    /// Replace the shuffle bag of `output`.
+    pub fn set_shuffle_bag(&mut self, output: &str, bag: Vec<PathBuf>) {
+        match self
+            .shuffle_bags
+            .iter_mut()
+            .find(|(name, _)| name == output)
+        {
+            Some((_, existing)) => *existing = bag,
+            None => self.shuffle_bags.push((output.to_string(), bag)),
+        }
+    }
+
+    /// Animation time the shader on `output` continues from.
+    pub fn shader_time(&self, output: &str) -> Option<Duration> {
+        self.shader_times
+            .iter()
+            .find(|(name, _)| name == output)
+            .and_then(|(_, secs)| Duration::try_from_secs_f64(*secs).ok())
+    }
+
+    /// Record the animation time of the shader on `output`.
+    pub fn set_shader_time(&mut self, output: &str, time: Duration) {
+        let secs = time.as_secs_f64();
+        match self
+            .shader_times
+            .iter_mut()
+            .find(|(name, _)| name == output)
+        {
+            Some((_, existing)) => *existing = secs,
+            None => self.shader_times.push((output.to_string(), secs)),
+        }
+    }
+
+    /// When the slideshow of `output` last rotated.
+    pub fn last_rotation(&self, output: &str) -> Option<SystemTime> {
+        self.last_rotations
+            .iter()
+            .find(|(name, _)| name == output)
+            .map(|(_, secs)| UNIX_EPOCH + Duration::from_secs(*secs))
+    }
+
+    /// Record when the slideshow of `output` rotated.
+    pub fn set_last_rotation(&mut self, output: &str, time: SystemTime) {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        match self
+            .last_rotations
+            .iter_mut()
+            .find(|(name, _)| name == output)
+        {
+            Some((_, existing)) => *existing = secs,
+            None => self.last_rotations.push((output.to_string(), secs)),
+        }
+    }
+
     pub fn version() -> u64 {
         1
     }
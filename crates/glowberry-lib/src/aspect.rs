@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Choice of slideshow images suiting the aspect ratio of each output.
+//!
+//! A slideshow shared by landscape and portrait outputs would otherwise crop
+//! every image heavily on one of them. Outputs that the current image doesn't
+//! suit show an upcoming image of their own orientation instead.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// Number of upcoming images searched for one that suits an output.
+pub const SEARCH_LEN: usize = 32;
+
+/// Image sizes by path, `None` for images whose header can't be read.
+type Sizes = HashMap<PathBuf, Option<(u32, u32)>>;
+
+/// Sizes of slideshow images as they are shown.
+///
+/// Reading image headers can be slow on large or network folders, so they
+/// are read by the image worker pool, and the event loop only looks sizes up.
+#[derive(Clone, Debug, Default)]
+pub struct Dimensions(Arc<Mutex<Sizes>>);
+
+impl Dimensions {
+    /// The size of the image at `path`, or `None` if it is unknown.
+    pub fn get(&self, path: &Path) -> Option<(u32, u32)> {
+        self.lock().get(path).copied().flatten()
+    }
+
+    /// Whether the header of the image at `path` has been read.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.lock().contains_key(path)
+    }
+
+    /// Records the size of the image at `path`, or `None` if its header can't be read.
+    pub fn insert(&self, path: PathBuf, size: Option<(u32, u32)>) {
+        self.lock().insert(path, size);
+    }
+
+    /// Forgets images that are no longer in the slideshow.
+    pub fn retain(&self, queue: &VecDeque<PathBuf>) {
+        let queue: HashSet<&PathBuf> = queue.iter().collect();
+        self.lock().retain(|path, _| queue.contains(path));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Sizes> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Whether an image of `image` size has the same orientation as an output of `output` size.
+pub fn suits(image: (u32, u32), output: (u32, u32)) -> bool {
+    (image.0 >= image.1) == (output.0 >= output.1)
+}
+
+/// How far the aspect ratio of `image` is from that of `output`, 0 for a perfect fit.
+fn mismatch(image: (u32, u32), output: (u32, u32)) -> f64 {
+    let ratio = |(width, height): (u32, u32)| f64::from(width.max(1)) / f64::from(height.max(1));
+    (ratio(image) / ratio(output)).ln().abs()
+}
+
+/// Picks the image shown instead of `current` on an output of `size`.
+///
+/// Returns `None` if `current` suits the output. Otherwise the first upcoming
+/// image in `queue` that suits it is picked, or else the one that fits best if
+/// it fits better than `current`. Images of unknown size suit every output.
+pub fn pick_for_output(
+    queue: &VecDeque<PathBuf>,
+    current: &Path,
+    size: (u32, u32),
+    mut dimensions: impl FnMut(&Path) -> Option<(u32, u32)>,
+) -> Option<PathBuf> {
+    let current_size = dimensions(current)?;
+    if suits(current_size, size) {
+        return None;
+    }
+
+    let mut best = (mismatch(current_size, size), None);
+    for path in queue.iter().take(SEARCH_LEN) {
+        if path == current {
+            continue;
+        }
+
+        let Some(image_size) = dimensions(path) else {
+            continue;
+        };
+
+        if suits(image_size, size) {
+            return Some(path.clone());
+        }
+
+        let mismatch = mismatch(image_size, size);
+        if mismatch < best.0 {
+            best = (mismatch, Some(path.clone()));
+        }
+    }
+
+    best.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pick_for_output;
+    use std::{
+        collections::VecDeque,
+        path::{Path, PathBuf},
+    };
+
+    const PORTRAIT: (u32, u32) = (1440, 2560);
+
+    fn size(path: &Path) -> Option<(u32, u32)> {
+        match path.to_str()? {
+            "wide" => Some((3000, 1000)),
+            "landscape" => Some((1920, 1080)),
+            "square" => Some((1000, 1050)),
+            "portrait" => Some((1080, 1920)),
+            _ => None,
+        }
+    }
+
+    fn queue(paths: &[&str]) -> VecDeque<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn keeps_images_that_suit_the_output() {
+        let queue = queue(&["landscape", "portrait"]);
+
+        assert_eq!(
+            pick_for_output(&queue, Path::new("portrait"), PORTRAIT, size),
+            None
+        );
+        assert_eq!(
+            pick_for_output(&queue, Path::new("unknown"), PORTRAIT, size),
+            None
+        );
+    }
+
+    #[test]
+    fn picks_the_next_image_of_the_same_orientation() {
+        let queue = queue(&["landscape", "square", "portrait", "wide"]);
+
+        assert_eq!(
+            pick_for_output(&queue, Path::new("wide"), PORTRAIT, size),
+            Some(PathBuf::from("square"))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_best_fitting_image() {
+        let queue = queue(&["landscape", "wide"]);
+
+        assert_eq!(
+            pick_for_output(&queue, Path::new("wide"), PORTRAIT, size),
+            Some(PathBuf::from("landscape"))
+        );
+        assert_eq!(
+            pick_for_output(&queue, Path::new("landscape"), PORTRAIT, size),
+            None
+        );
+    }
+}
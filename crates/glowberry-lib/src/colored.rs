@@ -1,8 +1,18 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use crate::draw::BAYER_8X8;
 use colorgrad::{Color, Gradient as ColorGradient};
-use glowberry_config::Gradient;
-use image::Rgb32FImage;
+use glowberry_config::{
+    Gradient, GradientKind, ShaderClock, ShaderContent, ShaderLanguage, ShaderSource,
+};
+use image::{Rgb32FImage, imageops::FilterType};
+use std::fmt::Write;
+
+/// Factor by which gradients are evaluated below the output's physical resolution.
+///
+/// Gradients are smooth, so they are interpolated up to the output's resolution
+/// without visible loss, and dithered there when they are drawn.
+pub const GRADIENT_DOWNSCALE: u32 = 4;
 
 /// Generate a background image from a color.
 pub fn single(color: [f32; 3], width: u32, height: u32) -> Rgb32FImage {
@@ -19,11 +29,32 @@ pub fn single(color: [f32; 3], width: u32, height: u32) -> Rgb32FImage {
     imgbuf
 }
 
-/// Generate a background image from a gradient.
+/// Generate a background image of `width` x `height` pixels from a gradient.
+///
+/// The gradient is evaluated at a fraction of the size and interpolated.
 pub fn gradient(
     gradient: &Gradient,
     width: u32,
     height: u32,
+) -> Result<Rgb32FImage, colorgrad::GradientBuilderError> {
+    let reduced = evaluate(
+        gradient,
+        width.div_ceil(GRADIENT_DOWNSCALE).max(1),
+        height.div_ceil(GRADIENT_DOWNSCALE).max(1),
+    )?;
+
+    Ok(image::imageops::resize(
+        &reduced,
+        width,
+        height,
+        FilterType::Triangle,
+    ))
+}
+
+fn evaluate(
+    gradient: &Gradient,
+    width: u32,
+    height: u32,
 ) -> Result<Rgb32FImage, colorgrad::GradientBuilderError> {
     let mut colors = Vec::with_capacity(gradient.colors.len());
 
@@ -55,14 +86,27 @@ pub fn gradient(
     #[allow(clippy::items_after_statements)]
     const SCALE: f64 = 0.015;
 
-    let positioner: Box<dyn Fn(u32, u32) -> f64> = match gradient.radius as u16 {
-        0 => Box::new(|_x, y| 1.0 - (y as f64 / height)),
-        90 => Box::new(|x, _y| x as f64 / width),
-        180 => Box::new(|_x, y| y as f64 / height),
-        270 => Box::new(|x, _y| 1.0 - (x as f64 / width)),
+    let positioner: Box<dyn Fn(u32, u32) -> f64> = match (gradient.kind, gradient.angle as u16) {
+        (GradientKind::Radial, _) => Box::new(|x, y| {
+            let (dmin, dmax) = grad.domain();
+            let (cx, cy) = (width / 2.0, height / 2.0);
+            let distance = (f64::from(x) - cx).hypot(f64::from(y) - cy);
+
+            remap(
+                distance,
+                0.0,
+                cx.hypot(cy),
+                f64::from(dmin),
+                f64::from(dmax),
+            )
+        }),
+        (_, 0) => Box::new(|_x, y| 1.0 - (y as f64 / height)),
+        (_, 90) => Box::new(|x, _y| x as f64 / width),
+        (_, 180) => Box::new(|_x, y| y as f64 / height),
+        (_, 270) => Box::new(|x, _y| 1.0 - (x as f64 / width)),
         _ => Box::new(|x, y| {
             let (dmin, dmax) = grad.domain();
-            let angle = f64::from(gradient.radius.to_radians());
+            let angle = f64::from(gradient.angle.to_radians());
             let (x, y) = (f64::from(x) - width / SCALE, f64::from(y) - height / SCALE);
 
             remap(
@@ -85,9 +129,112 @@ pub fn gradient(
     Ok(imgbuf)
 }
 
+/// A shader rendering the gradient on the GPU, dithered at the output's resolution.
+///
+/// The shader draws the same gradient as [`gradient`], and doesn't animate.
+#[must_use]
+pub fn gradient_shader(gradient: &Gradient) -> ShaderSource {
+    let mut code = String::new();
+
+    let stops = gradient.colors.len().max(1);
+    let _ = writeln!(code, "const STOPS: u32 = {stops}u;");
+
+    // Position of the pixel `p` along the gradient, from 0 to 1.
+    let position = match (gradient.kind, gradient.angle as u16) {
+        (GradientKind::Radial, _) => {
+            "length(p - iResolution / 2.0) / length(iResolution / 2.0)".to_string()
+        }
+        (_, 0) => "1.0 - p.y / iResolution.y".to_string(),
+        (_, 90) => "p.x / iResolution.x".to_string(),
+        (_, 180) => "p.y / iResolution.y".to_string(),
+        (_, 270) => "1.0 - p.x / iResolution.x".to_string(),
+        _ => {
+            let radians = gradient.angle.to_radians();
+            format!(
+                "(q.x * {:?} - q.y * {:?} + iResolution.x / SCALE) / (2.0 * iResolution.x / SCALE)",
+                radians.cos(),
+                radians.sin()
+            )
+        }
+    };
+    let _ = writeln!(
+        code,
+        "fn position(p: vec2<f32>) -> f32 {{\n    let q = p - iResolution / SCALE;\n    return {position};\n}}"
+    );
+
+    let colors = if gradient.colors.is_empty() {
+        vec!["vec3<f32>(0.0, 0.0, 0.0)".to_string()]
+    } else {
+        gradient
+            .colors
+            .iter()
+            .map(|[r, g, b]| format!("vec3<f32>({r:?}, {g:?}, {b:?})"))
+            .collect()
+    };
+    let _ = writeln!(
+        code,
+        "fn stop(i: u32) -> vec3<f32> {{\n    var colors = array<vec3<f32>, {stops}>({});\n    return colors[min(i, STOPS - 1u)];\n}}",
+        colors.join(", ")
+    );
+
+    let bayer: Vec<String> = BAYER_8X8.iter().flatten().map(u8::to_string).collect();
+    let _ = writeln!(
+        code,
+        "fn bayer(x: u32, y: u32) -> f32 {{\n    var matrix = array<f32, 64>({});\n    return matrix[(y % 8u) * 8u + x % 8u];\n}}",
+        bayer.join(".0, ") + ".0"
+    );
+
+    code.push_str(GRADIENT_SHADER);
+
+    ShaderSource {
+        shader: ShaderContent::Code(code),
+        source_path: None,
+        params: std::collections::HashMap::new(),
+        background_image: None,
+        language: ShaderLanguage::Wgsl,
+        frame_rate: 1,
+        clock: ShaderClock::default(),
+    }
+}
+
+/// The rest of [`gradient_shader`], whose positions follow those of [`evaluate`].
+const GRADIENT_SHADER: &str = r#"
+const SCALE: f32 = 0.015;
+
+fn srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn main(@builtin(position) fragCoord: vec4<f32>) -> @location(0) vec4<f32> {
+    // Colors blend in linear RGB between evenly spaced stops.
+    let t = clamp(position(floor(fragCoord.xy)), 0.0, 1.0) * f32(STOPS - 1u);
+    let i = min(u32(t), STOPS - 1u);
+    let color = srgb(clamp(mix(stop(i), stop(i + 1u), t - f32(i)), vec3<f32>(0.0), vec3<f32>(1.0)));
+
+    // Ordered dithering to 8 bits per channel, as for gradients drawn by the CPU.
+    let threshold = (bayer(u32(fragCoord.x), u32(fragCoord.y)) + 0.5) / 64.0 - 0.5;
+    return vec4<f32>(round(color * 255.0 + threshold) / 255.0, 1.0);
+}
+"#;
+
 #[cfg(test)]
 mod tests {
-    use super::single;
+    use super::{gradient, gradient_shader, single};
+    use crate::{draw, fragment_canvas::WGSL_PREAMBLE};
+    use glowberry_config::{Gradient, GradientKind, ShaderContent};
+    use image::DynamicImage;
+    use std::borrow::Cow;
+
+    fn linear(angle: f32, colors: &'static [[f32; 3]]) -> Gradient {
+        Gradient {
+            colors: Cow::Borrowed(colors),
+            angle,
+            kind: GradientKind::Linear,
+        }
+    }
 
     #[test]
     fn single_color_buffer_matches_size() {
@@ -98,4 +245,88 @@ mod tests {
         assert_eq!(buffer.width(), width);
         assert_eq!(buffer.height(), height);
     }
+
+    #[test]
+    fn radial_gradient_starts_at_center() {
+        let radial = Gradient {
+            colors: Cow::Borrowed(&[[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]),
+            angle: 0.0,
+            kind: GradientKind::Radial,
+        };
+        let buffer = gradient(&radial, 33, 33).unwrap();
+
+        let center = buffer.get_pixel(16, 16).0;
+        let corner = buffer.get_pixel(0, 0).0;
+
+        assert!(center[0] > center[2]);
+        assert!(corner[2] > corner[0]);
+    }
+
+    #[test]
+    fn gradient_is_interpolated_to_the_output_size() {
+        let buffer = gradient(&linear(90.0, &[[0.0; 3], [1.0; 3]]), 64, 8).unwrap();
+        assert_eq!(buffer.dimensions(), (64, 8));
+
+        let row: Vec<f32> = (0..64).map(|x| buffer.get_pixel(x, 4).0[0]).collect();
+        assert!(row[0] < 0.05);
+        assert!(row[63] > 0.9);
+        // Rises from pixel to pixel, rather than in steps of the reduced size.
+        for pair in row[2..62].windows(2) {
+            assert!(pair[1] > pair[0], "step {pair:?}");
+        }
+    }
+
+    #[test]
+    fn gradient_is_dithered_per_output_pixel() {
+        let buffer = gradient(&linear(0.0, &[[0.0; 3], [1.0; 3]]), 16, 256).unwrap();
+
+        let mut canvas = vec![0u8; 16 * 256 * 4];
+        draw::xrgb888_canvas(&mut canvas, &DynamicImage::from(buffer));
+        let rows: Vec<Vec<u8>> = canvas
+            .chunks_exact(16 * 4)
+            .map(|row| row.chunks_exact(4).map(|px| px[0]).collect())
+            .collect();
+
+        // Each row has a single level, which is dithered between two neighboring steps.
+        for row in &rows {
+            let (min, max) = (row.iter().min().unwrap(), row.iter().max().unwrap());
+            assert!(max - min <= 1, "{row:?}");
+        }
+
+        // The dither pattern changes within the first pixels of most rows, which
+        // would repeat the same step if the dithered image were upscaled.
+        let varied = rows
+            .iter()
+            .filter(|row| row[..4].iter().any(|&b| b != row[0]));
+        assert!(varied.count() > rows.len() / 2);
+    }
+
+    #[test]
+    fn gradient_shader_is_valid_wgsl() {
+        for gradient in [
+            linear(90.0, &[[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+            linear(45.0, &[[1.0, 0.0, 0.0]]),
+            Gradient {
+                kind: GradientKind::Radial,
+                ..linear(0.0, &[[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]])
+            },
+        ] {
+            let source = gradient_shader(&gradient);
+            let ShaderContent::Code(code) = &source.shader else {
+                panic!("inline shader expected");
+            };
+
+            let wgsl = format!("{WGSL_PREAMBLE}\n{code}");
+            let module = naga::front::wgsl::parse_str(&wgsl)
+                .unwrap_or_else(|err| panic!("{}", err.emit_to_string(&wgsl)));
+            naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::empty(),
+            )
+            .validate(&module)
+            .unwrap_or_else(|err| panic!("{}", err.emit_to_string(&wgsl)));
+            // Rendered once, not animated.
+            assert!(!code.contains("iTime"));
+        }
+    }
 }
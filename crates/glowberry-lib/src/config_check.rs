@@ -0,0 +1,418 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Validation of the GlowBerry configuration.
+//!
+//! Values that fail to parse are silently replaced by defaults when the config
+//! is loaded, so a typo in a hand-edited file only shows up as an unexpected
+//! background. The checks here find such values along with missing files,
+//! shaders that don't compile and values out of range, and the daemon records
+//! them in [`State`] for the settings app to show.
+
+use crate::{fragment_canvas, schedule, shader_analysis};
+use cosmic_config::CosmicConfigEntry;
+use glowberry_config::{
+    BACKGROUNDS, BLOCKLIST, CUSTOM_COLORS, Color, Context, DEFAULT_BACKGROUND, Entry, FAVORITES,
+    GPU_GRADIENTS, MAX_IMAGE_PIXELS, PREFER_LOW_POWER, SAME_ON_ALL, ScalingMode, ShaderContent,
+    ShaderLanguage, ShaderSource, Source, WINDOW_OPACITY,
+    backend::ConfigBackend,
+    diagnostics::{Diagnostic, Problem},
+    power_saving::{self, OnBatteryAction},
+    profiles::{self, Profile},
+    rules::{self, Condition, Rule},
+    state::State,
+};
+use sctk::reexports::calloop;
+use serde::de::DeserializeOwned;
+use std::{fmt::Display, path::Path, sync::mpsc, thread};
+
+/// Largest frame rate of shaders.
+const MAX_FRAME_RATE: u8 = 60;
+
+/// Checks every key of the config, returning the problems found.
+pub fn check(context: &Context) -> Vec<Diagnostic> {
+    let mut checker = Checker::default();
+
+    checker.parse::<bool>(context, SAME_ON_ALL);
+    checker.parse::<bool>(context, PREFER_LOW_POWER);
+    checker.parse::<u64>(context, MAX_IMAGE_PIXELS);
+    checker.parse::<Vec<std::path::PathBuf>>(context, BLOCKLIST);
+    checker.parse::<Vec<std::path::PathBuf>>(context, FAVORITES);
+    checker.parse::<bool>(context, GPU_GRADIENTS);
+    checker.parse::<Option<String>>(context, profiles::ACTIVE_PROFILE);
+    checker.parse::<bool>(context, power_saving::ADJUST_ON_BATTERY);
+    checker.parse::<OnBatteryAction>(context, power_saving::ON_BATTERY_ACTION);
+    checker.parse::<bool>(context, power_saving::PAUSE_ON_LOW_BATTERY);
+    checker.parse::<bool>(context, power_saving::PAUSE_ON_LID_CLOSED);
+
+    if let Some(threshold) = checker.parse::<u8>(context, power_saving::LOW_BATTERY_THRESHOLD)
+        && !(1..=100).contains(&threshold)
+    {
+        checker.out_of_range(
+            power_saving::LOW_BATTERY_THRESHOLD,
+            "threshold",
+            threshold,
+            "1 to 100",
+        );
+    }
+
+    if let Some(opacity) = checker.parse::<f32>(context, WINDOW_OPACITY)
+        && !(0.0..=1.0).contains(&opacity)
+    {
+        checker.out_of_range(WINDOW_OPACITY, "opacity", opacity, "0.0 to 1.0");
+    }
+
+    if let Some(colors) = checker.parse::<Vec<Color>>(context, CUSTOM_COLORS) {
+        for color in &colors {
+            checker.check_color(CUSTOM_COLORS, color);
+        }
+    }
+
+    if let Some(entry) = checker.parse::<Entry>(context, DEFAULT_BACKGROUND) {
+        checker.check_entry(DEFAULT_BACKGROUND, &entry);
+    }
+
+    for output in checker
+        .parse::<Vec<String>>(context, BACKGROUNDS)
+        .unwrap_or_default()
+    {
+        let key = ["output.", &output].concat();
+        match checker.parse::<Entry>(context, &key) {
+            Some(entry) => checker.check_entry(&key, &entry),
+            None if !checker.reported(&key) => checker.push(
+                &key,
+                Problem::Parse(format!("listed in {BACKGROUNDS} but not configured")),
+            ),
+            None => {}
+        }
+    }
+
+    for rule in checker
+        .parse::<Vec<Rule>>(context, rules::RULES)
+        .unwrap_or_default()
+    {
+        checker.check_rule(&rule);
+    }
+
+    // Backgrounds of profiles are checked when they are switched to.
+    checker.parse::<Vec<Profile>>(context, profiles::PROFILES);
+
+    checker.diagnostics
+}
+
+/// Records the problems found in the config in the state of the daemon.
+///
+/// Only called from the event loop, which writes the rest of the state as well,
+/// so that the read and write here can't interleave with another.
+pub fn save(diagnostics: Vec<Diagnostic>) {
+    for diagnostic in &diagnostics {
+        tracing::warn!("config problem: {diagnostic}");
+    }
+
+    if let Ok(state_helper) = State::state() {
+        let mut state = State::get_entry(&state_helper).unwrap_or_default();
+        if state.config_diagnostics != diagnostics {
+            state.config_diagnostics = diagnostics;
+            if let Err(err) = state.write_entry(&state_helper) {
+                tracing::error!("Failed to save config diagnostics: {err}");
+            }
+        }
+    }
+}
+
+/// Checks the config on a thread of its own.
+///
+/// Checking compiles every shader and looks up every referenced file, which is
+/// too slow for the event loop. Checks requested while one runs are merged
+/// into a single check of the latest config.
+#[derive(Debug)]
+pub struct Recorder {
+    tx: mpsc::Sender<Context>,
+}
+
+impl Recorder {
+    /// Spawns the thread, posting the problems found by each check to `result_tx`.
+    ///
+    /// The thread exits once the recorder is dropped.
+    pub fn spawn(result_tx: calloop::channel::Sender<Vec<Diagnostic>>) -> Self {
+        let (tx, rx) = mpsc::channel::<Context>();
+
+        let spawned = thread::Builder::new()
+            .name("glowberry-config-check".into())
+            .spawn(move || {
+                while let Ok(context) = rx.recv() {
+                    let context = rx.try_iter().last().unwrap_or(context);
+                    if result_tx.send(check(&context)).is_err() {
+                        return;
+                    }
+                }
+            });
+
+        if let Err(why) = spawned {
+            tracing::error!(?why, "failed to spawn config check thread");
+        }
+
+        Self { tx }
+    }
+
+    /// Checks the config in the background.
+    pub fn record(&self, context: &Context) {
+        let _ = self.tx.send(context.clone());
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn push(&mut self, key: &str, problem: Problem) {
+        self.diagnostics.push(Diagnostic::new(key, problem));
+    }
+
+    fn reported(&self, key: &str) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.key == key)
+    }
+
+    fn out_of_range(&mut self, key: &str, field: &str, value: impl Display, expected: &str) {
+        self.push(
+            key,
+            Problem::OutOfRange {
+                field: field.to_string(),
+                value: value.to_string(),
+                expected: expected.to_string(),
+            },
+        );
+    }
+
+    /// Parses the value of `key`, or returns `None` if it is unset or fails to parse.
+    fn parse<T: DeserializeOwned>(&mut self, context: &Context, key: &str) -> Option<T> {
+        match context.0.get::<T>(key) {
+            Ok(value) => Some(value),
+            Err(why) => {
+                // Unset keys use their defaults.
+                if why.is_err() {
+                    self.push(key, Problem::Parse(why.to_string()));
+                }
+                None
+            }
+        }
+    }
+
+    fn check_entry(&mut self, key: &str, entry: &Entry) {
+        self.check_source(key, &entry.source, false);
+
+        if let Err(why) = schedule::parse(&entry.rotation_schedule) {
+            self.out_of_range(
+                key,
+                "rotation_schedule",
+                format!("{:?}", entry.rotation_schedule),
+                &format!("a valid cron expression ({why})"),
+            );
+        }
+
+        if let ScalingMode::Fit(color) = entry.scaling_mode {
+            self.check_rgb(key, "scaling_mode", &color);
+        }
+    }
+
+    fn check_source(&mut self, key: &str, source: &Source, in_playlist: bool) {
+        match source {
+            Source::Path(path) => self.check_path(key, path),
+            Source::Color(color) => self.check_color(key, color),
+            Source::Shader(shader) => self.check_shader(key, shader),
+            Source::Playlist(_) if in_playlist => self.out_of_range(
+                key,
+                "source",
+                "a nested playlist",
+                "an image, directory, color or shader",
+            ),
+            Source::Playlist(items) => {
+                if items.is_empty() {
+                    self.out_of_range(key, "source", "an empty playlist", "at least one item");
+                }
+
+                for item in items {
+                    self.check_source(key, &item.source, true);
+                    if item.duration == Some(0) {
+                        self.out_of_range(key, "duration", 0, "at least 1 second");
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_path(&mut self, key: &str, path: &Path) {
+        if !path.exists() {
+            self.push(key, Problem::MissingPath(path.to_path_buf()));
+        }
+    }
+
+    fn check_color(&mut self, key: &str, color: &Color) {
+        match color {
+            Color::Single(rgb) => self.check_rgb(key, "color", rgb),
+            Color::Gradient(gradient) => {
+                if gradient.colors.is_empty() {
+                    self.out_of_range(key, "colors", "empty", "at least one color");
+                }
+                for rgb in gradient.colors.iter() {
+                    self.check_rgb(key, "colors", rgb);
+                }
+            }
+        }
+    }
+
+    fn check_rgb(&mut self, key: &str, field: &str, rgb: &[f32; 3]) {
+        if rgb.iter().any(|channel| !(0.0..=1.0).contains(channel)) {
+            self.out_of_range(key, field, format!("{rgb:?}"), "channels from 0.0 to 1.0");
+        }
+    }
+
+    fn check_shader(&mut self, key: &str, shader: &ShaderSource) {
+        if !(1..=MAX_FRAME_RATE).contains(&shader.frame_rate) {
+            self.out_of_range(
+                key,
+                "frame_rate",
+                shader.frame_rate,
+                &format!("1 to {MAX_FRAME_RATE}"),
+            );
+        }
+
+        if let Some(image) = &shader.background_image {
+            self.check_path(key, image);
+        }
+
+        let (path, code) = match &shader.shader {
+            ShaderContent::Path(path) => match std::fs::read_to_string(path) {
+                Ok(code) => (Some(path.clone()), code),
+                Err(why) if why.kind() == std::io::ErrorKind::NotFound => {
+                    self.push(key, Problem::MissingPath(path.clone()));
+                    return;
+                }
+                Err(why) => {
+                    self.push(
+                        key,
+                        Problem::InvalidShader {
+                            path: Some(path.clone()),
+                            error: why.to_string(),
+                        },
+                    );
+                    return;
+                }
+            },
+            ShaderContent::Code(code) => (None, code.clone()),
+        };
+
+        let result = match fragment_canvas::detect_language(shader) {
+            ShaderLanguage::Wgsl => shader_analysis::analyze_glowberry_shader(
+                &code,
+                shader.background_image.is_some(),
+                None,
+            )
+            .map(drop),
+            ShaderLanguage::Glsl => Err("GLSL shaders are not supported yet".to_string()),
+        };
+
+        if let Err(error) = result {
+            self.push(key, Problem::InvalidShader { path, error });
+        }
+    }
+
+    fn check_rule(&mut self, rule: &Rule) {
+        for condition in &rule.when {
+            self.check_condition(condition);
+        }
+        self.check_entry(rules::RULES, &rule.background);
+    }
+
+    fn check_condition(&mut self, condition: &Condition) {
+        match condition {
+            Condition::Time { from, to } => {
+                for (field, (hour, minute)) in [("from", from), ("to", to)] {
+                    if *hour > 23 || *minute > 59 {
+                        self.out_of_range(
+                            rules::RULES,
+                            field,
+                            format!("({hour}, {minute})"),
+                            "a time from (0, 0) to (23, 59)",
+                        );
+                    }
+                }
+            }
+            Condition::Not(condition) => self.check_condition(condition),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Checker;
+    use glowberry_config::{
+        Color, Entry, RotationSchedule, ShaderClock, ShaderContent, ShaderSource, Source,
+        diagnostics::{Diagnostic, Problem},
+    };
+    use std::{collections::HashMap, path::PathBuf};
+
+    fn check_entry(entry: &Entry) -> Vec<Diagnostic> {
+        let mut checker = Checker::default();
+        checker.check_entry("all", entry);
+        checker.diagnostics
+    }
+
+    fn shader(code: &str, frame_rate: u8) -> Source {
+        Source::Shader(ShaderSource {
+            shader: ShaderContent::Code(code.into()),
+            source_path: None,
+            params: HashMap::new(),
+            background_image: None,
+            language: Default::default(),
+            frame_rate,
+            clock: ShaderClock::default(),
+        })
+    }
+
+    #[test]
+    fn reports_missing_paths_and_bad_values() {
+        let missing = PathBuf::from("/nonexistent/glowberry/wallpapers");
+        let entry = Entry::new("all".into(), Source::Path(missing.clone()))
+            .rotation_schedule(RotationSchedule::Cron("every tuesday".into()));
+
+        let diagnostics = check_entry(&entry);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].problem, Problem::MissingPath(missing));
+        assert!(matches!(
+            &diagnostics[1].problem,
+            Problem::OutOfRange { field, .. } if field == "rotation_schedule"
+        ));
+
+        let color = Entry::new("all".into(), Source::Color(Color::Single([0.5, 2.0, 0.0])));
+        assert!(matches!(
+            &check_entry(&color)[..],
+            [Diagnostic { problem: Problem::OutOfRange { field, .. }, .. }] if field == "color"
+        ));
+    }
+
+    #[test]
+    fn reports_invalid_shaders() {
+        let valid = "@fragment\nfn main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {\n    return vec4<f32>(1.0);\n}\n";
+        assert_eq!(
+            check_entry(&Entry::new("all".into(), shader(valid, 30))),
+            []
+        );
+
+        let diagnostics = check_entry(&Entry::new("all".into(), shader("fn main( {", 120)));
+        assert_eq!(diagnostics.len(), 2);
+        assert!(matches!(
+            &diagnostics[0].problem,
+            Problem::OutOfRange { field, .. } if field == "frame_rate"
+        ));
+        assert!(matches!(
+            &diagnostics[1].problem,
+            Problem::InvalidShader { path: None, .. }
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Reloading of the plain config files used outside of COSMIC.
+//!
+//! cosmic-config notifies the daemon of changed keys itself. The file backend
+//! has no such service, so the config directory is watched instead, and the
+//! keys of changed files are passed on the same way.
+
+use glowberry_config::backend::FileConfig;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use sctk::reexports::calloop::{self, LoopHandle};
+
+/// Watches the config files of `config`, calling `on_change` with the keys of changed files.
+///
+/// The config is watched for as long as the returned watcher is kept.
+///
+/// # Errors
+///
+/// Fails if the config directory can't be created or watched.
+pub fn watch_files<T: 'static>(
+    config: &FileConfig,
+    handle: &LoopHandle<'static, T>,
+    mut on_change: impl FnMut(&mut T, Vec<String>) + 'static,
+) -> eyre::Result<RecommendedWatcher> {
+    config.create_dir()?;
+
+    let (tx, rx) = calloop::channel::channel::<Vec<String>>();
+    handle
+        .insert_source(rx, move |event, (), state| {
+            if let calloop::channel::Event::Msg(keys) = event {
+                on_change(state, keys);
+            }
+        })
+        .map_err(|err| {
+            eyre::eyre!("failed to insert config file channel into event loop: {err}")
+        })?;
+
+    let files = config.clone();
+    let mut watcher = RecommendedWatcher::new(
+        move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else {
+                return;
+            };
+
+            let keys = changed_keys(&files, &event);
+            if !keys.is_empty() {
+                let _ = tx.send(keys);
+            }
+        },
+        notify::Config::default(),
+    )?;
+
+    watcher.watch(config.dir(), RecursiveMode::NonRecursive)?;
+
+    Ok(watcher)
+}
+
+/// The keys whose files were written or removed by `event`.
+fn changed_keys(config: &FileConfig, event: &notify::Event) -> Vec<String> {
+    if !matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) {
+        return Vec::new();
+    }
+
+    let mut keys = Vec::new();
+    for key in event.paths.iter().filter_map(|path| config.key(path)) {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glowberry_config::backend::ConfigBackend;
+    use notify::event::{CreateKind, ModifyKind, RenameMode};
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn written_files_map_to_keys() {
+        let dir = std::env::temp_dir().join(format!("glowberry-config-{}", std::process::id()));
+        let config = FileConfig::new(dir.clone());
+
+        config.set("output.DP-1", vec![1u8, 2]).unwrap();
+        assert_eq!(config.get::<Vec<u8>>("output.DP-1").unwrap(), [1, 2]);
+        assert!(config.get::<bool>("same-on-all").is_err());
+
+        // The temporary file of an atomic write is skipped, its rename is not.
+        let event = notify::Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(dir.join("output.DP-1.ron.tmp"))
+            .add_path(config.path("output.DP-1"));
+        assert_eq!(changed_keys(&config, &event), ["output.DP-1"]);
+
+        let event = notify::Event::new(EventKind::Create(CreateKind::File))
+            .add_path(PathBuf::from("/elsewhere/all.ron"));
+        assert!(changed_keys(&config, &event).is_empty());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! D-Bus control interface of the daemon.
+//!
+//! The daemon serves `io.github.hojjatabdollahi.glowberry.Control` on the session
+//! bus. The `glowberry` command line and the settings app use it to step through
+//! slideshows, to block images from being shown again and to mark favorites.
+
+use glowberry_config::NAME;
+use sctk::reexports::calloop;
+use zbus::{connection, interface, proxy};
+
+/// Object path of the control interface.
+pub const OBJECT_PATH: &str = "/io/github/hojjatabdollahi/glowberry";
+
+/// A slideshow action requested over D-Bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Show the next image and restart the rotation timer.
+    Next,
+    /// Go back to the previously shown image.
+    Previous,
+    /// Never show the current image again and move on to the next one.
+    Block,
+    /// Add the current image to the favorites, or remove it if it already is one.
+    Favorite,
+}
+
+/// A command for the wallpaper of one output, or of every output if `output` is `None`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ControlRequest {
+    pub command: ControlCommand,
+    pub output: Option<String>,
+}
+
+struct ControlService {
+    tx: calloop::channel::Sender<ControlRequest>,
+}
+
+impl ControlService {
+    fn send(&self, command: ControlCommand, output: &str) -> zbus::fdo::Result<()> {
+        let output = (!output.is_empty()).then(|| output.to_string());
+        self.tx
+            .send(ControlRequest { command, output })
+            .map_err(|_| zbus::fdo::Error::Failed("daemon is shutting down".into()))
+    }
+}
+
+#[interface(name = "io.github.hojjatabdollahi.glowberry.Control")]
+impl ControlService {
+    /// Show the next slideshow image on `output`, or on every output if empty.
+    async fn next(&self, output: &str) -> zbus::fdo::Result<()> {
+        self.send(ControlCommand::Next, output)
+    }
+
+    /// Show the previous slideshow image on `output`, or on every output if empty.
+    async fn previous(&self, output: &str) -> zbus::fdo::Result<()> {
+        self.send(ControlCommand::Previous, output)
+    }
+
+    /// Block the current image on `output`, or on every output if empty.
+    async fn block(&self, output: &str) -> zbus::fdo::Result<()> {
+        self.send(ControlCommand::Block, output)
+    }
+
+    /// Toggle whether the current image on `output`, or on every output if empty, is a favorite.
+    async fn favorite(&self, output: &str) -> zbus::fdo::Result<()> {
+        self.send(ControlCommand::Favorite, output)
+    }
+}
+
+#[proxy(
+    interface = "io.github.hojjatabdollahi.glowberry.Control",
+    default_service = "io.github.hojjatabdollahi.glowberry",
+    default_path = "/io/github/hojjatabdollahi/glowberry"
+)]
+trait Control {
+    fn next(&self, output: &str) -> zbus::Result<()>;
+
+    fn previous(&self, output: &str) -> zbus::Result<()>;
+
+    fn block(&self, output: &str) -> zbus::Result<()>;
+
+    fn favorite(&self, output: &str) -> zbus::Result<()>;
+}
+
+/// Serve the control interface on a separate thread, forwarding requests to `tx`.
+pub fn start_control_service(tx: calloop::channel::Sender<ControlRequest>) {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(why) => {
+            tracing::error!(?why, "failed to create runtime for the control service");
+            return;
+        }
+    };
+
+    std::thread::spawn(move || {
+        rt.block_on(async {
+            let connection = connection::Builder::session()
+                .and_then(|builder| builder.name(NAME))
+                .and_then(|builder| builder.serve_at(OBJECT_PATH, ControlService { tx }));
+
+            let connection = match connection {
+                Ok(builder) => builder.build().await,
+                Err(why) => Err(why),
+            };
+
+            // Keep the connection alive for as long as the daemon runs.
+            match connection {
+                Ok(_connection) => std::future::pending::<()>().await,
+                Err(why) => tracing::error!(?why, "failed to start the control service"),
+            }
+        });
+    });
+}
+
+/// Send a command to the running daemon.
+pub async fn send(command: ControlCommand, output: Option<&str>) -> zbus::Result<()> {
+    let connection = zbus::Connection::session().await?;
+    let proxy = ControlProxy::new(&connection).await?;
+    let output = output.unwrap_or_default();
+
+    match command {
+        ControlCommand::Next => proxy.next(output).await,
+        ControlCommand::Previous => proxy.previous(output).await,
+        ControlCommand::Block => proxy.block(output).await,
+        ControlCommand::Favorite => proxy.favorite(output).await,
+    }
+}
+
+/// Send a command to the running daemon, blocking until it was delivered.
+pub fn send_blocking(command: ControlCommand, output: Option<&str>) -> eyre::Result<()> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    rt.block_on(send(command, output))?;
+    Ok(())
+}
@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Carrying wallpapers over from cosmic-bg and mirroring them back.
+
+use cosmic_config::CosmicConfigEntry;
+use glowberry_config::{Context, cosmic_bg, state::State};
+
+/// Imports the backgrounds of cosmic-bg on the first run, if nothing is configured yet.
+///
+/// Failed imports are tried again on the next run.
+pub fn import_on_first_run(context: &Context) {
+    let Ok(state_helper) = State::state() else {
+        return;
+    };
+
+    let mut state = State::get_entry(&state_helper).unwrap_or_default();
+    if state.cosmic_bg_imported {
+        return;
+    }
+
+    if context.is_empty() {
+        match cosmic_bg::context().and_then(|cosmic_bg| context.import_cosmic_bg(&cosmic_bg)) {
+            Ok(true) => tracing::info!("Imported the backgrounds of cosmic-bg"),
+            Ok(false) => tracing::debug!("cosmic-bg has no backgrounds to import"),
+            Err(why) => {
+                tracing::error!(?why, "Failed to import the backgrounds of cosmic-bg");
+                return;
+            }
+        }
+    }
+
+    state.cosmic_bg_imported = true;
+    if let Err(err) = state.write_entry(&state_helper) {
+        tracing::error!("Failed to save cosmic-bg import: {err}");
+    }
+}
+
+/// Writes the backgrounds that cosmic-bg can show to its config, if mirroring is enabled.
+pub fn mirror(context: &Context) {
+    if !context.mirror_cosmic_bg() {
+        return;
+    }
+
+    if let Err(why) =
+        cosmic_bg::context().and_then(|cosmic_bg| context.mirror_to_cosmic_bg(&cosmic_bg))
+    {
+        tracing::error!(?why, "Failed to mirror backgrounds to cosmic-bg");
+    }
+}
@@ -4,9 +4,13 @@ use image::{DynamicImage, GenericImageView};
 use sctk::{
     reexports::{
         client::{
-            Dispatch, QueueHandle, protocol::wl_callback, protocol::wl_shm, protocol::wl_surface,
+            Dispatch, QueueHandle, protocol::wl_buffer, protocol::wl_callback, protocol::wl_shm,
+            protocol::wl_surface,
+        },
+        protocols::wp::{
+            single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1,
+            viewporter::client::wp_viewport,
         },
-        protocols::wp::viewporter::client::wp_viewport,
     },
     shell::{WaylandSurface, wlr_layer::LayerSurface},
     shm::slot::{Buffer, CreateBufferError, SlotPool},
@@ -77,6 +81,45 @@ pub fn layer_surface<T>(
     wl_surface.commit();
 }
 
+/// Attaches a single-pixel buffer of the given color, scaled to `size` by the viewport.
+pub fn single_pixel<T>(
+    manager: &wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1,
+    layer_surface: &LayerSurface,
+    viewport: &wp_viewport::WpViewport,
+    queue_handle: &QueueHandle<T>,
+    [r, g, b]: [f32; 3],
+    size: (u32, u32),
+) where
+    T: Dispatch<wl_callback::WlCallback, wl_surface::WlSurface>
+        + Dispatch<wl_buffer::WlBuffer, ()>
+        + 'static,
+{
+    let (width, height) = size;
+
+    // Channels are premultiplied 32-bit values; the buffer is fully opaque.
+    let channel = |c: f32| (f64::from(c.clamp(0.0, 1.0)) * f64::from(u32::MAX)) as u32;
+    let buffer = manager.create_u32_rgba_buffer(
+        channel(r),
+        channel(g),
+        channel(b),
+        u32::MAX,
+        queue_handle,
+        (),
+    );
+
+    let wl_surface = layer_surface.wl_surface();
+
+    wl_surface.damage_buffer(0, 0, 1, 1);
+
+    wl_surface.frame(queue_handle, wl_surface.clone());
+
+    wl_surface.attach(Some(&buffer), 0, 0);
+
+    viewport.set_destination(width as i32, height as i32);
+
+    wl_surface.commit();
+}
+
 /// Draws the image on a 10-bit canvas.
 pub fn xrgb21010_canvas(canvas: &mut [u8], image: &DynamicImage) {
     const BIT_MASK: u32 = (1 << 10) - 1;
@@ -94,8 +137,27 @@ pub fn xrgb21010_canvas(canvas: &mut [u8], image: &DynamicImage) {
     }
 }
 
+/// 8x8 Bayer matrix for ordered dithering.
+pub(crate) const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
 /// Draws the image on an 8-bit canvas.
+///
+/// Images with more than 8 bits per channel, such as rendered gradients, are
+/// quantized with ordered dithering to avoid visible banding.
 pub fn xrgb888_canvas(canvas: &mut [u8], image: &DynamicImage) {
+    if is_high_bit_depth(image) {
+        return dithered_xrgb888_canvas(canvas, image);
+    }
+
     for (pos, (_, _, pixel)) in image.pixels().enumerate() {
         let indice = pos * 4;
 
@@ -108,3 +170,67 @@ pub fn xrgb888_canvas(canvas: &mut [u8], image: &DynamicImage) {
         canvas[indice..indice + 4].copy_from_slice(&(r | g | b).to_le_bytes());
     }
 }
+
+/// Whether the image stores more than 8 bits per channel.
+pub fn is_high_bit_depth(image: &DynamicImage) -> bool {
+    matches!(
+        image,
+        DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_)
+            | DynamicImage::ImageRgb32F(_)
+            | DynamicImage::ImageRgba32F(_)
+    )
+}
+
+fn rgb32f(image: &DynamicImage) -> std::borrow::Cow<'_, image::Rgb32FImage> {
+    match image {
+        DynamicImage::ImageRgb32F(buffer) => std::borrow::Cow::Borrowed(buffer),
+        image => std::borrow::Cow::Owned(image.to_rgb32f()),
+    }
+}
+
+/// Quantizes a channel to 8 bits with a threshold from the Bayer matrix.
+fn dither(x: u32, y: u32, c: f32) -> u8 {
+    // Threshold in the range (-0.5, 0.5) added before rounding to 8 bits.
+    let threshold = (f32::from(BAYER_8X8[y as usize % 8][x as usize % 8]) + 0.5) / 64.0 - 0.5;
+
+    (c.clamp(0.0, 1.0) * 255.0 + threshold)
+        .round()
+        .clamp(0.0, 255.0) as u8
+}
+
+fn dithered_xrgb888_canvas(canvas: &mut [u8], image: &DynamicImage) {
+    for (pos, (x, y, pixel)) in rgb32f(image).enumerate_pixels().enumerate() {
+        let indice = pos * 4;
+
+        let [r, g, b] = pixel.0;
+
+        let r = u32::from(dither(x, y, r)) << 16;
+        let g = u32::from(dither(x, y, g)) << 8;
+        let b = u32::from(dither(x, y, b));
+
+        canvas[indice..indice + 4].copy_from_slice(&(r | g | b).to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::xrgb888_canvas;
+    use image::{DynamicImage, Rgb32FImage};
+
+    #[test]
+    fn dithering_preserves_average_level() {
+        // A level halfway between two 8-bit steps should dither evenly between them.
+        let level = 100.5 / 255.0;
+        let image = DynamicImage::from(Rgb32FImage::from_pixel(8, 8, image::Rgb([level; 3])));
+        let mut canvas = vec![0u8; 8 * 8 * 4];
+
+        xrgb888_canvas(&mut canvas, &image);
+
+        let blues: Vec<u8> = canvas.chunks_exact(4).map(|px| px[0]).collect();
+        assert!(blues.iter().all(|&b| b == 100 || b == 101));
+        assert_eq!(blues.iter().filter(|&&b| b == 101).count(), 32);
+    }
+}
@@ -1,7 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use crate::{
-    fragment_canvas, gpu, img_source,
+    config_check, config_watch,
+    control::{self, ControlCommand, ControlRequest},
+    cosmic_bg, fragment_canvas, gpu,
+    image_worker::{ImageWorker, WorkerResult},
+    img_source, monitor, rules,
+    slideshow::ImagePreferences,
     upower::{PowerMonitorHandle, PowerStateChanged, start_power_monitor},
     user_context::{EnvGuard, UserContext},
     wallpaper::Wallpaper,
@@ -9,7 +14,8 @@ use crate::{
 use cosmic_config::{CosmicConfigEntry, calloop::ConfigWatchSource};
 use eyre::{Context, eyre};
 use glowberry_config::{
-    Config,
+    Config, Entry, Monitor,
+    backend::Backend,
     power_saving::{OnBatteryAction, PowerSavingConfig},
     state::State,
 };
@@ -18,12 +24,16 @@ use sctk::{
     delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_shm,
     output::{OutputHandler, OutputInfo, OutputState},
     reexports::{
-        calloop,
+        calloop::{
+            self, RegistrationToken,
+            timer::{TimeoutAction, Timer},
+        },
         calloop_wayland_source::WaylandSource,
         client::{
             Connection, Dispatch, Proxy, QueueHandle, Weak, delegate_noop,
             globals::registry_queue_init,
             protocol::{
+                wl_buffer,
                 wl_output::{self, WlOutput},
                 wl_surface,
             },
@@ -32,6 +42,7 @@ use sctk::{
             fractional_scale::v1::client::{
                 wp_fractional_scale_manager_v1, wp_fractional_scale_v1,
             },
+            single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1,
             viewporter::client::{wp_viewport, wp_viewporter},
         },
     },
@@ -46,9 +57,12 @@ use sctk::{
     },
     shm::{Shm, ShmHandler, slot::SlotPool},
 };
-use std::thread;
+use std::{collections::HashMap, path::Path, thread, time::Duration};
 use tracing::error;
 
+/// How often the animation time of shaders is saved.
+const SHADER_TIME_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Access glibc malloc tunables.
 #[cfg(target_env = "gnu")]
 mod malloc {
@@ -138,6 +152,10 @@ impl BackgroundEngine {
 
         let qh = event_queue.handle();
 
+        let single_pixel_buffer_manager: Option<
+            wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1,
+        > = globals.bind(&qh, 1..=1, ()).ok();
+
         WaylandSource::new(conn, event_queue)
             .insert(event_loop.handle())
             .map_err(|err| err.error)
@@ -155,99 +173,65 @@ impl BackgroundEngine {
         }
 
         let config_context = glowberry_config::context();
+        let mut config_watcher = None;
+        // Check the config off the event loop, saving the problems found on it
+        let (diagnostics_tx, diagnostics_rx) = calloop::channel::channel();
+        let config_checker = config_check::Recorder::spawn(diagnostics_tx);
+
+        event_loop
+            .handle()
+            .insert_source(diagnostics_rx, |event, _, _state| {
+                if let calloop::channel::Event::Msg(diagnostics) = event {
+                    config_check::save(diagnostics);
+                }
+            })
+            .map_err(|err| eyre!("failed to insert config check channel into event loop: {err}"))?;
 
         let config = match config_context {
             Ok(config_context) => {
-                let source = ConfigWatchSource::new(&config_context.0)
-                    .expect("failed to create ConfigWatchSource");
+                // Keep the wallpapers configured before switching from cosmic-bg. Imported
+                // before migrating, so that the config is tagged with the current version after.
+                cosmic_bg::import_on_first_run(&config_context);
+
+                match config_context.migrate() {
+                    Ok(report) if !report.keys.is_empty() => tracing::info!(
+                        from = report.from,
+                        to = report.to,
+                        keys = ?report.keys,
+                        backup = ?report.backup,
+                        "Migrated config"
+                    ),
+                    Ok(_) => {}
+                    Err(why) => tracing::error!(?why, "Failed to migrate config"),
+                }
 
                 let conf_context = config_context.clone();
-                event_loop
-                    .handle()
-                    .insert_source(source, move |(_config, keys), (), state| {
-                        let mut changes_applied = false;
-
-                        for key in &keys {
-                            match key.as_str() {
-                                glowberry_config::BACKGROUNDS => {
-                                    tracing::debug!("updating backgrounds");
-                                    state.config.load_backgrounds(&conf_context);
-                                    changes_applied = true;
-                                }
-
-                                glowberry_config::DEFAULT_BACKGROUND => {
-                                    tracing::debug!("updating default background");
-                                    let entry = conf_context.default_background();
-
-                                    if state.config.default_background != entry {
-                                        state.config.default_background = entry;
-                                        changes_applied = true;
-                                    }
-                                }
-
-                                glowberry_config::SAME_ON_ALL => {
-                                    tracing::debug!("updating same_on_all");
-                                    state.config.same_on_all = conf_context.same_on_all();
-
-                                    if state.config.same_on_all {
-                                        state.config.outputs.clear();
-                                    } else {
-                                        state.config.load_backgrounds(&conf_context);
-                                    }
-                                    state.config.outputs.clear();
-                                    changes_applied = true;
-                                }
-
-                                // Power saving config keys
-                                glowberry_config::power_saving::ADJUST_ON_BATTERY
-                                | glowberry_config::power_saving::ON_BATTERY_ACTION
-                                | glowberry_config::power_saving::PAUSE_ON_LOW_BATTERY
-                                | glowberry_config::power_saving::LOW_BATTERY_THRESHOLD
-                                | glowberry_config::power_saving::PAUSE_ON_LID_CLOSED => {
-                                    tracing::debug!(key, "power saving config changed");
-                                    let was_paused = state.should_pause_animation();
-                                    state.power_saving_config = conf_context.power_saving_config();
-                                    tracing::info!(config = ?state.power_saving_config, "Updated power saving config");
-                                    // Force reapply frame rates with new config
-                                    state.reapply_frame_rates();
-                                    // Resume animation if we were paused and now we're not
-                                    let is_paused = state.should_pause_animation();
-                                    if was_paused && !is_paused {
-                                        tracing::info!("Resuming shader animation after config change");
-                                        state.request_frame_callbacks();
-                                    }
-                                }
-
-                                _ => {
-                                    tracing::debug!(key, "key modified");
-                                    if let Some(output) = key.strip_prefix("output.") {
-                                        if let Ok(new_entry) = conf_context.entry(key) {
-                                            if let Some(existing) = state.config.entry_mut(output) {
-                                                *existing = new_entry;
-                                                changes_applied = true;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+                let on_change = move |state: &mut GlowBerry, keys: Vec<String>| {
+                    state.config_changed(&conf_context, &keys);
+                };
+
+                match &config_context.0 {
+                    Backend::Cosmic(config) => {
+                        let source = ConfigWatchSource::new(config)
+                            .expect("failed to create ConfigWatchSource");
+
+                        event_loop
+                            .handle()
+                            .insert_source(source, move |(_config, keys), (), state| {
+                                on_change(state, keys);
+                            })
+                            .expect("failed to insert config watching source into event loop");
+                    }
+                    Backend::File(files) => {
+                        match config_watch::watch_files(files, &event_loop.handle(), on_change) {
+                            Ok(watcher) => config_watcher = Some(watcher),
+                            Err(why) => tracing::error!(?why, "Failed to watch config files"),
                         }
+                    }
+                }
 
-                        if changes_applied {
-                            state.apply_backgrounds();
-
-                            #[cfg(target_env = "gnu")]
-                            malloc::trim();
-
-                            tracing::debug!(
-                                same_on_all = state.config.same_on_all,
-                                outputs = ?state.config.outputs,
-                                backgrounds = ?state.config.backgrounds,
-                                default_background = ?state.config.default_background.source,
-                                "new state"
-                            );
-                        }
-                    })
-                    .expect("failed to insert config watching source into event loop");
+                config_checker.record(&config_context);
+                cosmic_bg::mirror(&config_context);
 
                 Config::load(&config_context).unwrap_or_else(|why| {
                     tracing::error!(?why, "Config file error, falling back to defaults");
@@ -288,42 +272,103 @@ impl BackgroundEngine {
             })
             .expect("failed to insert power notification channel into event loop");
 
+        // Follow the theme mode for rules on the dark theme
+        let dark_theme = match cosmic_config::Config::new(rules::THEME_MODE, 1) {
+            Ok(mode) => {
+                match ConfigWatchSource::new(&mode) {
+                    Ok(source) => {
+                        event_loop
+                            .handle()
+                            .insert_source(source, |(mode, _keys), (), state| {
+                                let dark_theme = rules::dark_theme(&mode);
+                                if state.dark_theme != dark_theme {
+                                    state.dark_theme = dark_theme;
+                                    state.reevaluate_rules();
+                                }
+                            })
+                            .map_err(|err| {
+                                eyre!("failed to insert theme mode source into event loop: {err}")
+                            })?;
+                    }
+                    Err(why) => tracing::warn!(?why, "failed to watch the theme mode"),
+                }
+
+                rules::dark_theme(&mode)
+            }
+            Err(why) => {
+                tracing::warn!(?why, "failed to read the theme mode");
+                true
+            }
+        };
+
         let source_tx = img_source::img_source(&event_loop.handle(), |state, source, event| {
-            use notify::event::{ModifyKind, RenameMode};
-
-            match event.kind {
-                notify::EventKind::Create(_)
-                | notify::EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
-                    for w in state
-                        .wallpapers
-                        .iter_mut()
-                        .filter(|w| w.entry.output == source)
-                    {
-                        for p in &event.paths {
-                            if !w.image_queue.contains(p) {
-                                w.image_queue.push_front(p.into());
-                            }
+            for w in state
+                .wallpapers
+                .iter_mut()
+                .filter(|w| w.entry.output == source)
+            {
+                w.source_changed(&event);
+            }
+        });
+
+        // Handle slideshow commands from the command line and the settings app
+        let (control_tx, control_rx) = calloop::channel::channel::<ControlRequest>();
+        control::start_control_service(control_tx);
+
+        event_loop
+            .handle()
+            .insert_source(control_rx, |event, _, state| {
+                if let calloop::channel::Event::Msg(request) = event {
+                    state.handle_control_request(request);
+                }
+            })
+            .map_err(|err| eyre!("failed to insert control channel into event loop: {err}"))?;
+
+        // Decode and scale images off the event loop
+        let (image_tx, image_rx) = calloop::channel::channel::<WorkerResult>();
+        let image_worker = ImageWorker::new(image_tx, config.max_image_pixels);
+
+        event_loop
+            .handle()
+            .insert_source(image_rx, |event, _, state| {
+                let calloop::channel::Event::Msg(result) = event else {
+                    return;
+                };
+
+                match result {
+                    WorkerResult::Image(result) => {
+                        if let Some(wallpaper) = state
+                            .wallpapers
+                            .iter_mut()
+                            .find(|w| w.is_pending_job(result.id))
+                        {
+                            wallpaper.image_loaded(result);
                         }
-                        w.image_queue.retain(|p| !event.paths.contains(p));
-                        // TODO maybe resort or shuffle at some point?
                     }
-                }
-                notify::EventKind::Remove(_)
-                | notify::EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
-                    for w in state
-                        .wallpapers
-                        .iter_mut()
-                        .filter(|w| w.entry.output == source)
-                    {
-                        w.image_queue.retain(|p| !event.paths.contains(p));
+                    WorkerResult::Dimensions(id) => {
+                        if let Some(wallpaper) = state
+                            .wallpapers
+                            .iter_mut()
+                            .find(|w| w.is_pending_dimensions(id))
+                        {
+                            wallpaper.dimensions_read();
+                        }
+                    }
+                    WorkerResult::SortKeys(id) => {
+                        if let Some(wallpaper) = state
+                            .wallpapers
+                            .iter_mut()
+                            .find(|w| w.is_pending_sort_keys(id))
+                        {
+                            wallpaper.sort_keys_read();
+                        }
                     }
                 }
-                _ => {}
-            }
-        });
+            })
+            .map_err(|err| eyre!("failed to insert image worker channel into event loop: {err}"))?;
 
         // initial setup with all images
-        let wallpapers = {
+        let mut wallpapers = {
             let mut wallpapers = Vec::with_capacity(config.backgrounds.len() + 1);
 
             wallpapers.extend({
@@ -331,6 +376,9 @@ impl BackgroundEngine {
                     Wallpaper::new(
                         bg.clone(),
                         qh.clone(),
+                        single_pixel_buffer_manager.clone(),
+                        image_worker.clone(),
+                        ImagePreferences::from_config(&config),
                         event_loop.handle(),
                         source_tx.clone(),
                     )
@@ -342,6 +390,9 @@ impl BackgroundEngine {
             wallpapers.push(Wallpaper::new(
                 config.default_background.clone(),
                 qh.clone(),
+                single_pixel_buffer_manager.clone(),
+                image_worker.clone(),
+                ImagePreferences::from_config(&config),
                 event_loop.handle(),
                 source_tx.clone(),
             ));
@@ -367,6 +418,11 @@ impl BackgroundEngine {
             None
         };
 
+        let gpu_gradients = gpu_gradients(config.gpu_gradients, gpu_renderer.as_ref());
+        for wallpaper in &mut wallpapers {
+            wallpaper.set_gpu_gradients(gpu_gradients);
+        }
+
         let mut bg_state = GlowBerry {
             registry_state: RegistryState::new(&globals),
             output_state: OutputState::new(&globals, &qh),
@@ -375,6 +431,8 @@ impl BackgroundEngine {
             layer_state: LayerShell::bind(&globals, &qh).unwrap(),
             viewporter: globals.bind(&qh, 1..=1, ()).unwrap(),
             fractional_scale_manager: globals.bind(&qh, 1..=1, ()).ok(),
+            single_pixel_buffer_manager,
+            image_worker,
             qh,
             source_tx,
             loop_handle: event_loop.handle(),
@@ -383,14 +441,34 @@ impl BackgroundEngine {
             config,
             active_outputs: Vec::new(),
             gpu_renderer,
+            gpu_gradients,
             connection: conn_for_state,
             power_monitor,
             power_saving_config,
             current_frame_rate_override: None,
             was_on_battery: false,
             was_animation_paused: false,
+            dark_theme,
+            applied_rules: HashMap::new(),
+            rules_timer: None,
+            _config_watcher: config_watcher,
+            config_checker,
         };
 
+        bg_state.schedule_rules_check();
+
+        // The daemon is usually killed at logout, so shader times are saved regularly.
+        event_loop
+            .handle()
+            .insert_source(
+                Timer::from_duration(SHADER_TIME_SAVE_INTERVAL),
+                |_, _, state| {
+                    state.save_shader_times();
+                    TimeoutAction::ToDuration(SHADER_TIME_SAVE_INTERVAL)
+                },
+            )
+            .map_err(|err| eyre!("failed to insert shader time timer into event loop: {err}"))?;
+
         loop {
             event_loop.dispatch(None, &mut bg_state)?;
 
@@ -399,6 +477,8 @@ impl BackgroundEngine {
             }
         }
 
+        bg_state.save_shader_times();
+
         Ok(())
     }
 }
@@ -464,6 +544,11 @@ pub struct GlowBerry {
     layer_state: LayerShell,
     viewporter: wp_viewporter::WpViewporter,
     fractional_scale_manager: Option<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1>,
+    /// Used to draw solid colors without allocating a full-size buffer.
+    single_pixel_buffer_manager:
+        Option<wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1>,
+    /// Worker pool decoding and scaling wallpaper images.
+    image_worker: ImageWorker,
     qh: QueueHandle<GlowBerry>,
     source_tx: calloop::channel::SyncSender<(String, notify::Event)>,
     loop_handle: calloop::LoopHandle<'static, GlowBerry>,
@@ -473,6 +558,8 @@ pub struct GlowBerry {
     active_outputs: Vec<WlOutput>,
     /// GPU renderer for shader wallpapers (lazily initialized).
     gpu_renderer: Option<gpu::GpuRenderer>,
+    /// Whether gradients are rendered by the GPU, if enabled and a GPU is available.
+    gpu_gradients: bool,
     /// Wayland connection for creating GPU surfaces.
     connection: Connection,
     /// Power monitor handle for battery/lid state.
@@ -485,6 +572,16 @@ pub struct GlowBerry {
     was_on_battery: bool,
     /// Whether animation was paused in the last frame (for detecting resume).
     was_animation_paused: bool,
+    /// Whether the desktop uses a dark theme.
+    dark_theme: bool,
+    /// Index of the background rule applied to each output.
+    applied_rules: HashMap<String, usize>,
+    /// Timer checking time based background rules.
+    rules_timer: Option<RegistrationToken>,
+    /// Watcher of the config files, when not using cosmic-config.
+    _config_watcher: Option<notify::RecommendedWatcher>,
+    /// Thread recording the problems of the config after it changes.
+    config_checker: config_check::Recorder,
 }
 
 // Manual Debug impl since wgpu types don't implement Debug
@@ -634,6 +731,95 @@ impl GlowBerry {
             self.was_animation_paused = false;
             self.request_frame_callbacks();
         }
+
+        self.reevaluate_rules();
+    }
+
+    /// The state of the system that background rules are checked against.
+    fn rule_environment(&self) -> rules::Environment {
+        let power = self
+            .power_monitor
+            .as_ref()
+            .map(PowerMonitorHandle::current)
+            .unwrap_or_default();
+
+        rules::Environment::now(power.on_battery, power.lid_is_closed, self.dark_theme)
+    }
+
+    /// Index of the background rule that applies to an output, if any.
+    fn rule_for(&self, env: &rules::Environment, output_info: &OutputInfo) -> Option<usize> {
+        let output = rules::OutputIdentity {
+            name: output_info.name.as_deref().unwrap_or_default(),
+            make: &output_info.make,
+        };
+
+        rules::select(&self.config.rules, env, output)
+    }
+
+    /// Reapply the backgrounds if a different rule applies to any output now.
+    pub(crate) fn reevaluate_rules(&mut self) {
+        let env = self.rule_environment();
+        let selection = self
+            .active_outputs
+            .iter()
+            .filter_map(|output| self.output_state.info(output))
+            .filter_map(|info| {
+                let index = self.rule_for(&env, &info)?;
+                Some((info.name.unwrap_or_default(), index))
+            })
+            .collect::<HashMap<_, _>>();
+
+        if selection != self.applied_rules {
+            tracing::info!(rules = ?selection, "background rules changed");
+            self.apply_backgrounds();
+        }
+    }
+
+    /// Check time based background rules again at the start of every minute.
+    fn schedule_rules_check(&mut self) {
+        if let Some(token) = self.rules_timer.take() {
+            self.loop_handle.remove(token);
+        }
+
+        let Some(wait) = rules::recheck_after(&self.config.rules) else {
+            return;
+        };
+
+        self.rules_timer = self
+            .loop_handle
+            .insert_source(Timer::from_duration(wait), |_, _, state| {
+                state.reevaluate_rules();
+                match rules::recheck_after(&state.config.rules) {
+                    Some(wait) => TimeoutAction::ToDuration(wait),
+                    None => TimeoutAction::Drop,
+                }
+            })
+            .ok();
+    }
+
+    /// Create the wallpaper showing `entry` on a single output.
+    fn output_wallpaper(
+        &self,
+        mut entry: Entry,
+        output: WlOutput,
+        output_info: OutputInfo,
+    ) -> Wallpaper {
+        entry.output = output_info.name.clone().unwrap_or_default();
+
+        let mut wallpaper = Wallpaper::new(
+            entry,
+            self.qh.clone(),
+            self.single_pixel_buffer_manager.clone(),
+            self.image_worker.clone(),
+            ImagePreferences::from_config(&self.config),
+            self.loop_handle.clone(),
+            self.source_tx.clone(),
+        );
+        wallpaper.set_gpu_gradients(self.gpu_gradients);
+
+        wallpaper.layers.push(self.new_layer(output, output_info));
+        _ = wallpaper.save_state();
+        wallpaper
     }
 
     /// Request frame callbacks for all shader layers.
@@ -661,10 +847,18 @@ impl GlowBerry {
             .filter_map(|info| info.name.clone())
             .collect();
 
+        let monitors: Vec<(String, Monitor)> = self
+            .active_outputs
+            .iter()
+            .filter_map(|o| self.output_state.info(o))
+            .filter_map(|info| Some((info.name.clone()?, monitor::identify(&info)?)))
+            .collect();
+
         if let Ok(state_helper) = State::state() {
             let mut state = State::get_entry(&state_helper).unwrap_or_default();
-            if state.connected_outputs != connected {
+            if state.connected_outputs != connected || state.connected_monitors != monitors {
                 state.connected_outputs = connected;
+                state.connected_monitors = monitors;
                 if let Err(err) = state.write_entry(&state_helper) {
                     tracing::error!("Failed to save connected outputs: {err}");
                 } else {
@@ -674,6 +868,36 @@ impl GlowBerry {
         }
     }
 
+    /// Saves the animation time of every shader, so that it continues when
+    /// the shader is recreated.
+    fn save_shader_times(&self) {
+        let times: Vec<(String, Duration)> = self
+            .wallpapers
+            .iter()
+            .flat_map(|wallpaper| &wallpaper.layers)
+            .filter_map(|layer| {
+                // Shaders without animation, such as gradients, keep the saved time.
+                let canvas = &layer.gpu_state.as_ref()?.canvas;
+                let time = canvas.is_animated().then(|| canvas.elapsed())?;
+                Some((layer.output_info.name.clone()?, time))
+            })
+            .collect();
+
+        if times.is_empty() {
+            return;
+        }
+
+        if let Ok(state_helper) = State::state() {
+            let mut state = State::get_entry(&state_helper).unwrap_or_default();
+            for (output, time) in times {
+                state.set_shader_time(&output, time);
+            }
+            if let Err(err) = state.write_entry(&state_helper) {
+                tracing::error!("Failed to save shader times: {err}");
+            }
+        }
+    }
+
     fn shader_physical_size(
         layer_size: Option<(u32, u32)>,
         fractional_scale: Option<u32>,
@@ -732,42 +956,231 @@ impl GlowBerry {
         layer.layer.commit();
     }
 
+    fn handle_control_request(&mut self, request: ControlRequest) {
+        tracing::debug!(?request, "slideshow control request");
+
+        let mut blocked = Vec::new();
+        let mut current = Vec::new();
+
+        for wallpaper in &mut self.wallpapers {
+            if let Some(output) = &request.output
+                && wallpaper.entry.output != *output
+                && !wallpaper
+                    .layers
+                    .iter()
+                    .any(|layer| layer.output_info.name.as_ref() == Some(output))
+            {
+                continue;
+            }
+
+            match request.command {
+                ControlCommand::Next => wallpaper.next(),
+                ControlCommand::Previous => wallpaper.previous(),
+                ControlCommand::Block => blocked.extend(wallpaper.block_current()),
+                ControlCommand::Favorite => {
+                    current.extend(wallpaper.current_image_path().map(Path::to_path_buf));
+                }
+            }
+        }
+
+        if !blocked.is_empty() {
+            self.config.blocklist.extend(blocked);
+            self.update_image_preferences();
+
+            let mut blocklist: Vec<_> = self.config.blocklist.iter().cloned().collect();
+            blocklist.sort();
+            if let Err(why) =
+                glowberry_config::context().and_then(|context| context.set_blocklist(&blocklist))
+            {
+                tracing::error!(?why, "failed to save blocklist");
+            }
+        }
+
+        if !current.is_empty() {
+            // The same image may be shown on several outputs, but is toggled once.
+            current.sort();
+            current.dedup();
+            for path in current {
+                if !self.config.favorites.remove(&path) {
+                    self.config.favorites.insert(path);
+                }
+            }
+            self.update_image_preferences();
+
+            let mut favorites: Vec<_> = self.config.favorites.iter().cloned().collect();
+            favorites.sort();
+            if let Err(why) =
+                glowberry_config::context().and_then(|context| context.set_favorites(&favorites))
+            {
+                tracing::error!(?why, "failed to save favorites");
+            }
+        }
+    }
+
+    /// Applies the config keys that changed in `context`.
+    fn config_changed(&mut self, context: &glowberry_config::Context, keys: &[String]) {
+        let mut changes_applied = false;
+
+        for key in keys {
+            match key.as_str() {
+                glowberry_config::BACKGROUNDS => {
+                    tracing::debug!("updating backgrounds");
+                    self.config.load_backgrounds(context);
+                    changes_applied = true;
+                }
+
+                glowberry_config::DEFAULT_BACKGROUND => {
+                    tracing::debug!("updating default background");
+                    let entry = context.default_background();
+
+                    if self.config.default_background != entry {
+                        self.config.default_background = entry;
+                        changes_applied = true;
+                    }
+                }
+
+                glowberry_config::SAME_ON_ALL => {
+                    tracing::debug!("updating same_on_all");
+                    self.config.same_on_all = context.same_on_all();
+
+                    if self.config.same_on_all {
+                        self.config.outputs.clear();
+                    } else {
+                        self.config.load_backgrounds(context);
+                    }
+                    self.config.outputs.clear();
+                    changes_applied = true;
+                }
+
+                glowberry_config::MAX_IMAGE_PIXELS => {
+                    tracing::debug!("updating image pixel limit");
+                    self.config.max_image_pixels = context.max_image_pixels();
+                    self.image_worker
+                        .set_max_pixels(self.config.max_image_pixels);
+                }
+
+                glowberry_config::rules::RULES => {
+                    tracing::debug!("updating background rules");
+                    self.config.rules = context.rules();
+                    self.schedule_rules_check();
+                    changes_applied = true;
+                }
+
+                glowberry_config::GPU_GRADIENTS => {
+                    tracing::debug!("updating gradient rendering");
+                    self.config.gpu_gradients = context.gpu_gradients();
+                    self.gpu_gradients =
+                        gpu_gradients(self.config.gpu_gradients, self.gpu_renderer.as_ref());
+                    changes_applied = true;
+                }
+
+                glowberry_config::BLOCKLIST | glowberry_config::FAVORITES => {
+                    tracing::debug!(key, "updating image preferences");
+                    self.config.blocklist = context.blocklist().into_iter().collect();
+                    self.config.favorites = context.favorites().into_iter().collect();
+                    self.update_image_preferences();
+                }
+
+                // Power saving config keys
+                glowberry_config::power_saving::ADJUST_ON_BATTERY
+                | glowberry_config::power_saving::ON_BATTERY_ACTION
+                | glowberry_config::power_saving::PAUSE_ON_LOW_BATTERY
+                | glowberry_config::power_saving::LOW_BATTERY_THRESHOLD
+                | glowberry_config::power_saving::PAUSE_ON_LID_CLOSED => {
+                    tracing::debug!(key, "power saving config changed");
+                    let was_paused = self.should_pause_animation();
+                    self.power_saving_config = context.power_saving_config();
+                    tracing::info!(config = ?self.power_saving_config, "Updated power saving config");
+                    // Force reapply frame rates with new config
+                    self.reapply_frame_rates();
+                    // Resume animation if we were paused and now we're not
+                    let is_paused = self.should_pause_animation();
+                    if was_paused && !is_paused {
+                        tracing::info!("Resuming shader animation after config change");
+                        self.request_frame_callbacks();
+                    }
+                }
+
+                _ => {
+                    tracing::debug!(key, "key modified");
+                    if let Some(output) = key.strip_prefix("output.") {
+                        if let Ok(new_entry) = context.entry(key) {
+                            if let Some(existing) = self.config.entry_mut(output) {
+                                *existing = new_entry;
+                                changes_applied = true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        self.config_checker.record(context);
+        cosmic_bg::mirror(context);
+
+        if changes_applied {
+            self.apply_backgrounds();
+
+            #[cfg(target_env = "gnu")]
+            malloc::trim();
+
+            tracing::debug!(
+                same_on_all = self.config.same_on_all,
+                outputs = ?self.config.outputs,
+                backgrounds = ?self.config.backgrounds,
+                default_background = ?self.config.default_background.source,
+                "new state"
+            );
+        }
+    }
+
+    /// Passes changed blocked and favorite images on to every wallpaper.
+    fn update_image_preferences(&mut self) {
+        let preferences = ImagePreferences::from_config(&self.config);
+        for wallpaper in &mut self.wallpapers {
+            wallpaper.set_preferences(preferences.clone());
+        }
+    }
+
     fn apply_backgrounds(&mut self) {
+        self.save_shader_times();
         self.wallpapers.clear();
+        self.applied_rules.clear();
+
+        let env = self.rule_environment();
 
         let mut all_wallpaper = Wallpaper::new(
             self.config.default_background.clone(),
             self.qh.clone(),
+            self.single_pixel_buffer_manager.clone(),
+            self.image_worker.clone(),
+            ImagePreferences::from_config(&self.config),
             self.loop_handle.clone(),
             self.source_tx.clone(),
         );
+        all_wallpaper.set_gpu_gradients(self.gpu_gradients);
 
-        let mut backgrounds = self.config.backgrounds.clone();
-        backgrounds.sort_by(|a, b| a.output.cmp(&b.output));
-
-        'outer: for output in &self.active_outputs {
+        for output in &self.active_outputs {
             let Some(output_info) = self.output_state.info(output) else {
                 continue;
             };
 
             let o_name = output_info.name.clone().unwrap_or_default();
-            for background in &backgrounds {
-                if background.output == o_name {
-                    let mut new_wallpaper = Wallpaper::new(
-                        background.clone(),
-                        self.qh.clone(),
-                        self.loop_handle.clone(),
-                        self.source_tx.clone(),
-                    );
 
-                    new_wallpaper
-                        .layers
-                        .push(self.new_layer(output.clone(), output_info));
-                    _ = new_wallpaper.save_state();
-                    self.wallpapers.push(new_wallpaper);
+            if let Some(index) = self.rule_for(&env, &output_info) {
+                self.applied_rules.insert(o_name, index);
+                let entry = self.config.rules[index].background.clone();
+                let wallpaper = self.output_wallpaper(entry, output.clone(), output_info);
+                self.wallpapers.push(wallpaper);
+                continue;
+            }
 
-                    continue 'outer;
-                }
+            let monitor = monitor::identify(&output_info);
+            if let Some(background) = self.config.entry_for(&o_name, monitor.as_ref()) {
+                let wallpaper =
+                    self.output_wallpaper(background.clone(), output.clone(), output_info);
+                self.wallpapers.push(wallpaper);
+                continue;
             }
 
             all_wallpaper
@@ -819,6 +1232,97 @@ impl GlowBerry {
         }
     }
 
+    /// Create or resize the SHM buffer pool of a static wallpaper layer.
+    ///
+    /// Returns `false` if the pool could not be set up.
+    fn prepare_shm_pool(&mut self, wp_idx: usize, layer_idx: usize, w: u32, h: u32) -> bool {
+        let pool_len = self.wallpapers[wp_idx].shm_pool_len(w, h);
+        let w_layer = &mut self.wallpapers[wp_idx].layers[layer_idx];
+
+        if let Some(pool) = w_layer.pool.as_mut() {
+            if let Err(why) = pool.resize(pool_len) {
+                tracing::error!(?why, "failed to resize pool");
+                return false;
+            }
+        } else {
+            match SlotPool::new(pool_len, &self.shm_state) {
+                Ok(pool) => {
+                    w_layer.pool.replace(pool);
+                }
+                Err(why) => {
+                    tracing::error!(?why, "failed to create pool");
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Show the next item of the playlist on `output`, returning how long it is shown.
+    pub(crate) fn next_playlist_item(&mut self, output: &str) -> Option<std::time::Duration> {
+        let wp_idx = self
+            .wallpapers
+            .iter()
+            .position(|w| w.entry.output == output)?;
+
+        let was_shader = self.wallpapers[wp_idx].is_shader();
+        self.wallpapers[wp_idx].next_playlist_item();
+
+        // Shader canvases are recreated even between two shaders. The SHM pool
+        // only exists once the GPU surface is gone, so drawing waits until then.
+        if was_shader || self.wallpapers[wp_idx].is_shader() {
+            self.switch_renderer(wp_idx);
+        } else {
+            self.wallpapers[wp_idx].draw();
+        }
+
+        self.wallpapers[wp_idx].playlist_item_duration()
+    }
+
+    /// Move the layers of a wallpaper between GPU and SHM rendering to match its source.
+    ///
+    /// GPU state is created when a shader is shown and released when it no longer
+    /// is, along with the GPU renderer once no layer uses it.
+    fn switch_renderer(&mut self, wp_idx: usize) {
+        let shader_source = self.wallpapers[wp_idx].shader_source();
+        self.save_shader_times();
+
+        for layer_idx in 0..self.wallpapers[wp_idx].layers.len() {
+            let layer = &mut self.wallpapers[wp_idx].layers[layer_idx];
+            // A surface can only be used by one GPU surface at a time.
+            layer.gpu_state = None;
+            layer.needs_redraw = true;
+
+            let Some((w, h)) = layer.size else {
+                // The layer is set up once it is configured.
+                continue;
+            };
+
+            match &shader_source {
+                Some(shader_source) => {
+                    layer.pool = None;
+                    self.init_gpu_layer_internal(wp_idx, layer_idx, shader_source);
+                }
+                None => {
+                    self.prepare_shm_pool(wp_idx, layer_idx, w, h);
+                }
+            }
+        }
+
+        if shader_source.is_none() {
+            self.wallpapers[wp_idx].draw();
+        }
+
+        let gpu_in_use = self
+            .wallpapers
+            .iter()
+            .any(|w| w.layers.iter().any(|layer| layer.gpu_state.is_some()));
+        if !gpu_in_use && self.gpu_renderer.take().is_some() {
+            tracing::info!("Released GPU renderer, no shader wallpaper is shown");
+        }
+    }
+
     /// Initialize GPU state for a shader wallpaper layer (internal version using indices).
     fn init_gpu_layer_internal(
         &mut self,
@@ -866,8 +1370,20 @@ impl GlowBerry {
         // Configure surface at native resolution
         let surface_config = gpu.configure_surface(&surface, physical_width, physical_height);
 
+        // Continue the animation where the shader on this output left off
+        let start = output_name
+            .as_deref()
+            .and_then(|name| {
+                let state_helper = State::state().ok()?;
+                State::get_entry(&state_helper)
+                    .unwrap_or_default()
+                    .shader_time(name)
+            })
+            .unwrap_or_default();
+
         // Create fragment canvas
-        match fragment_canvas::FragmentCanvas::new(gpu, shader_source, surface_config.format) {
+        match fragment_canvas::FragmentCanvas::new(gpu, shader_source, surface_config.format, start)
+        {
             Ok(mut canvas) => {
                 canvas.update_resolution(gpu.queue(), physical_width, physical_height);
 
@@ -1049,13 +1565,18 @@ impl CompositorHandler for GlowBerry {
                     // Request next frame callback to continue animation
                     // Only request if not paused - when paused, GPU goes truly idle
                     // The on_power_state_changed handler will request frames when resuming
-                    if !should_pause {
-                        surface.frame(qh, surface.clone());
-                        layer.layer.commit();
-                    } else {
+                    // Shaders without animation only need frames until they are rendered.
+                    let needs_frames = layer
+                        .gpu_state
+                        .as_ref()
+                        .is_none_or(|gpu_state| gpu_state.canvas.needs_frames());
+                    if should_pause {
                         // Track that we're paused so on_power_state_changed can resume us
                         self.was_animation_paused = true;
                         tracing::debug!(output = ?layer.output_info.name, "Shader paused, not requesting frame callback");
+                    } else if needs_frames {
+                        surface.frame(qh, surface.clone());
+                        layer.layer.commit();
                     }
                 }
                 break;
@@ -1108,17 +1629,39 @@ impl OutputHandler for GlowBerry {
             return;
         };
 
-        if let Some(pos) = self
-            .wallpapers
-            .iter()
-            .position(|w| match w.entry.output.as_str() {
-                "all" => !w.layers.iter().any(|l| l.wl_output == wl_output),
-                name => {
-                    Some(name) == output_info.name.as_deref()
-                        && !w.layers.iter().any(|l| l.wl_output == wl_output)
+        let name = output_info.name.clone().unwrap_or_default();
+        let env = self.rule_environment();
+        let rule = self.rule_for(&env, &output_info);
+        let entry = match rule {
+            Some(index) => {
+                self.applied_rules.insert(name.clone(), index);
+                Some(self.config.rules[index].background.clone())
+            }
+            None => {
+                let monitor = monitor::identify(&output_info);
+                self.config.entry_for(&name, monitor.as_ref()).cloned()
+            }
+        };
+
+        if let Some(mut entry) = entry {
+            entry.output = name;
+
+            if let Some(pos) = self.wallpapers.iter().position(|w| {
+                w.entry == entry && !w.layers.iter().any(|l| l.wl_output == wl_output)
+            }) {
+                let layer = self.new_layer(wl_output, output_info);
+                self.wallpapers[pos].layers.push(layer);
+                if let Err(err) = self.wallpapers[pos].save_state() {
+                    tracing::error!("{err}");
                 }
-            })
-        {
+            } else {
+                let wallpaper = self.output_wallpaper(entry, wl_output, output_info);
+                // Keep it ahead of the `all` wallpaper, which claims any output when searched first.
+                self.wallpapers.insert(0, wallpaper);
+            }
+        } else if let Some(pos) = self.wallpapers.iter().position(|w| {
+            w.entry.output == "all" && !w.layers.iter().any(|l| l.wl_output == wl_output)
+        }) {
             let layer = self.new_layer(wl_output, output_info);
             self.wallpapers[pos].layers.push(layer);
             if let Err(err) = self.wallpapers[pos].save_state() {
@@ -1186,6 +1729,10 @@ impl OutputHandler for GlowBerry {
             return;
         };
 
+        if let Some(name) = &output_info.name {
+            self.applied_rules.remove(name);
+        }
+
         // state cleanup
         if let Ok(state_helper) = State::state() {
             let mut state = State::get_entry(&state_helper).unwrap_or_default();
@@ -1200,6 +1747,9 @@ impl OutputHandler for GlowBerry {
         // Update connected outputs in state for settings app
         self.save_connected_outputs();
 
+        // Continue the shader when the output returns
+        self.save_shader_times();
+
         let Some(output_wallpaper) =
             self.wallpapers
                 .iter_mut()
@@ -1257,7 +1807,7 @@ impl LayerShellHandler for GlowBerry {
         for (wp_idx, wallpaper) in self.wallpapers.iter_mut().enumerate() {
             if let Some(layer_idx) = wallpaper.layers.iter().position(|l| &l.layer == layer) {
                 let is_shader = wallpaper.is_shader();
-                let shader_source = wallpaper.shader_source().cloned();
+                let shader_source = wallpaper.shader_source();
                 found_info = Some((wp_idx, layer_idx, is_shader, shader_source));
 
                 // Update layer state
@@ -1290,26 +1840,9 @@ impl LayerShellHandler for GlowBerry {
             }
         } else {
             // Static wallpaper - use SHM buffer pool
-            let w_layer = &mut self.wallpapers[wp_idx].layers[layer_idx];
-
-            if let Some(pool) = w_layer.pool.as_mut() {
-                if let Err(why) = pool.resize(w as usize * h as usize * 4) {
-                    tracing::error!(?why, "failed to resize pool");
-                    return;
-                }
-            } else {
-                match SlotPool::new(w as usize * h as usize * 4, &self.shm_state) {
-                    Ok(pool) => {
-                        w_layer.pool.replace(pool);
-                    }
-                    Err(why) => {
-                        tracing::error!(?why, "failed to create pool");
-                        return;
-                    }
-                }
+            if self.prepare_shm_pool(wp_idx, layer_idx, w, h) {
+                self.wallpapers[wp_idx].draw();
             }
-
-            self.wallpapers[wp_idx].draw();
         }
     }
 }
@@ -1328,6 +1861,23 @@ delegate_registry!(GlowBerry);
 delegate_noop!(GlowBerry: wp_viewporter::WpViewporter);
 delegate_noop!(GlowBerry: wp_viewport::WpViewport);
 delegate_noop!(GlowBerry: wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1);
+delegate_noop!(GlowBerry: wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1);
+
+impl Dispatch<wl_buffer::WlBuffer, ()> for GlowBerry {
+    fn event(
+        _: &mut GlowBerry,
+        buffer: &wl_buffer::WlBuffer,
+        event: wl_buffer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<GlowBerry>,
+    ) {
+        // Single-pixel buffers are attached once; destroy them when the compositor is done.
+        if let wl_buffer::Event::Release = event {
+            buffer.destroy();
+        }
+    }
+}
 
 impl Dispatch<wp_fractional_scale_v1::WpFractionalScaleV1, Weak<wl_surface::WlSurface>>
     for GlowBerry
@@ -1383,6 +1933,19 @@ impl ProvidesRegistryState for GlowBerry {
     registry_handlers![OutputState];
 }
 
+/// Whether gradients are rendered by the GPU: if `enabled` and a GPU is available.
+fn gpu_gradients(enabled: bool, renderer: Option<&gpu::GpuRenderer>) -> bool {
+    if !enabled || renderer.is_some() {
+        return enabled;
+    }
+
+    let available = gpu::GpuRenderer::try_new().is_some();
+    if !available {
+        tracing::warn!("No GPU to render gradients, drawing them on the CPU");
+    }
+    available
+}
+
 #[cfg(test)]
 mod tests {
     use super::GlowBerry;
@@ -8,7 +8,7 @@
 //! directly on the lock surface.
 
 use crate::user_context::UserContext;
-use glowberry_config::{Color, Config, ShaderSource, Source};
+use glowberry_config::{Color, Config, FilterMethod, ShaderSource, Source};
 use image::DynamicImage;
 use std::path::PathBuf;
 
@@ -62,6 +62,12 @@ pub fn load_background_image(
     height: u32,
 ) -> Option<DynamicImage> {
     let source = load_background_source(user_context)?;
+    let max_pixels = {
+        let _env_guard = user_context.apply();
+        glowberry_config::context().map_or(glowberry_config::DEFAULT_MAX_IMAGE_PIXELS, |ctx| {
+            ctx.max_image_pixels()
+        })
+    };
 
     match source {
         BackgroundSource::Image(path) => {
@@ -72,9 +78,18 @@ pub fn load_background_image(
                 path
             };
 
-            let img = image::open(&img_path).ok()?;
+            let img = crate::loader::decode(&img_path, Some((width, height)), max_pixels)
+                .inspect_err(|why| {
+                    tracing::warn!(?why, ?img_path, "failed to load background image")
+                })
+                .ok()?;
             // Scale to fit the target dimensions
-            Some(crate::scaler::zoom(&img, width, height))
+            Some(crate::scaler::zoom(
+                &img,
+                &FilterMethod::default(),
+                width,
+                height,
+            ))
         }
         BackgroundSource::SolidColor(color) => {
             // Create a solid color image
@@ -121,9 +136,13 @@ fn source_to_background(source: &Source) -> Option<BackgroundSource> {
         Source::Color(Color::Single(color)) => Some(BackgroundSource::SolidColor(*color)),
         Source::Color(Color::Gradient(gradient)) => Some(BackgroundSource::Gradient {
             colors: gradient.colors.to_vec(),
-            radius: gradient.radius,
+            radius: gradient.angle,
         }),
         Source::Shader(shader_source) => Some(BackgroundSource::Shader(shader_source.clone())),
+        // Surfaces outside the session show the first item of a playlist.
+        Source::Playlist(items) => items
+            .first()
+            .and_then(|item| source_to_background(&item.source)),
     }
 }
 
@@ -132,17 +151,7 @@ fn find_first_image_in_dir(dir: &PathBuf) -> Option<PathBuf> {
     let mut images: Vec<PathBuf> = entries
         .filter_map(|e| e.ok())
         .map(|e| e.path())
-        .filter(|p| {
-            p.extension()
-                .map(|ext| {
-                    let ext = ext.to_str().unwrap_or("").to_lowercase();
-                    matches!(
-                        ext.as_str(),
-                        "jpg" | "jpeg" | "png" | "webp" | "gif" | "bmp"
-                    )
-                })
-                .unwrap_or(false)
-        })
+        .filter(|p| crate::loader::is_supported_image(p))
         .collect();
 
     images.sort();
@@ -12,10 +12,10 @@ use image::DynamicImage;
 use std::borrow::Cow;
 use std::time::{Duration, Instant};
 
-use crate::gpu::GpuRenderer;
+use crate::{gpu::GpuRenderer, shader_time::ShaderTime};
 
 /// WGSL preamble prepended to user shaders.
-const WGSL_PREAMBLE: &str = r#"
+pub(crate) const WGSL_PREAMBLE: &str = r#"
 // GlowBerry live wallpaper uniforms
 @group(0) @binding(0) var<uniform> iResolution: vec2f;
 @group(0) @binding(1) var<uniform> iTime: f32;
@@ -138,7 +138,12 @@ pub struct FragmentCanvas {
     time_buffer: wgpu::Buffer,
 
     // Animation state
-    start_time: Instant,
+    time: ShaderTime,
+    /// Whether the shader uses `iTime`. Other shaders are only rendered again when resized.
+    animated: bool,
+    /// Whether the current resolution has been rendered.
+    rendered: bool,
+    resolution: (u32, u32),
     last_frame: Instant,
     frame_interval: Duration,
     /// The configured (original) frame rate from the shader source.
@@ -149,11 +154,12 @@ pub struct FragmentCanvas {
 }
 
 impl FragmentCanvas {
-    /// Create a new fragment canvas from a shader source.
+    /// Create a new fragment canvas from a shader source, animated from `start`.
     pub fn new(
         renderer: &GpuRenderer,
         source: &ShaderSource,
         format: wgpu::TextureFormat,
+        start: Duration,
     ) -> Result<Self, ShaderError> {
         let device = renderer.device();
         let queue = renderer.queue();
@@ -165,6 +171,7 @@ impl FragmentCanvas {
         };
 
         let language = detect_language(source);
+        let animated = shader_code.contains("iTime");
 
         // Load optional background texture
         let (background_texture, has_texture) = if let Some(img_path) = &source.background_image {
@@ -382,7 +389,10 @@ impl FragmentCanvas {
             bind_group,
             resolution_buffer,
             time_buffer,
-            start_time: Instant::now(),
+            time: ShaderTime::new(source.clock, start),
+            animated,
+            rendered: false,
+            resolution: (0, 0),
             last_frame: Instant::now(),
             frame_interval,
             configured_frame_rate,
@@ -439,19 +449,41 @@ impl FragmentCanvas {
     }
 
     /// Update the resolution uniform.
-    pub fn update_resolution(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+    pub fn update_resolution(&mut self, queue: &wgpu::Queue, width: u32, height: u32) {
         let data = [width as f32, height as f32];
         queue.write_buffer(&self.resolution_buffer, 0, bytemuck::cast_slice(&data));
+
+        if self.resolution != (width, height) {
+            self.resolution = (width, height);
+            self.rendered = false;
+        }
     }
 
-    /// Check if enough time has passed for the next frame.
+    /// Check if the next frame should be rendered: once enough time has passed
+    /// for animated shaders, and after a resize for the others.
     pub fn should_render(&self) -> bool {
-        self.last_frame.elapsed() >= self.frame_interval
+        !self.rendered || (self.animated && self.last_frame.elapsed() >= self.frame_interval)
+    }
+
+    /// Whether frame callbacks are needed to render the next frame.
+    pub fn needs_frames(&self) -> bool {
+        self.animated || !self.rendered
+    }
+
+    /// Whether the shader uses the animation time.
+    pub fn is_animated(&self) -> bool {
+        self.animated
     }
 
     /// Mark that a frame was rendered.
     pub fn mark_frame_rendered(&mut self) {
         self.last_frame = Instant::now();
+        self.rendered = true;
+    }
+
+    /// The current animation time.
+    pub fn elapsed(&self) -> Duration {
+        self.time.elapsed()
     }
 
     /// Get the configured (original) frame rate.
@@ -479,7 +511,7 @@ impl FragmentCanvas {
         let queue = renderer.queue();
 
         // Update time uniform
-        let elapsed = self.start_time.elapsed().as_secs_f32();
+        let elapsed = self.time.elapsed().as_secs_f32();
         queue.write_buffer(&self.time_buffer, 0, bytemuck::bytes_of(&elapsed));
 
         // Create command encoder
@@ -516,7 +548,7 @@ impl FragmentCanvas {
 
 #[cfg(test)]
 mod tests {
-    use glowberry_config::{ShaderContent, ShaderLanguage, ShaderSource};
+    use glowberry_config::{ShaderClock, ShaderContent, ShaderLanguage, ShaderSource};
 
     #[test]
     fn detects_glsl_language_for_frag_extension() {
@@ -527,6 +559,7 @@ mod tests {
             background_image: None,
             language: ShaderLanguage::Wgsl,
             frame_rate: 30,
+            clock: ShaderClock::default(),
         };
 
         assert_eq!(super::detect_language(&source), ShaderLanguage::Glsl);
@@ -28,6 +28,11 @@ impl GpuRenderer {
     ///
     /// Panics if no suitable GPU adapter is found.
     pub fn new() -> Self {
+        Self::try_new().expect("Failed to find GPU adapter for live wallpapers")
+    }
+
+    /// Create a new GPU renderer, or `None` if no suitable GPU adapter is found.
+    pub fn try_new() -> Option<Self> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::VULKAN | wgpu::Backends::GL,
             ..Default::default()
@@ -40,7 +45,8 @@ impl GpuRenderer {
                 compatible_surface: None,
             })
             .block_on()
-            .expect("Failed to find GPU adapter for live wallpapers");
+            .inspect_err(|err| tracing::warn!(?err, "No GPU adapter found"))
+            .ok()?;
 
         tracing::info!(
             "GPU renderer using: {} ({:?})",
@@ -51,14 +57,15 @@ impl GpuRenderer {
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor::default())
             .block_on()
-            .expect("Failed to create GPU device");
+            .inspect_err(|err| tracing::warn!(?err, "Failed to create GPU device"))
+            .ok()?;
 
-        Self {
+        Some(Self {
             instance,
             adapter,
             device,
             queue,
-        }
+        })
     }
 
     /// Create a wgpu surface from a Wayland surface.
@@ -0,0 +1,367 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Persistent disk cache of scaled wallpaper images in `$XDG_CACHE_HOME/glowberry/`.
+//!
+//! Entries hold ready-to-draw RGB pixels, keyed by the source file and the
+//! parameters used to scale it, so the daemon can paint a known wallpaper without
+//! decoding or scaling it again. Images with more than 8 bits per channel are
+//! stored with 16 bits, keeping their precision for 10-bit outputs. The cache is bounded in size and evicts the least
+//! recently used entries first, using file modification times as access times.
+
+use crate::draw;
+use glowberry_config::{FilterMethod, ScalingMode};
+use image::{DynamicImage, ImageBuffer, Rgb};
+use std::{
+    borrow::Cow,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Maximum total size of the cache directory in bytes.
+pub const MAX_CACHE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Identifies the file format and version of cache entries.
+///
+/// Bumped whenever the pixels stored for a source change, such as when colour
+/// management was added, so that stale entries are discarded.
+const MAGIC: &[u8; 4] = b"GBC3";
+
+/// Length of the header before the key: magic, width, height, bit depth and key length.
+const HEADER_LEN: usize = MAGIC.len() + 4 + 4 + 1 + 4;
+
+/// Key of a scaled image in the cache.
+///
+/// Entries are named after a hash of the key and store the full key, so that
+/// an entry of another image with the same hash is never mistaken for this one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheKey {
+    hash: u64,
+    id: Vec<u8>,
+}
+
+impl CacheKey {
+    /// Derives the key of `path` scaled to `width` x `height`.
+    ///
+    /// Returns `None` if the source file's metadata can't be read.
+    pub fn new(
+        path: &Path,
+        scaling_mode: &ScalingMode,
+        filter_method: &FilterMethod,
+        width: u32,
+        height: u32,
+    ) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+        let mut id = path.as_os_str().as_encoded_bytes().to_vec();
+        // Floats in `ScalingMode::Fit` aren't `Hash`, but their debug output is stable.
+        id.extend_from_slice(
+            format!(
+                "\n{}.{:09} {} {scaling_mode:?} {filter_method:?} {width}x{height}",
+                modified.as_secs(),
+                modified.subsec_nanos(),
+                metadata.len(),
+            )
+            .as_bytes(),
+        );
+
+        Some(Self::from_id(id))
+    }
+
+    fn from_id(id: Vec<u8>) -> Self {
+        // FNV-1a, which unlike `DefaultHasher` is stable across Rust releases.
+        let hash = id.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+
+        Self { hash, id }
+    }
+
+    fn file_name(&self) -> String {
+        format!("{:016x}.rgb", self.hash)
+    }
+}
+
+/// Size-bounded cache of scaled images on disk.
+#[derive(Debug)]
+pub struct ImageCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ImageCache {
+    /// Opens the cache in `$XDG_CACHE_HOME/glowberry/`, falling back to `~/.cache/glowberry/`.
+    pub fn open() -> Option<Self> {
+        let cache_home = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+        Some(Self::with_dir(
+            cache_home.join("glowberry"),
+            MAX_CACHE_BYTES,
+        ))
+    }
+
+    pub fn with_dir(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes }
+    }
+
+    /// Loads a cached image, marking it as recently used.
+    pub fn load(&self, key: &CacheKey) -> Option<DynamicImage> {
+        let path = self.dir.join(key.file_name());
+        let mut file = File::open(&path).ok()?;
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).ok()?;
+
+        let image = match decode_entry(&data) {
+            Some((id, image)) if id == key.id => image,
+            // Another image whose key has the same hash, replaced once this one is stored.
+            Some(_) => return None,
+            None => {
+                tracing::debug!(?path, "discarding invalid cache entry");
+                let _ = fs::remove_file(&path);
+                return None;
+            }
+        };
+
+        // Touch the entry so that eviction keeps recently used images.
+        if let Err(why) = File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+        {
+            tracing::debug!(?why, ?path, "failed to update cache entry access time");
+        }
+
+        Some(image)
+    }
+
+    /// Stores an image, evicting the least recently used entries if the cache is full.
+    ///
+    /// The image must be in the format returned by [`stored_format`].
+    pub fn store(&self, key: &CacheKey, image: &DynamicImage) {
+        if let Err(why) = self.try_store(key, image) {
+            tracing::warn!(?why, dir = ?self.dir, "failed to store image in cache");
+        }
+    }
+
+    fn try_store(&self, key: &CacheKey, image: &DynamicImage) -> io::Result<()> {
+        let (depth, pixels): (u8, Cow<'_, [u8]>) = match image {
+            DynamicImage::ImageRgb8(buffer) => (8, Cow::Borrowed(buffer.as_raw())),
+            DynamicImage::ImageRgb16(buffer) => (
+                16,
+                Cow::Owned(buffer.iter().flat_map(|c| c.to_le_bytes()).collect()),
+            ),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "unsupported pixel format",
+                ));
+            }
+        };
+
+        fs::create_dir_all(&self.dir)?;
+
+        let path = self.dir.join(key.file_name());
+        // Write to a temporary file first so that readers never see partial entries.
+        let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&image.width().to_le_bytes())?;
+        file.write_all(&image.height().to_le_bytes())?;
+        file.write_all(&[depth])?;
+        file.write_all(&(key.id.len() as u32).to_le_bytes())?;
+        file.write_all(&key.id)?;
+        file.write_all(&pixels)?;
+        drop(file);
+
+        fs::rename(&tmp_path, &path)?;
+
+        self.evict()
+    }
+
+    /// Removes the least recently used entries until the cache fits within its size limit.
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+
+        for entry in fs::read_dir(&self.dir)?.filter_map(Result::ok) {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+
+            if !metadata.is_file() {
+                continue;
+            }
+
+            total += metadata.len();
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+            entries.push((modified, metadata.len(), entry.path()));
+        }
+
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+
+        entries.sort_by_key(|(modified, _, _)| *modified);
+
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts a scaled image to the format stored in the cache: 16-bit RGB for
+/// images with more than 8 bits per channel, and 8-bit RGB otherwise.
+pub fn stored_format(image: DynamicImage) -> DynamicImage {
+    match image {
+        image @ (DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgb16(_)) => image,
+        image if draw::is_high_bit_depth(&image) => DynamicImage::from(image.to_rgb16()),
+        image => DynamicImage::from(image.to_rgb8()),
+    }
+}
+
+/// Decodes an entry into its key and image.
+fn decode_entry(data: &[u8]) -> Option<(&[u8], DynamicImage)> {
+    if data.len() < HEADER_LEN || &data[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+
+    let number = |offset: usize| {
+        let bytes = data[offset..offset + 4].try_into().ok()?;
+        Some(u32::from_le_bytes(bytes))
+    };
+
+    let width = number(MAGIC.len())?;
+    let height = number(MAGIC.len() + 4)?;
+    let depth = data[MAGIC.len() + 8];
+    let id_len = number(MAGIC.len() + 9)? as usize;
+
+    let id = data.get(HEADER_LEN..HEADER_LEN + id_len)?;
+    let pixels = &data[HEADER_LEN + id_len..];
+
+    let image = match depth {
+        8 => DynamicImage::from(ImageBuffer::<Rgb<u8>, _>::from_raw(
+            width,
+            height,
+            pixels.to_vec(),
+        )?),
+        16 => {
+            let pixels = pixels
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            DynamicImage::from(ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, pixels)?)
+        }
+        _ => return None,
+    };
+
+    Some((id, image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheKey, HEADER_LEN, ImageCache};
+    use image::{DynamicImage, ImageBuffer, RgbImage};
+    use std::{
+        fs::{self, File},
+        time::{Duration, SystemTime},
+    };
+
+    fn cache_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("glowberry-cache-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn key(id: &str) -> CacheKey {
+        CacheKey::from_id(id.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn stored_images_round_trip() {
+        let dir = cache_dir("round-trip");
+        let cache = ImageCache::with_dir(dir.clone(), u64::MAX);
+        let image = DynamicImage::from(RgbImage::from_fn(3, 2, |x, y| {
+            image::Rgb([x as u8, y as u8, 7])
+        }));
+        // 16-bit images keep their precision.
+        let deep = DynamicImage::from(ImageBuffer::from_fn(3, 2, |x, y| {
+            image::Rgb::<u16>([x as u16 * 1001, y as u16, 65535])
+        }));
+
+        cache.store(&key("a"), &image);
+        cache.store(&key("b"), &deep);
+
+        assert_eq!(cache.load(&key("a")), Some(image));
+        assert_eq!(cache.load(&key("b")), Some(deep));
+        assert_eq!(cache.load(&key("c")), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn colliding_keys_are_told_apart() {
+        let dir = cache_dir("collision");
+        let cache = ImageCache::with_dir(dir.clone(), u64::MAX);
+        let image = DynamicImage::from(RgbImage::new(2, 2));
+        let stored = CacheKey {
+            hash: 1,
+            id: b"/a.jpg".to_vec(),
+        };
+        let other = CacheKey {
+            hash: 1,
+            id: b"/b.jpg".to_vec(),
+        };
+
+        cache.store(&stored, &image);
+
+        assert_eq!(cache.load(&other), None);
+        assert_eq!(cache.load(&stored), Some(image));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_entries() {
+        let dir = cache_dir("evict");
+        let image = DynamicImage::from(RgbImage::new(4, 4));
+        let entry_len = (HEADER_LEN + 1 + 4 * 4 * 3) as u64;
+        let cache = ImageCache::with_dir(dir.clone(), 2 * entry_len);
+
+        // Entries are aged explicitly, as file times may be coarser than the test runs.
+        let age = |id: &str, hours: u64| {
+            let modified = SystemTime::now() - Duration::from_secs(hours * 3600);
+            File::options()
+                .write(true)
+                .open(dir.join(key(id).file_name()))
+                .and_then(|file| file.set_modified(modified))
+                .unwrap();
+        };
+
+        cache.store(&key("1"), &image);
+        age("1", 2);
+        cache.store(&key("2"), &image);
+        age("2", 1);
+        // Loading the first entry makes the second one the least recently used.
+        assert!(cache.load(&key("1")).is_some());
+        cache.store(&key("3"), &image);
+
+        assert!(cache.load(&key("1")).is_some());
+        assert!(cache.load(&key("2")).is_none());
+        assert!(cache.load(&key("3")).is_some());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Reference-counted store of decoded and scaled wallpaper images.
+//!
+//! Wallpapers showing the same file, such as one image on several outputs, share
+//! a single decoded original and a single copy of each scaled size. The store only
+//! holds weak references, so images are released once no wallpaper uses them.
+
+use glowberry_config::{FilterMethod, ScalingMode};
+use image::DynamicImage;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+    time::SystemTime,
+};
+
+/// Identifies a version of a source image file.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct SourceKey {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl SourceKey {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            modified: fs::metadata(path).and_then(|m| m.modified()).ok(),
+        }
+    }
+}
+
+/// Identifies a source image scaled to a buffer size.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ScaledKey {
+    source: SourceKey,
+    // Floats in `ScalingMode::Fit` aren't `Hash`, so the parameters are keyed by their debug output.
+    scaling: String,
+    size: (u32, u32),
+}
+
+impl ScaledKey {
+    pub fn new(
+        source: SourceKey,
+        scaling_mode: &ScalingMode,
+        filter_method: &FilterMethod,
+        size: (u32, u32),
+    ) -> Self {
+        Self {
+            source,
+            scaling: format!("{scaling_mode:?} {filter_method:?}"),
+            size,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    originals: HashMap<SourceKey, Weak<DynamicImage>>,
+    scaled: HashMap<ScaledKey, Weak<DynamicImage>>,
+}
+
+/// Shared handle to the image store.
+#[derive(Clone, Debug, Default)]
+pub struct ImageStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl ImageStore {
+    /// Returns the decoded original if it is still in use.
+    pub fn original(&self, key: &SourceKey) -> Option<Arc<DynamicImage>> {
+        let inner = self.inner.lock().ok()?;
+        inner.originals.get(key).and_then(Weak::upgrade)
+    }
+
+    /// Adds a decoded original, returning the shared copy if another one is already in use.
+    ///
+    /// Originals may be decoded at a reduced size, so a larger image replaces a smaller one.
+    pub fn insert_original(&self, key: SourceKey, image: DynamicImage) -> Arc<DynamicImage> {
+        let Ok(mut inner) = self.inner.lock() else {
+            return Arc::new(image);
+        };
+
+        inner.originals.retain(|_, image| image.strong_count() > 0);
+
+        if let Some(existing) = inner.originals.get(&key).and_then(Weak::upgrade)
+            && existing.width() < image.width()
+        {
+            inner.originals.remove(&key);
+        }
+
+        share(&mut inner.originals, key, image)
+    }
+
+    /// Returns the scaled image if it is still in use.
+    pub fn scaled(&self, key: &ScaledKey) -> Option<Arc<DynamicImage>> {
+        let inner = self.inner.lock().ok()?;
+        inner.scaled.get(key).and_then(Weak::upgrade)
+    }
+
+    /// Adds a scaled image, returning the shared copy if another one is already in use.
+    pub fn insert_scaled(&self, key: ScaledKey, image: DynamicImage) -> Arc<DynamicImage> {
+        let Ok(mut inner) = self.inner.lock() else {
+            return Arc::new(image);
+        };
+
+        inner.scaled.retain(|_, image| image.strong_count() > 0);
+        share(&mut inner.scaled, key, image)
+    }
+}
+
+fn share<K: std::hash::Hash + Eq>(
+    images: &mut HashMap<K, Weak<DynamicImage>>,
+    key: K,
+    image: DynamicImage,
+) -> Arc<DynamicImage> {
+    if let Some(existing) = images.get(&key).and_then(Weak::upgrade) {
+        return existing;
+    }
+
+    let image = Arc::new(image);
+    images.insert(key, Arc::downgrade(&image));
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImageStore, SourceKey};
+    use image::{DynamicImage, RgbImage};
+    use std::{path::Path, sync::Arc};
+
+    #[test]
+    fn shares_images_while_in_use() {
+        let store = ImageStore::default();
+        let key = SourceKey::new(Path::new("/nonexistent/wallpaper.png"));
+
+        let first = store.insert_original(key.clone(), DynamicImage::from(RgbImage::new(2, 2)));
+        let second = store.insert_original(key.clone(), DynamicImage::from(RgbImage::new(2, 2)));
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(store.original(&key).is_some());
+    }
+
+    #[test]
+    fn releases_unused_images() {
+        let store = ImageStore::default();
+        let key = SourceKey::new(Path::new("/nonexistent/wallpaper.png"));
+
+        drop(store.insert_original(key.clone(), DynamicImage::from(RgbImage::new(2, 2))));
+
+        assert!(store.original(&key).is_none());
+    }
+}
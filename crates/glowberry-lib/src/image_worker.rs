@@ -0,0 +1,428 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Decodes and scales wallpaper images on a pool of worker threads.
+//!
+//! Finished images are posted back to the event loop over a calloop channel, so
+//! decoding large images never blocks frame callbacks or configure handling.
+//! Results are shared with other wallpapers through the [`ImageStore`], and scaled
+//! images are kept in the disk cache, which spares decoding entirely when every
+//! requested size is cached. The workers also read the sizes of slideshow images
+//! from their headers, and the dates that sorted slideshows are ordered by.
+
+use crate::{
+    aspect,
+    image_cache::{self, CacheKey, ImageCache},
+    image_store::{ImageStore, ScaledKey, SourceKey},
+    loader, scaler, slideshow,
+};
+use glowberry_config::{FilterMethod, SamplingMethod, ScalingMode};
+use image::DynamicImage;
+use sctk::reexports::calloop;
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+/// Maximum number of worker threads decoding images.
+const MAX_WORKERS: usize = 4;
+
+/// Scaled images keyed by their buffer size.
+pub type ScaledImages = Vec<((u32, u32), Arc<DynamicImage>)>;
+
+/// A request to decode an image and scale it to one or more buffer sizes.
+#[derive(Debug)]
+pub struct ImageJob {
+    /// Identifies the job, so that results of superseded jobs can be discarded.
+    pub id: u64,
+    pub path: PathBuf,
+    /// Previously decoded image for `path`, which skips decoding when set.
+    pub source: Option<Arc<DynamicImage>>,
+    pub scaling_mode: ScalingMode,
+    pub filter_method: FilterMethod,
+    /// Physical buffer sizes to scale the image to.
+    pub sizes: Vec<(u32, u32)>,
+    /// Size to decode the image at, if the decoder supports it: the largest buffer
+    /// of the outputs showing the image, even those with a cached scaled image.
+    pub target: (u32, u32),
+    /// Set when the job is superseded, e.g. by a config change or image rotation.
+    pub cancelled: Arc<AtomicBool>,
+}
+
+/// A finished [`ImageJob`].
+#[derive(Debug)]
+pub struct ImageResult {
+    pub id: u64,
+    pub path: PathBuf,
+    /// The decoded image, unless every size was cached, and the scaled images.
+    pub image: eyre::Result<(Option<Arc<DynamicImage>>, ScaledImages)>,
+}
+
+/// A request to read the sizes of images from their headers.
+#[derive(Debug)]
+pub struct DimensionsJob {
+    pub id: u64,
+    /// Images whose sizes are read, skipping those already known.
+    pub paths: Vec<PathBuf>,
+    /// Where the sizes are recorded.
+    pub dimensions: aspect::Dimensions,
+}
+
+/// A request to read the timestamps that a sorted slideshow orders images by.
+#[derive(Debug)]
+pub struct SortKeysJob {
+    pub id: u64,
+    /// Images whose timestamps are read, skipping those already known.
+    pub paths: Vec<PathBuf>,
+    pub sampling_method: SamplingMethod,
+    /// Where the timestamps are recorded.
+    pub keys: slideshow::SortKeys,
+}
+
+/// A finished job of the worker pool.
+#[derive(Debug)]
+pub enum WorkerResult {
+    Image(ImageResult),
+    /// The sizes requested by the [`DimensionsJob`] of this id are recorded.
+    Dimensions(u64),
+    /// The timestamps requested by the [`SortKeysJob`] of this id are recorded.
+    SortKeys(u64),
+}
+
+#[derive(Debug)]
+enum Job {
+    Image(ImageJob),
+    Dimensions(DimensionsJob),
+    SortKeys(SortKeysJob),
+}
+
+/// Handle for submitting jobs to the image worker pool.
+///
+/// Worker threads exit once every handle has been dropped.
+#[derive(Clone, Debug)]
+pub struct ImageWorker {
+    job_tx: mpsc::Sender<Job>,
+    max_pixels: Arc<AtomicU64>,
+}
+
+impl ImageWorker {
+    /// Spawns the worker pool, posting finished jobs to `result_tx`.
+    ///
+    /// Images with more than `max_pixels` pixels are rejected, unless it is 0.
+    pub fn new(result_tx: calloop::channel::Sender<WorkerResult>, max_pixels: u64) -> Self {
+        let (job_tx, job_rx) = mpsc::channel::<Job>();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        let cache = ImageCache::open().map(Arc::new);
+        let store = ImageStore::default();
+        let max_pixels = Arc::new(AtomicU64::new(max_pixels));
+
+        let workers = thread::available_parallelism()
+            .map_or(1, usize::from)
+            .min(MAX_WORKERS);
+
+        for idx in 0..workers {
+            let job_rx = job_rx.clone();
+            let result_tx = result_tx.clone();
+            let cache = cache.clone();
+            let store = store.clone();
+            let max_pixels = max_pixels.clone();
+
+            let spawned = thread::Builder::new()
+                .name(format!("glowberry-image-{idx}"))
+                .spawn(move || {
+                    loop {
+                        let job = {
+                            let Ok(job_rx) = job_rx.lock() else {
+                                return;
+                            };
+                            match job_rx.recv() {
+                                Ok(job) => job,
+                                Err(_) => return,
+                            }
+                        };
+
+                        let result = match job {
+                            Job::Image(job) => process(
+                                job,
+                                &store,
+                                cache.as_deref(),
+                                max_pixels.load(Ordering::Relaxed),
+                            )
+                            .map(WorkerResult::Image),
+                            Job::Dimensions(job) => Some(read_dimensions(job)),
+                            Job::SortKeys(job) => Some(read_sort_keys(job)),
+                        };
+
+                        if let Some(result) = result
+                            && result_tx.send(result).is_err()
+                        {
+                            return;
+                        }
+                    }
+                });
+
+            if let Err(why) = spawned {
+                tracing::error!(?why, "failed to spawn image worker thread");
+            }
+        }
+
+        Self { job_tx, max_pixels }
+    }
+
+    /// Updates the pixel limit applied to images decoded from now on.
+    pub fn set_max_pixels(&self, max_pixels: u64) {
+        self.max_pixels.store(max_pixels, Ordering::Relaxed);
+    }
+
+    /// Queues a job for decoding and scaling.
+    pub fn submit(&self, job: ImageJob) {
+        if let Err(mpsc::SendError(Job::Image(job))) = self.job_tx.send(Job::Image(job)) {
+            tracing::error!(path = ?job.path, "image worker pool is not running");
+        }
+    }
+
+    /// Queues a job reading the sizes of images.
+    pub fn submit_dimensions(&self, job: DimensionsJob) {
+        if self.job_tx.send(Job::Dimensions(job)).is_err() {
+            tracing::error!("image worker pool is not running");
+        }
+    }
+
+    /// Queues a job reading the timestamps of images.
+    pub fn submit_sort_keys(&self, job: SortKeysJob) {
+        if self.job_tx.send(Job::SortKeys(job)).is_err() {
+            tracing::error!("image worker pool is not running");
+        }
+    }
+}
+
+fn read_dimensions(job: DimensionsJob) -> WorkerResult {
+    for path in job.paths {
+        if !job.dimensions.contains(&path) {
+            let size = loader::dimensions(&path).ok();
+            job.dimensions.insert(path, size);
+        }
+    }
+
+    WorkerResult::Dimensions(job.id)
+}
+
+fn read_sort_keys(job: SortKeysJob) -> WorkerResult {
+    for path in job.paths {
+        if !job.keys.contains(&path) {
+            let timestamp = slideshow::timestamp(&path, &job.sampling_method);
+            job.keys.insert(path, timestamp);
+        }
+    }
+
+    WorkerResult::SortKeys(job.id)
+}
+
+/// Runs a job, returning `None` if it was cancelled before completing.
+fn process(
+    job: ImageJob,
+    store: &ImageStore,
+    cache: Option<&ImageCache>,
+    max_pixels: u64,
+) -> Option<ImageResult> {
+    let is_cancelled = || job.cancelled.load(Ordering::Relaxed);
+
+    if is_cancelled() {
+        return None;
+    }
+
+    let source_key = SourceKey::new(&job.path);
+    let mut scaled = Vec::with_capacity(job.sizes.len());
+    let mut missing = Vec::new();
+
+    for &size in &job.sizes {
+        let key = ScaledKey::new(
+            source_key.clone(),
+            &job.scaling_mode,
+            &job.filter_method,
+            size,
+        );
+
+        // Prefer images in use by other wallpapers, then the disk cache.
+        if let Some(image) = store.scaled(&key) {
+            scaled.push((size, image));
+            continue;
+        }
+
+        let cache_key = cache.and_then(|_| {
+            CacheKey::new(
+                &job.path,
+                &job.scaling_mode,
+                &job.filter_method,
+                size.0,
+                size.1,
+            )
+        });
+
+        match cache
+            .zip(cache_key.as_ref())
+            .and_then(|(cache, key)| cache.load(key))
+        {
+            Some(image) => scaled.push((size, store.insert_scaled(key, image))),
+            None => missing.push((key, cache_key)),
+        }
+    }
+
+    if missing.is_empty() {
+        return Some(ImageResult {
+            id: job.id,
+            path: job.path,
+            image: Ok((job.source, scaled)),
+        });
+    }
+
+    // Vector images are rendered at each size instead of being decoded and scaled.
+    #[cfg(feature = "svg")]
+    if crate::svg::is_svg(&job.path) {
+        for (key, cache_key) in missing {
+            if is_cancelled() {
+                return None;
+            }
+
+            let (width, height) = key.size();
+            match crate::svg::render(&job.path, &job.scaling_mode, width, height) {
+                Ok(image) => {
+                    let image = store_scaled(store, cache, key, cache_key, image);
+                    scaled.push(((width, height), image));
+                }
+                Err(why) => {
+                    return Some(ImageResult {
+                        id: job.id,
+                        path: job.path,
+                        image: Err(why),
+                    });
+                }
+            }
+        }
+
+        return Some(ImageResult {
+            id: job.id,
+            path: job.path,
+            image: Ok((None, scaled)),
+        });
+    }
+
+    let largest = missing.iter().fold((0, 0), |(w, h), (key, _)| {
+        let (width, height) = key.size();
+        (w.max(width), h.max(height))
+    });
+
+    // A reduced original may be too small for a larger output.
+    let covers =
+        |image: &Arc<DynamicImage>| image.width() >= largest.0 && image.height() >= largest.1;
+
+    // Decode just large enough for the largest output, if the decoder supports it,
+    // so that the original also serves the outputs scaled later.
+    let target = (job.target.0.max(largest.0), job.target.1.max(largest.1));
+
+    let source = match job
+        .source
+        .filter(covers)
+        .or_else(|| store.original(&source_key).filter(covers))
+    {
+        Some(source) => source,
+        None => match loader::decode(&job.path, Some(target), max_pixels) {
+            Ok(image) => store.insert_original(source_key, image),
+            Err(why) => {
+                return Some(ImageResult {
+                    id: job.id,
+                    path: job.path,
+                    image: Err(why),
+                });
+            }
+        },
+    };
+
+    for (key, cache_key) in missing {
+        if is_cancelled() {
+            return None;
+        }
+
+        let (width, height) = key.size();
+        let filter = &job.filter_method;
+        let image = match job.scaling_mode {
+            ScalingMode::Fit(color) => scaler::fit(&source, &color, filter, width, height),
+            ScalingMode::Zoom => scaler::zoom(&source, filter, width, height),
+            ScalingMode::Stretch => scaler::stretch(&source, filter, width, height),
+        };
+
+        let image = store_scaled(store, cache, key, cache_key, image);
+        scaled.push(((width, height), image));
+    }
+
+    if is_cancelled() {
+        return None;
+    }
+
+    Some(ImageResult {
+        id: job.id,
+        path: job.path,
+        image: Ok((Some(source), scaled)),
+    })
+}
+
+/// Adds a scaled image to the disk cache and the store.
+fn store_scaled(
+    store: &ImageStore,
+    cache: Option<&ImageCache>,
+    key: ScaledKey,
+    cache_key: Option<CacheKey>,
+    mut image: DynamicImage,
+) -> Arc<DynamicImage> {
+    if let (Some(cache), Some(cache_key)) = (cache, cache_key) {
+        // Keep the pixels exactly as they are cached, so that every draw looks the same.
+        image = image_cache::stored_format(image);
+        cache.store(&cache_key, &image);
+    }
+
+    store.insert_scaled(key, image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImageJob, process};
+    use crate::image_store::ImageStore;
+    use glowberry_config::{FilterMethod, ScalingMode};
+    use image::{DynamicImage, RgbImage};
+    use std::sync::{Arc, atomic::AtomicBool};
+
+    fn job(cancelled: bool) -> ImageJob {
+        ImageJob {
+            id: 1,
+            path: "unused.png".into(),
+            source: Some(Arc::new(DynamicImage::from(RgbImage::new(8, 4)))),
+            scaling_mode: ScalingMode::Stretch,
+            filter_method: FilterMethod::default(),
+            sizes: vec![(4, 2), (2, 1)],
+            target: (4, 2),
+            cancelled: Arc::new(AtomicBool::new(cancelled)),
+        }
+    }
+
+    #[test]
+    fn scales_to_every_requested_size() {
+        let result =
+            process(job(false), &ImageStore::default(), None, 0).expect("job should complete");
+        let (_, scaled) = result.image.expect("scaling should succeed");
+
+        let sizes: Vec<_> = scaled
+            .iter()
+            .map(|(size, image)| (*size, (image.width(), image.height())))
+            .collect();
+        assert_eq!(sizes, vec![((4, 2), (4, 2)), ((2, 1), (2, 1))]);
+    }
+
+    #[test]
+    fn cancelled_jobs_produce_no_result() {
+        assert!(process(job(true), &ImageStore::default(), None, 0).is_none());
+    }
+}
@@ -1,12 +1,29 @@
+pub mod aspect;
 pub mod colored;
+pub mod config_check;
+pub mod config_watch;
+pub mod control;
+pub mod cosmic_bg;
 pub mod draw;
 pub mod engine;
 pub mod external_surface;
 pub mod fragment_canvas;
 pub mod gpu;
+pub mod image_cache;
+pub mod image_store;
+pub mod image_worker;
 pub mod img_source;
+pub mod loader;
+pub mod monitor;
+pub mod profile_archive;
+pub mod rules;
 pub mod scaler;
+pub mod schedule;
 pub mod shader_analysis;
+pub mod shader_time;
+pub mod slideshow;
+#[cfg(feature = "svg")]
+pub mod svg;
 pub mod upower;
 pub mod user_context;
 pub mod wallpaper;
@@ -0,0 +1,549 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Decoding of wallpaper image files, shared by the daemon, the greeter surface
+//! and the settings app.
+//!
+//! Images are checked against a pixel limit before decoding. Embedded ICC profiles
+//! are converted to sRGB and the EXIF orientation is applied, so that wide-gamut
+//! photos keep their colours and are shown upright. When the size they
+//! will be displayed at is known, JPEG images are decoded at a reduced scale with
+//! DCT scaling, so huge photos never need to be held in memory at full resolution.
+//! JPEG XL images are reduced right after decoding, before they are stored.
+
+use eyre::{WrapErr, eyre};
+use image::{
+    DynamicImage, GrayImage, ImageBuffer, ImageDecoder, ImageFormat, ImageReader, Pixel, RgbImage,
+    metadata::Orientation,
+};
+use jxl_oxide::integration::JxlDecoder;
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformExecutor, TransformOptions};
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+/// Lowercase file extensions of the image formats supported by this build.
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg",
+    "jpeg",
+    "png",
+    "webp",
+    "hdr",
+    "jxl",
+    #[cfg(feature = "avif")]
+    "avif",
+    #[cfg(feature = "heic")]
+    "heic",
+    #[cfg(feature = "heic")]
+    "heif",
+    #[cfg(feature = "tiff")]
+    "tif",
+    #[cfg(feature = "tiff")]
+    "tiff",
+    #[cfg(feature = "qoi")]
+    "qoi",
+    #[cfg(feature = "svg")]
+    "svg",
+    #[cfg(feature = "svg")]
+    "svgz",
+];
+
+/// Whether the file extension of `path` belongs to a supported image format.
+pub fn is_supported_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|supported| ext.eq_ignore_ascii_case(supported))
+        })
+}
+
+/// Whether `path` has a supported extension and starts with the signature of an image format.
+///
+/// This rejects files that merely look like images by name, such as partially
+/// written downloads or metadata saved with an image extension.
+pub fn is_image_file(path: &Path) -> bool {
+    if !is_supported_image(path) {
+        return false;
+    }
+
+    let mut header = [0; 32];
+    let len = match File::open(path).and_then(|mut file| read_header(&mut file, &mut header)) {
+        Ok(len) => len,
+        Err(_) => return false,
+    };
+
+    has_image_signature(&header[..len])
+}
+
+fn read_header(file: &mut File, header: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < header.len() {
+        match file.read(&mut header[len..])? {
+            0 => break,
+            read => len += read,
+        }
+    }
+
+    Ok(len)
+}
+
+/// Whether `header` starts with the signature of a supported image format.
+fn has_image_signature(header: &[u8]) -> bool {
+    const JXL_CODESTREAM: &[u8] = &[0xff, 0x0a];
+    const JXL_CONTAINER: &[u8] = b"\0\0\0\x0cJXL \r\n\x87\n";
+
+    if header.starts_with(JXL_CODESTREAM) || header.starts_with(JXL_CONTAINER) {
+        return true;
+    }
+
+    #[cfg(feature = "heic")]
+    if header.get(4..8) == Some(b"ftyp")
+        && header.get(8..12).is_some_and(|brand| {
+            [
+                b"heic", b"heix", b"heim", b"heis", b"hevc", b"mif1", b"msf1",
+            ]
+            .contains(&brand)
+        })
+    {
+        return true;
+    }
+
+    #[cfg(feature = "svg")]
+    {
+        // SVGZ files are gzip streams, plain SVGs are XML text that starts with an
+        // XML declaration or the svg element, possibly after a byte order mark.
+        let text = header.strip_prefix(b"\xef\xbb\xbf").unwrap_or(header);
+        let text = text.trim_ascii_start();
+        if header.starts_with(&[0x1f, 0x8b])
+            || text.starts_with(b"<?xml")
+            || text.starts_with(b"<svg")
+        {
+            return true;
+        }
+    }
+
+    image::guess_format(header).is_ok()
+}
+
+/// Decodes an image file, guessing its format from the content.
+///
+/// `target` is the largest buffer size the image will be scaled to. Decoders that
+/// support it return an image at least that large but possibly smaller than the
+/// original. Images with more than `max_pixels` pixels are rejected, unless it is 0.
+pub fn decode(
+    path: &Path,
+    target: Option<(u32, u32)>,
+    max_pixels: u64,
+) -> eyre::Result<DynamicImage> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("jxl") => return decode_jpegxl(path, target, max_pixels),
+        #[cfg(feature = "heic")]
+        Some(ext) if ext.eq_ignore_ascii_case("heic") || ext.eq_ignore_ascii_case("heif") => {
+            return decode_heic(path, max_pixels);
+        }
+        _ => (),
+    }
+
+    #[cfg(feature = "svg")]
+    if crate::svg::is_svg(path) {
+        return crate::svg::decode(path, target, max_pixels);
+    }
+
+    let reader = ImageReader::open(path)
+        .wrap_err("failed to open image file")?
+        .with_guessed_format()
+        .wrap_err("failed to read image format")?;
+
+    let format = reader.format();
+    let mut decoder = reader
+        .into_decoder()
+        .wrap_err("failed to read image header")?;
+
+    let (width, height) = decoder.dimensions();
+    check_pixel_limit(width, height, max_pixels)?;
+
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let icc_profile = decoder.icc_profile().ok().flatten();
+
+    let scaled = match (format, target) {
+        (Some(ImageFormat::Jpeg), Some((target_width, target_height))) => {
+            // The target is in display orientation, while the decoder scales stored pixels.
+            let target = if swaps_dimensions(orientation) {
+                (target_height, target_width)
+            } else {
+                (target_width, target_height)
+            };
+
+            decode_jpeg_scaled(path, target)
+                .inspect_err(|why| {
+                    tracing::debug!(
+                        ?why,
+                        ?path,
+                        "scaled jpeg decode failed, decoding full image"
+                    );
+                })
+                .ok()
+                .flatten()
+        }
+        _ => None,
+    };
+
+    let mut image = match scaled {
+        Some(image) => image,
+        None => DynamicImage::from_decoder(decoder).wrap_err("failed to decode image")?,
+    };
+
+    if let Some(icc_profile) = icc_profile {
+        image = convert_to_srgb(image, &icc_profile, path);
+    }
+
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Reads the size of an image as it is shown, with the EXIF orientation applied,
+/// from its header.
+pub fn dimensions(path: &Path) -> eyre::Result<(u32, u32)> {
+    let ((width, height), orientation) = if path.extension().is_some_and(|ext| ext == "jxl") {
+        let file = File::open(path).wrap_err("failed to open jxl image file")?;
+        let mut decoder =
+            JxlDecoder::new(file).map_err(|why| eyre!("failed to read jxl image header: {why}"))?;
+        (decoder.dimensions(), decoder.orientation())
+    } else {
+        let mut decoder = ImageReader::open(path)
+            .wrap_err("failed to open image file")?
+            .with_guessed_format()
+            .wrap_err("failed to read image format")?
+            .into_decoder()
+            .wrap_err("failed to read image header")?;
+        (decoder.dimensions(), decoder.orientation())
+    };
+
+    if swaps_dimensions(orientation.unwrap_or(Orientation::NoTransforms)) {
+        Ok((height, width))
+    } else {
+        Ok((width, height))
+    }
+}
+
+/// Whether the orientation transform swaps the width and height of an image.
+fn swaps_dimensions(orientation: Orientation) -> bool {
+    matches!(
+        orientation,
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    )
+}
+
+pub(crate) fn check_pixel_limit(width: u32, height: u32, max_pixels: u64) -> eyre::Result<()> {
+    let pixels = u64::from(width) * u64::from(height);
+    if max_pixels > 0 && pixels > max_pixels {
+        return Err(eyre!(
+            "image of {width}x{height} exceeds the limit of {max_pixels} pixels"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Decodes a JPEG with DCT scaling to the smallest size covering `target`.
+///
+/// Returns `Ok(None)` for pixel formats that are left to the full decoder.
+fn decode_jpeg_scaled(path: &Path, target: (u32, u32)) -> eyre::Result<Option<DynamicImage>> {
+    let file = File::open(path).wrap_err("failed to open jpeg file")?;
+    let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(file));
+    decoder.read_info().wrap_err("failed to read jpeg header")?;
+
+    let to_u16 = |v: u32| u16::try_from(v).unwrap_or(u16::MAX);
+    let (width, height) = decoder
+        .scale(to_u16(target.0), to_u16(target.1))
+        .wrap_err("failed to scale jpeg")?;
+
+    let pixel_format = decoder
+        .info()
+        .ok_or_else(|| eyre!("missing jpeg info"))?
+        .pixel_format;
+
+    let pixels = decoder.decode().wrap_err("failed to decode jpeg")?;
+    let (width, height) = (u32::from(width), u32::from(height));
+
+    // CMYK and 16-bit lossless images are left to the full decoder.
+    let image = match pixel_format {
+        jpeg_decoder::PixelFormat::L8 => {
+            GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+        }
+        jpeg_decoder::PixelFormat::RGB24 => {
+            RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+        }
+        jpeg_decoder::PixelFormat::L16 | jpeg_decoder::PixelFormat::CMYK32 => return Ok(None),
+    };
+
+    image
+        .map(Some)
+        .ok_or_else(|| eyre!("jpeg buffer does not match its dimensions"))
+}
+
+/// Converts an image from its embedded ICC profile to sRGB.
+///
+/// Outputs are treated as sRGB, since the compositor doesn't report their colour
+/// spaces. Images that can't be converted are returned unchanged.
+fn convert_to_srgb(image: DynamicImage, icc_profile: &[u8], path: &Path) -> DynamicImage {
+    let profile = match ColorProfile::new_from_slice(icc_profile) {
+        Ok(profile) => profile,
+        Err(why) => {
+            tracing::debug!(%why, ?path, "ignoring invalid icc profile");
+            return image;
+        }
+    };
+
+    // Grayscale and CMYK profiles are left to the decoder's own conversion.
+    if profile.color_space != DataColorSpace::Rgb {
+        return image;
+    }
+
+    let srgb = ColorProfile::new_srgb();
+    let options = TransformOptions::default();
+
+    let converted = match &image {
+        DynamicImage::ImageRgb8(pixels) => profile
+            .create_transform_8bit(Layout::Rgb, &srgb, Layout::Rgb, options)
+            .and_then(|transform| apply_transform(&*transform, pixels))
+            .map(DynamicImage::ImageRgb8),
+        DynamicImage::ImageRgba8(pixels) => profile
+            .create_transform_8bit(Layout::Rgba, &srgb, Layout::Rgba, options)
+            .and_then(|transform| apply_transform(&*transform, pixels))
+            .map(DynamicImage::ImageRgba8),
+        DynamicImage::ImageRgb16(pixels) => profile
+            .create_transform_16bit(Layout::Rgb, &srgb, Layout::Rgb, options)
+            .and_then(|transform| apply_transform(&*transform, pixels))
+            .map(DynamicImage::ImageRgb16),
+        DynamicImage::ImageRgba16(pixels) => profile
+            .create_transform_16bit(Layout::Rgba, &srgb, Layout::Rgba, options)
+            .and_then(|transform| apply_transform(&*transform, pixels))
+            .map(DynamicImage::ImageRgba16),
+        // Float images may hold values outside of the profile's range.
+        _ => return image,
+    };
+
+    converted.unwrap_or_else(|why| {
+        tracing::warn!(%why, ?path, "failed to convert icc profile to srgb");
+        image
+    })
+}
+
+fn apply_transform<P: Pixel>(
+    transform: &dyn TransformExecutor<P::Subpixel>,
+    pixels: &ImageBuffer<P, Vec<P::Subpixel>>,
+) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, moxcms::CmsError>
+where
+    P::Subpixel: Default,
+{
+    let mut converted = ImageBuffer::new(pixels.width(), pixels.height());
+    transform.transform(pixels.as_raw(), &mut converted)?;
+    Ok(converted)
+}
+
+/// Decodes JPEG XL image files into `image::DynamicImage` via `jxl-oxide`.
+///
+/// `jxl-oxide` has no scaled decoding, so the image is reduced to `target` as soon
+/// as it is decoded, and only the reduced image is kept.
+fn decode_jpegxl(
+    path: &Path,
+    target: Option<(u32, u32)>,
+    max_pixels: u64,
+) -> eyre::Result<DynamicImage> {
+    let file = File::open(path).map_err(|why| eyre!("failed to open jxl image file: {why}"))?;
+
+    let mut decoder =
+        JxlDecoder::new(file).map_err(|why| eyre!("failed to read jxl image header: {why}"))?;
+
+    let (width, height) = decoder.dimensions();
+    check_pixel_limit(width, height, max_pixels)?;
+
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let icc_profile = decoder.icc_profile().ok().flatten();
+
+    let mut image = image::DynamicImage::from_decoder(decoder)
+        .map_err(|why| eyre!("failed to decode jxl image: {why}"))?;
+
+    image.apply_orientation(orientation);
+    if let Some(target) = target {
+        image = reduce(image, target);
+    }
+
+    // Converting the reduced image is cheaper than converting the full one.
+    if let Some(icc_profile) = icc_profile {
+        image = convert_to_srgb(image, &icc_profile, path);
+    }
+
+    Ok(image)
+}
+
+/// Shrinks an image, keeping its aspect ratio, to the smallest size covering
+/// `target`. Images less than twice as large are returned unchanged, like with DCT
+/// scaling, since their scaling is left to the scaler.
+fn reduce(image: DynamicImage, target: (u32, u32)) -> DynamicImage {
+    let (width, height) = (image.width(), image.height());
+    let scale = f64::max(
+        f64::from(target.0) / f64::from(width),
+        f64::from(target.1) / f64::from(height),
+    );
+
+    if scale > 0.5 {
+        return image;
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let scaled = |size: u32| ((f64::from(size) * scale).ceil() as u32).clamp(1, size);
+    image.resize_exact(
+        scaled(width),
+        scaled(height),
+        image::imageops::FilterType::Triangle,
+    )
+}
+
+/// Decodes HEIC and HEIF image files via libheif.
+#[cfg(feature = "heic")]
+fn decode_heic(path: &Path, max_pixels: u64) -> eyre::Result<DynamicImage> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let file_name = path
+        .to_str()
+        .ok_or_else(|| eyre!("heic path is not valid UTF-8"))?;
+
+    let context = HeifContext::read_from_file(file_name)
+        .map_err(|why| eyre!("failed to read heic image: {why}"))?;
+
+    let handle = context
+        .primary_image_handle()
+        .map_err(|why| eyre!("failed to read heic image header: {why}"))?;
+
+    check_pixel_limit(handle.width(), handle.height(), max_pixels)?;
+
+    // libheif applies the rotation and mirroring stored in the file while decoding.
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        .map_err(|why| eyre!("failed to decode heic image: {why}"))?;
+
+    let planes = decoded.planes();
+    let plane = planes
+        .interleaved
+        .ok_or_else(|| eyre!("heic image has no interleaved plane"))?;
+
+    // Rows may be padded beyond their pixels.
+    let row_len = plane.width as usize * 3;
+    let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        let row = row
+            .get(..row_len)
+            .ok_or_else(|| eyre!("heic row is shorter than its width"))?;
+        pixels.extend_from_slice(row);
+    }
+
+    let image = RgbImage::from_raw(plane.width, plane.height, pixels)
+        .map(DynamicImage::ImageRgb8)
+        .ok_or_else(|| eyre!("heic buffer does not match its dimensions"))?;
+
+    Ok(match handle.color_profile_raw() {
+        Some(profile) => convert_to_srgb(image, &profile.data, path),
+        None => image,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_pixel_limit, convert_to_srgb, decode, has_image_signature, is_supported_image,
+        reduce, swaps_dimensions,
+    };
+    use image::{DynamicImage, ImageFormat, Rgb, RgbImage, metadata::Orientation};
+    use moxcms::ColorProfile;
+    use std::path::Path;
+
+    #[test]
+    fn pixel_limit_rejects_large_images() {
+        assert!(check_pixel_limit(100, 100, 10_000).is_ok());
+        assert!(check_pixel_limit(100, 101, 10_000).is_err());
+        assert!(check_pixel_limit(100_000, 100_000, 0).is_ok());
+    }
+
+    #[test]
+    fn recognizes_supported_extensions() {
+        assert!(is_supported_image(Path::new("/wallpapers/photo.JPG")));
+        assert!(is_supported_image(Path::new("/wallpapers/photo.jxl")));
+        assert!(!is_supported_image(Path::new("/wallpapers/notes.txt")));
+        assert!(!is_supported_image(Path::new("/wallpapers/photo")));
+    }
+
+    #[test]
+    fn recognizes_image_signatures() {
+        assert!(has_image_signature(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(has_image_signature(&[0xff, 0xd8, 0xff, 0xe0]));
+        assert!(has_image_signature(&[0xff, 0x0a, 0x00]));
+        assert!(!has_image_signature(b"Thumbnail cache metadata"));
+        assert!(!has_image_signature(&[]));
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn recognizes_svg_documents() {
+        assert!(has_image_signature(b"<?xml version=\"1.0\"?>"));
+        assert!(has_image_signature(b"\xef\xbb\xbf  <svg xmlns="));
+        assert!(!has_image_signature(b"<html><body>"));
+        assert!(!has_image_signature(b"<!-- not an image -->"));
+    }
+
+    #[test]
+    fn quarter_turns_swap_dimensions() {
+        assert!(swaps_dimensions(Orientation::Rotate90));
+        assert!(swaps_dimensions(Orientation::Rotate270FlipH));
+        assert!(!swaps_dimensions(Orientation::Rotate180));
+        assert!(!swaps_dimensions(Orientation::FlipHorizontal));
+    }
+
+    #[test]
+    fn wide_gamut_images_are_converted_to_srgb() {
+        let convert = |profile: ColorProfile| {
+            let image = DynamicImage::from(RgbImage::from_pixel(1, 1, Rgb([200, 100, 50])));
+            let icc_profile = profile.encode().unwrap();
+            convert_to_srgb(image, &icc_profile, Path::new("test.png")).to_rgb8()[(0, 0)].0
+        };
+
+        let [r, g, b] = convert(ColorProfile::new_srgb());
+        assert!(r.abs_diff(200) <= 1 && g.abs_diff(100) <= 1 && b.abs_diff(50) <= 1);
+
+        // Display P3 reds are more saturated than sRGB can show at the same level.
+        let [r, _, _] = convert(ColorProfile::new_display_p3());
+        assert!(r > 205);
+    }
+
+    #[test]
+    fn jpeg_is_decoded_at_reduced_scale() {
+        let path =
+            std::env::temp_dir().join(format!("glowberry-loader-{}.jpg", std::process::id()));
+        RgbImage::new(800, 600)
+            .save_with_format(&path, ImageFormat::Jpeg)
+            .unwrap();
+
+        let image = decode(&path, Some((200, 150)), 0).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!((image.width(), image.height()), (200, 150));
+    }
+
+    #[test]
+    fn reduced_images_cover_the_target() {
+        let image = DynamicImage::from(RgbImage::new(4000, 3000));
+
+        let reduced = reduce(image.clone(), (1920, 1080));
+        assert_eq!((reduced.width(), reduced.height()), (1920, 1440));
+
+        let reduced = reduce(image, (2560, 1440));
+        assert_eq!((reduced.width(), reduced.height()), (4000, 3000));
+    }
+}
@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Identification of the monitors connected to outputs.
+//!
+//! Docking stations renumber connectors, so per-output backgrounds can follow a
+//! monitor by its make, model and serial number instead of the connector name.
+//! Wayland only advertises the make and model; the serial number is read from
+//! the EDID that the kernel exposes for the DRM connector of the same name.
+
+use glowberry_config::Monitor;
+use sctk::output::OutputInfo;
+
+const DRM_CLASS: &str = "/sys/class/drm";
+
+const EDID_HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+
+/// Tag of the display descriptor holding the serial number as text.
+const SERIAL_DESCRIPTOR: u8 = 0xff;
+
+/// Identifies the monitor of an output, if the compositor advertises its make or model.
+pub fn identify(info: &OutputInfo) -> Option<Monitor> {
+    if info.make.is_empty() && info.model.is_empty() {
+        return None;
+    }
+
+    Some(Monitor {
+        make: info.make.clone(),
+        model: info.model.clone(),
+        serial: info.name.as_deref().and_then(connector_serial),
+    })
+}
+
+/// Serial number in the EDID of the DRM connector named `connector`, such as `DP-3`.
+fn connector_serial(connector: &str) -> Option<String> {
+    std::fs::read_dir(DRM_CLASS)
+        .ok()?
+        .filter_map(Result::ok)
+        .find(|entry| {
+            // Connectors are named after their card, such as `card1-DP-3`.
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.split_once('-'))
+                .is_some_and(|(card, name)| card.starts_with("card") && name == connector)
+        })
+        .and_then(|entry| std::fs::read(entry.path().join("edid")).ok())
+        .and_then(|edid| edid_serial(&edid))
+}
+
+/// Serial number of an EDID, preferring the text descriptor over the numeric field.
+pub fn edid_serial(edid: &[u8]) -> Option<String> {
+    if edid.len() < 128 || edid[..8] != EDID_HEADER {
+        return None;
+    }
+
+    // Four 18 byte descriptors follow the basic display parameters.
+    let text = edid[54..126]
+        .chunks_exact(18)
+        .find(|descriptor| descriptor[..3] == [0, 0, 0] && descriptor[3] == SERIAL_DESCRIPTOR)
+        .map(|descriptor| {
+            let text = &descriptor[5..];
+            let end = text.iter().position(|&b| b == b'\n').unwrap_or(text.len());
+            String::from_utf8_lossy(&text[..end]).trim().to_string()
+        })
+        .filter(|text| !text.is_empty());
+
+    text.or_else(|| {
+        let number = u32::from_le_bytes([edid[12], edid[13], edid[14], edid[15]]);
+        (number != 0).then(|| number.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EDID_HEADER, edid_serial};
+
+    fn edid() -> Vec<u8> {
+        let mut edid = vec![0; 128];
+        edid[..8].copy_from_slice(&EDID_HEADER);
+        edid
+    }
+
+    #[test]
+    fn prefers_the_serial_descriptor() {
+        let mut edid = edid();
+        edid[12..16].copy_from_slice(&1234u32.to_le_bytes());
+        edid[72 + 3] = 0xff;
+        edid[72 + 5..72 + 18].copy_from_slice(b"ABC123\n      ");
+
+        assert_eq!(edid_serial(&edid).as_deref(), Some("ABC123"));
+    }
+
+    #[test]
+    fn falls_back_to_the_numeric_serial() {
+        let mut edid = edid();
+        assert_eq!(edid_serial(&edid), None);
+
+        edid[12..16].copy_from_slice(&1234u32.to_le_bytes());
+        assert_eq!(edid_serial(&edid).as_deref(), Some("1234"));
+
+        assert_eq!(edid_serial(&edid[..100]), None);
+    }
+}
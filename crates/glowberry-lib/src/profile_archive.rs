@@ -0,0 +1,324 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Portable archives of profiles.
+//!
+//! An archive is a gzipped tarball holding the profile as `profile.ron` and the
+//! shaders and images it references under `files/`, so a setup can be moved to
+//! another machine. Paths in the archived profile are relative to the archive
+//! root; importing extracts the files to `$XDG_DATA_HOME/glowberry/profiles/`
+//! and points the profile at them.
+//!
+//! Of a slideshow directory, only the images the slideshow would show are
+//! bundled, keeping their place below the directory.
+
+use crate::slideshow;
+use eyre::{OptionExt, WrapErr};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use glowberry_config::{Entry, ShaderContent, Source, profiles::Profile};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+const PROFILE_FILE: &str = "profile.ron";
+const FILES_DIR: &str = "files";
+
+/// Writes `profile` and the files it references to an archive at `path`.
+///
+/// Referenced files that don't exist are left out and keep their original path.
+pub fn export(profile: &Profile, path: &Path) -> eyre::Result<()> {
+    let mut profile = profile.clone();
+    let mut bundled: HashMap<PathBuf, PathBuf> = HashMap::new();
+    // Sources by their name in the archive
+    let mut files = BTreeMap::new();
+
+    visit_paths(&mut profile, &mut |path, recursion_depth| {
+        if !bundled.contains_key(path) && !path.exists() {
+            tracing::warn!(?path, "not bundling missing file");
+            return;
+        }
+
+        // Numbered directories keep files of the same name apart.
+        let index = bundled.len();
+        let name = bundled
+            .entry(path.clone())
+            .or_insert_with(|| {
+                Path::new(FILES_DIR)
+                    .join(index.to_string())
+                    .join(path.file_name().unwrap_or(path.as_os_str()))
+            })
+            .clone();
+
+        if path.is_dir() {
+            // The extracted directory is no system wallpaper collection, so a
+            // recursion depth that depends on the location is kept explicitly.
+            let depth = recursion_depth.unwrap_or_else(|| slideshow::default_recursion_depth(path));
+            if depth > 0 {
+                *recursion_depth = Some(depth);
+            }
+
+            for image in slideshow::scan(path, depth) {
+                if let Ok(relative) = image.strip_prefix(&*path) {
+                    files.insert(name.join(relative), image.clone());
+                }
+            }
+        } else {
+            files.insert(name.clone(), path.clone());
+        }
+
+        *path = name;
+    });
+
+    let file = File::create(path).wrap_err_with(|| format!("failed to create {path:?}"))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let ron = ron::ser::to_string_pretty(&profile, ron::ser::PrettyConfig::default())?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(ron.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, PROFILE_FILE, ron.as_bytes())?;
+
+    for (name, source) in files {
+        builder
+            .append_path_with_name(&source, &name)
+            .wrap_err_with(|| format!("failed to bundle {source:?}"))?;
+    }
+
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Extracts the archive at `path` and returns its profile, renamed to `name` if given.
+///
+/// The bundled files are extracted to a directory named after the profile,
+/// replacing the files of an earlier import of the same name.
+pub fn import(path: &Path, name: Option<&str>) -> eyre::Result<Profile> {
+    let profiles_dir = profiles_dir().ok_or_eyre("no data directory for imported profiles")?;
+    import_into(path, name, &profiles_dir)
+}
+
+fn import_into(path: &Path, name: Option<&str>, profiles_dir: &Path) -> eyre::Result<Profile> {
+    let open = || -> eyre::Result<_> {
+        let file = File::open(path).wrap_err_with(|| format!("failed to open {path:?}"))?;
+        Ok(tar::Archive::new(GzDecoder::new(file)))
+    };
+
+    let mut profile = read_profile(&mut open()?)?;
+    if let Some(name) = name {
+        profile.name = name.to_string();
+    }
+
+    let dir = profiles_dir.join(dir_name(&profile.name));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    // Entries that would be extracted outside of `dir` are skipped.
+    open()?.unpack(&dir)?;
+    let _ = fs::remove_file(dir.join(PROFILE_FILE));
+
+    visit_paths(&mut profile, &mut |path, _| {
+        if path.is_relative() {
+            *path = dir.join(&*path);
+        }
+    });
+
+    Ok(profile)
+}
+
+fn read_profile(archive: &mut tar::Archive<impl Read>) -> eyre::Result<Profile> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()? == Path::new(PROFILE_FILE) {
+            let mut ron = String::new();
+            entry.read_to_string(&mut ron)?;
+            return ron::from_str(&ron).wrap_err("invalid profile");
+        }
+    }
+
+    Err(eyre::eyre!("archive has no {PROFILE_FILE}"))
+}
+
+/// Directory holding the files of imported profiles.
+fn profiles_dir() -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+        })?;
+
+    Some(data_home.join("glowberry").join("profiles"))
+}
+
+/// A file name for the profile named `name`.
+///
+/// Names that only differ in replaced characters, such as `a b` and `a_b`, are
+/// told apart by a hash of the exact name, so that importing one doesn't
+/// replace the files of the other.
+fn dir_name(name: &str) -> String {
+    // FNV-1a, which unlike `DefaultHasher` is stable across Rust releases.
+    let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    });
+
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{sanitized}-{hash:08x}")
+}
+
+/// Calls `f` with every file path the backgrounds of `profile` reference, and
+/// the recursion depth of the background referencing it.
+fn visit_paths(profile: &mut Profile, f: &mut impl FnMut(&mut PathBuf, &mut Option<usize>)) {
+    let entries = std::iter::once(&mut profile.default_background)
+        .chain(&mut profile.backgrounds)
+        .chain(profile.rules.iter_mut().map(|rule| &mut rule.background));
+
+    for Entry {
+        source,
+        recursion_depth,
+        ..
+    } in entries
+    {
+        visit_source(source, &mut |path| f(path, recursion_depth));
+    }
+}
+
+fn visit_source(source: &mut Source, f: &mut impl FnMut(&mut PathBuf)) {
+    match source {
+        Source::Path(path) => f(path),
+        Source::Color(_) => {}
+        Source::Shader(shader) => {
+            if let ShaderContent::Path(path) = &mut shader.shader {
+                f(path);
+            }
+            if let Some(path) = &mut shader.source_path {
+                f(path);
+            }
+            if let Some(path) = &mut shader.background_image {
+                f(path);
+            }
+        }
+        Source::Playlist(items) => {
+            for item in items {
+                visit_source(&mut item.source, f);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dir_name, export, import_into};
+    use glowberry_config::{
+        Entry, ShaderClock, ShaderContent, ShaderSource, Source, power_saving::PowerSavingConfig,
+        profiles::Profile,
+    };
+    use std::{collections::HashMap, fs, path::PathBuf};
+
+    /// The signature of a PNG image, which slideshows check for.
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("glowberry-profile-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn archives_bundle_referenced_files() {
+        let dir = test_dir("round-trip");
+        let shader = dir.join("waves.wgsl");
+        let images = dir.join("photos");
+        fs::write(&shader, "// waves").unwrap();
+        fs::create_dir_all(images.join("2024").join("summer")).unwrap();
+        fs::write(images.join("lake.png"), PNG).unwrap();
+        fs::write(images.join("2024").join("beach.png"), PNG).unwrap();
+        // Beyond the recursion depth, not an image, or hidden.
+        fs::write(images.join("2024").join("summer").join("dune.png"), PNG).unwrap();
+        fs::write(images.join("notes.txt"), "notes").unwrap();
+        fs::write(images.join("fake.png"), "fake").unwrap();
+        fs::write(images.join(".hidden.png"), PNG).unwrap();
+
+        let profile = Profile {
+            name: "focus mode".into(),
+            same_on_all: false,
+            default_background: Entry::new(
+                "all".into(),
+                Source::Shader(ShaderSource {
+                    shader: ShaderContent::Path(shader.clone()),
+                    source_path: Some(shader),
+                    params: HashMap::from([("speed".into(), 0.5)]),
+                    background_image: None,
+                    language: Default::default(),
+                    frame_rate: 30,
+                    clock: ShaderClock::default(),
+                }),
+            ),
+            backgrounds: vec![
+                Entry::new("DP-1".into(), Source::Path(images)).recursion_depth(Some(1)),
+            ],
+            rules: Vec::new(),
+            power_saving: PowerSavingConfig::default(),
+            prefer_low_power: true,
+        };
+
+        let archive = dir.join("focus.tar.gz");
+        export(&profile, &archive).unwrap();
+        let imported = import_into(&archive, None, &dir.join("imported")).unwrap();
+
+        let extracted = dir.join("imported").join(dir_name("focus mode"));
+        let Source::Shader(shader) = &imported.default_background.source else {
+            panic!("shader source expected");
+        };
+        let ShaderContent::Path(shader_path) = &shader.shader else {
+            panic!("shader path expected");
+        };
+        assert!(shader_path.starts_with(&extracted));
+        assert_eq!(shader.source_path.as_ref(), Some(shader_path));
+        assert_eq!(fs::read_to_string(shader_path).unwrap(), "// waves");
+        assert_eq!(shader.params.get("speed"), Some(&0.5));
+
+        let Source::Path(images) = &imported.backgrounds[0].source else {
+            panic!("path source expected");
+        };
+        assert_eq!(fs::read(images.join("lake.png")).unwrap(), PNG);
+        assert_eq!(
+            fs::read(images.join("2024").join("beach.png")).unwrap(),
+            PNG
+        );
+        for left_out in ["2024/summer", "notes.txt", "fake.png", ".hidden.png"] {
+            assert!(!images.join(left_out).exists(), "{left_out} was bundled");
+        }
+        assert_eq!(imported.name, "focus mode");
+
+        let renamed = import_into(&archive, Some("home"), &dir.join("imported")).unwrap();
+        assert_eq!(renamed.name, "home");
+        assert!(
+            dir.join("imported")
+                .join(dir_name("home"))
+                .join("files")
+                .exists()
+        );
+
+        // A name that only differs in replaced characters keeps the files of the first import.
+        assert_ne!(dir_name("focus mode"), dir_name("focus_mode"));
+        import_into(&archive, Some("focus_mode"), &dir.join("imported")).unwrap();
+        assert_eq!(fs::read_to_string(shader_path).unwrap(), "// waves");
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
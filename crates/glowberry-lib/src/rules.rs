@@ -0,0 +1,190 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Evaluation of the rules choosing the background of an output.
+
+use chrono::{Datelike, Local, Timelike};
+use cosmic_config::ConfigGet;
+use glowberry_config::rules::{Condition, Rule, Weekday};
+use std::time::Duration;
+
+/// Config of the COSMIC theme mode, telling whether the dark theme is active.
+pub const THEME_MODE: &str = "com.system76.CosmicTheme.Mode";
+
+const IS_DARK: &str = "is_dark";
+
+/// State of the system that rules are checked against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Environment {
+    pub on_battery: bool,
+    pub lid_closed: bool,
+    pub dark_theme: bool,
+    pub weekday: Weekday,
+    /// Local time as `(hour, minute)`.
+    pub time: (u8, u8),
+}
+
+impl Environment {
+    /// The environment at the current local time.
+    pub fn now(on_battery: bool, lid_closed: bool, dark_theme: bool) -> Self {
+        let now = Local::now();
+
+        Self {
+            on_battery,
+            lid_closed,
+            dark_theme,
+            weekday: match now.weekday() {
+                chrono::Weekday::Mon => Weekday::Mon,
+                chrono::Weekday::Tue => Weekday::Tue,
+                chrono::Weekday::Wed => Weekday::Wed,
+                chrono::Weekday::Thu => Weekday::Thu,
+                chrono::Weekday::Fri => Weekday::Fri,
+                chrono::Weekday::Sat => Weekday::Sat,
+                chrono::Weekday::Sun => Weekday::Sun,
+            },
+            time: (now.hour() as u8, now.minute() as u8),
+        }
+    }
+}
+
+/// Name and manufacturer of the output a rule is checked for.
+#[derive(Clone, Copy, Debug)]
+pub struct OutputIdentity<'a> {
+    pub name: &'a str,
+    pub make: &'a str,
+}
+
+/// Whether `condition` holds for `output`.
+pub fn holds(condition: &Condition, env: &Environment, output: OutputIdentity) -> bool {
+    match condition {
+        Condition::OnBattery => env.on_battery,
+        Condition::LidClosed => env.lid_closed,
+        Condition::DarkTheme => env.dark_theme,
+        Condition::Weekdays(days) => days.contains(&env.weekday),
+        Condition::Time { from, to } => {
+            if from < to {
+                *from <= env.time && env.time < *to
+            } else {
+                *from <= env.time || env.time < *to
+            }
+        }
+        Condition::Output(name) => output.name == name,
+        Condition::Make(make) => output.make.to_lowercase().contains(&make.to_lowercase()),
+        Condition::Not(condition) => !holds(condition, env, output),
+    }
+}
+
+/// Index of the first rule whose conditions all hold for `output`.
+pub fn select(rules: &[Rule], env: &Environment, output: OutputIdentity) -> Option<usize> {
+    rules.iter().position(|rule| {
+        rule.when
+            .iter()
+            .all(|condition| holds(condition, env, output))
+    })
+}
+
+/// How long to wait before checking time based rules again, if there are any.
+///
+/// Checks happen at the start of every minute, when time windows begin and end.
+pub fn recheck_after(rules: &[Rule]) -> Option<Duration> {
+    let depends_on_time = rules
+        .iter()
+        .flat_map(|rule| &rule.when)
+        .any(Condition::depends_on_time);
+
+    depends_on_time.then(|| Duration::from_secs(60 - u64::from(Local::now().second().min(59))))
+}
+
+/// Whether the COSMIC dark theme is active.
+pub fn dark_theme(mode: &cosmic_config::Config) -> bool {
+    mode.get::<bool>(IS_DARK).unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Environment, OutputIdentity, select};
+    use glowberry_config::{
+        Entry, Source,
+        rules::{Condition, Rule, Weekday},
+    };
+
+    const LAPTOP: OutputIdentity = OutputIdentity {
+        name: "eDP-1",
+        make: "BOE",
+    };
+
+    fn rule(when: Vec<Condition>) -> Rule {
+        Rule {
+            when,
+            background: Entry::new(
+                "all".into(),
+                Source::Color(glowberry_config::Color::Single([0.0; 3])),
+            ),
+        }
+    }
+
+    fn env() -> Environment {
+        Environment {
+            on_battery: false,
+            lid_closed: false,
+            dark_theme: false,
+            weekday: Weekday::Mon,
+            time: (12, 0),
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_applies() {
+        let rules = [
+            rule(vec![Condition::OnBattery]),
+            rule(vec![Condition::Not(Box::new(Condition::OnBattery))]),
+        ];
+
+        assert_eq!(select(&rules, &env(), LAPTOP), Some(1));
+
+        let on_battery = Environment {
+            on_battery: true,
+            ..env()
+        };
+        assert_eq!(select(&rules, &on_battery, LAPTOP), Some(0));
+    }
+
+    #[test]
+    fn time_windows_wrap_past_midnight() {
+        let rules = [rule(vec![Condition::Time {
+            from: (22, 0),
+            to: (6, 30),
+        }])];
+
+        for (time, expected) in [
+            ((23, 0), true),
+            ((3, 0), true),
+            ((6, 30), false),
+            ((12, 0), false),
+        ] {
+            let env = Environment { time, ..env() };
+            assert_eq!(select(&rules, &env, LAPTOP).is_some(), expected, "{time:?}");
+        }
+    }
+
+    #[test]
+    fn output_conditions_match_name_and_make() {
+        let rules = [rule(vec![
+            Condition::Output("eDP-1".into()),
+            Condition::Make("boe".into()),
+            Condition::Weekdays(vec![Weekday::Mon, Weekday::Tue]),
+        ])];
+
+        assert_eq!(select(&rules, &env(), LAPTOP), Some(0));
+        assert_eq!(
+            select(
+                &rules,
+                &env(),
+                OutputIdentity {
+                    name: "DP-2",
+                    make: "BOE"
+                }
+            ),
+            None
+        );
+    }
+}
@@ -2,12 +2,13 @@
 
 //! Background scaling methods such as fit, stretch, and zoom.
 
-use image::imageops::FilterType;
+use glowberry_config::FilterMethod;
 use image::{DynamicImage, Pixel};
 
 pub fn fit(
     img: &image::DynamicImage,
     color: &[f32; 3],
+    filter: &FilterMethod,
     layer_width: u32,
     layer_height: u32,
 ) -> image::DynamicImage {
@@ -24,7 +25,7 @@ pub fn fit(
         (h as f64 * ratio).round() as u32,
     );
 
-    let resized_image = resize(img, new_width, new_height);
+    let resized_image = resize(img, filter, new_width, new_height);
 
     image::imageops::replace(
         &mut filled_image,
@@ -38,13 +39,19 @@ pub fn fit(
 
 pub fn stretch(
     img: &image::DynamicImage,
+    filter: &FilterMethod,
     layer_width: u32,
     layer_height: u32,
 ) -> image::DynamicImage {
-    resize(img, layer_width, layer_height)
+    resize(img, filter, layer_width, layer_height)
 }
 
-pub fn zoom(img: &image::DynamicImage, layer_width: u32, layer_height: u32) -> image::DynamicImage {
+pub fn zoom(
+    img: &image::DynamicImage,
+    filter: &FilterMethod,
+    layer_width: u32,
+    layer_height: u32,
+) -> image::DynamicImage {
     let (w, h) = (img.width(), img.height());
 
     let ratio = (layer_width as f64 / w as f64).max(layer_height as f64 / h as f64);
@@ -54,7 +61,7 @@ pub fn zoom(img: &image::DynamicImage, layer_width: u32, layer_height: u32) -> i
         (h as f64 * ratio).round() as u32,
     );
 
-    let mut new_image = resize(img, new_width, new_height);
+    let mut new_image = resize(img, filter, new_width, new_height);
 
     image::imageops::crop(
         &mut new_image,
@@ -67,19 +74,30 @@ pub fn zoom(img: &image::DynamicImage, layer_width: u32, layer_height: u32) -> i
     .into()
 }
 
-fn resize(img: &image::DynamicImage, new_width: u32, new_height: u32) -> image::DynamicImage {
+fn resize(
+    img: &image::DynamicImage,
+    filter: &FilterMethod,
+    new_width: u32,
+    new_height: u32,
+) -> image::DynamicImage {
     let mut resizer = fast_image_resize::Resizer::new();
     let options = fast_image_resize::ResizeOptions {
-        algorithm: fast_image_resize::ResizeAlg::Convolution(
-            fast_image_resize::FilterType::Lanczos3,
-        ),
+        algorithm: match filter {
+            FilterMethod::Nearest => fast_image_resize::ResizeAlg::Nearest,
+            FilterMethod::Linear => {
+                fast_image_resize::ResizeAlg::Convolution(fast_image_resize::FilterType::Bilinear)
+            }
+            FilterMethod::Lanczos => {
+                fast_image_resize::ResizeAlg::Convolution(fast_image_resize::FilterType::Lanczos3)
+            }
+        },
         ..Default::default()
     };
     let mut new_image = image::DynamicImage::new(new_width, new_height, img.color());
     if let Err(err) = resizer.resize(img, &mut new_image, &options) {
         tracing::warn!(?err, "Failed to use `fast_image_resize`. Falling back.");
         new_image =
-            image::imageops::resize(img, new_width, new_height, FilterType::Lanczos3).into();
+            image::imageops::resize(img, new_width, new_height, filter.clone().into()).into();
     }
     new_image
 }
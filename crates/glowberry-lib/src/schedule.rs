@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Rotation times of slideshows.
+//!
+//! Rotations are computed from the wall clock and the time of the previous
+//! rotation, so a slideshow keeps its rhythm across restarts and catches up
+//! on a missed rotation after a suspend.
+
+use chrono::{DateTime, Local};
+use croner::Cron;
+use eyre::eyre;
+use glowberry_config::RotationSchedule;
+use std::time::{Duration, SystemTime};
+
+/// Longest time the rotation timer sleeps before checking the wall clock again.
+///
+/// Timers run on the monotonic clock, which stops while the system is suspended.
+pub const MAX_TIMER_WAIT: Duration = Duration::from_secs(60);
+
+/// Parses the cron expression of a schedule, if it has one.
+pub fn parse(schedule: &RotationSchedule) -> eyre::Result<Option<Cron>> {
+    let pattern = match schedule {
+        RotationSchedule::Interval => return Ok(None),
+        RotationSchedule::Hourly => "0 * * * *",
+        RotationSchedule::Daily => "0 0 * * *",
+        RotationSchedule::Cron(pattern) => pattern,
+    };
+
+    pattern
+        .parse::<Cron>()
+        .map(Some)
+        .map_err(|why| eyre!("invalid cron expression {pattern:?}: {why}"))
+}
+
+/// Time of the first rotation after the one at `last`.
+///
+/// Returns `None` if the slideshow doesn't rotate. A time in the past means
+/// that a rotation was missed and is due now.
+pub fn next_rotation(
+    schedule: &RotationSchedule,
+    rotation_frequency: u64,
+    last: SystemTime,
+) -> eyre::Result<Option<SystemTime>> {
+    let Some(cron) = parse(schedule)? else {
+        return Ok((rotation_frequency > 0).then(|| last + Duration::from_secs(rotation_frequency)));
+    };
+
+    let last = DateTime::<Local>::from(last);
+    let next = cron
+        .find_next_occurrence(&last, false)
+        .map_err(|why| eyre!("no time matches the cron expression: {why}"))?;
+
+    Ok(Some(next.into()))
+}
+
+/// How long the rotation timer sleeps before the rotation at `next`.
+pub fn timer_wait(next: SystemTime) -> Duration {
+    next.duration_since(SystemTime::now())
+        .unwrap_or_default()
+        .min(MAX_TIMER_WAIT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::next_rotation;
+    use chrono::{Local, TimeZone, Timelike};
+    use glowberry_config::RotationSchedule;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn intervals_continue_from_the_last_rotation() {
+        let last = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+
+        assert_eq!(
+            next_rotation(&RotationSchedule::Interval, 60, last).unwrap(),
+            Some(last + Duration::from_secs(60))
+        );
+        assert_eq!(
+            next_rotation(&RotationSchedule::Interval, 0, last).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn clock_schedules_align_to_boundaries() {
+        let last = Local.with_ymd_and_hms(2024, 5, 6, 13, 20, 45).unwrap();
+
+        let hourly = next_rotation(&RotationSchedule::Hourly, 0, last.into())
+            .unwrap()
+            .map(chrono::DateTime::<Local>::from)
+            .unwrap();
+        assert_eq!(
+            (hourly.hour(), hourly.minute(), hourly.second()),
+            (14, 0, 0)
+        );
+
+        let daily = next_rotation(&RotationSchedule::Daily, 0, last.into())
+            .unwrap()
+            .map(chrono::DateTime::<Local>::from)
+            .unwrap();
+        assert_eq!(daily.date_naive(), last.date_naive().succ_opt().unwrap());
+        assert_eq!((daily.hour(), daily.minute()), (0, 0));
+    }
+
+    #[test]
+    fn rejects_invalid_cron_expressions() {
+        let schedule = RotationSchedule::Cron("every tuesday".into());
+
+        assert!(next_rotation(&schedule, 0, SystemTime::now()).is_err());
+    }
+}
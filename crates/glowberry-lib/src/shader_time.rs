@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Animation time of shader wallpapers.
+//!
+//! The time starts from the value saved for the output, so evolving shaders
+//! don't restart when their canvas is recreated after a config change, an
+//! output hotplug or a restart of the daemon.
+//!
+//! Shaders receive the time as an `f32`, whose steps grow with its value. The
+//! time is kept as a [`Duration`] and starts over every [`PERIOD`], so that the
+//! value passed to shaders stays precise however long the wallpaper has run.
+
+use glowberry_config::ShaderClock;
+use rustix::time::{ClockId, clock_gettime};
+use std::time::Duration;
+
+/// Period after which the animation time starts over, about 9 hours.
+///
+/// Below it, the steps of an `f32` are at most 4 ms, a quarter of a frame at 60 Hz.
+const PERIOD: Duration = Duration::from_secs(1 << 15);
+
+/// Animation time of a shader, continuing from a previous run.
+#[derive(Debug, Clone)]
+pub struct ShaderTime {
+    clock: ShaderClock,
+    /// Animation time when the clock was started
+    offset: Duration,
+    /// Reading of the clock when it was started
+    started: Duration,
+}
+
+impl ShaderTime {
+    /// Start the animation time at `offset`.
+    pub fn new(clock: ShaderClock, offset: Duration) -> Self {
+        Self {
+            clock,
+            offset: wrap(offset),
+            started: now(clock),
+        }
+    }
+
+    /// The current animation time, below [`PERIOD`].
+    ///
+    /// This is also the time saved for the output, so the saved value doesn't grow either.
+    pub fn elapsed(&self) -> Duration {
+        wrap(self.offset + now(self.clock).saturating_sub(self.started))
+    }
+}
+
+/// `time` modulo [`PERIOD`], keeping its fraction of a second exact.
+fn wrap(time: Duration) -> Duration {
+    Duration::new(time.as_secs() % PERIOD.as_secs(), time.subsec_nanos())
+}
+
+fn now(clock: ShaderClock) -> Duration {
+    let id = match clock {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        ShaderClock::Boot => ClockId::Boottime,
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        ShaderClock::Boot => ClockId::Realtime,
+        ShaderClock::Monotonic => ClockId::Monotonic,
+    };
+
+    let time = clock_gettime(id);
+    Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continues_from_offset() {
+        for clock in [ShaderClock::Boot, ShaderClock::Monotonic] {
+            let time = ShaderTime::new(clock, Duration::from_secs(90));
+            let elapsed = time.elapsed();
+            assert!(elapsed >= Duration::from_secs(90));
+            assert!(elapsed < Duration::from_secs(91));
+            assert!(time.elapsed() >= elapsed);
+        }
+
+        // Long running shaders start over, keeping the fraction of a second.
+        let offset = PERIOD * 1000 + Duration::from_millis(90_250);
+        let elapsed = ShaderTime::new(ShaderClock::Monotonic, offset).elapsed();
+        assert!(elapsed >= Duration::from_millis(90_250));
+        assert!(elapsed < Duration::from_millis(91_250));
+    }
+
+    #[test]
+    fn stays_precise_at_large_offsets() {
+        for offset in [
+            PERIOD - Duration::from_millis(1),
+            PERIOD * 1000 + Duration::from_secs(90),
+            Duration::from_secs(1 << 40),
+        ] {
+            let time = ShaderTime::new(ShaderClock::Monotonic, offset)
+                .elapsed()
+                .as_secs_f32();
+            let next = f32::from_bits(time.to_bits() + 1);
+            assert!(next - time <= 1.0 / 240.0, "step of {} s", next - time);
+        }
+    }
+}
@@ -0,0 +1,708 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Discovery of slideshow images and upkeep of the rotation queue.
+//!
+//! Only files with a supported extension and a matching image signature are
+//! queued, and hidden files and directories are skipped. When images are added
+//! to a watched directory, they are inserted where the sampling method would have
+//! placed them, so the slideshow doesn't have to be reloaded.
+//!
+//! The queue rotates: the current image is its last entry, and the next image is
+//! taken from the front.
+
+use crate::loader;
+use glowberry_config::{Config, DateOrder, SamplingMethod};
+use rand::{Rng, rng, seq::SliceRandom};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::UNIX_EPOCH,
+};
+use walkdir::WalkDir;
+
+/// How many times more likely favorites are picked by weighted sampling.
+const FAVORITE_WEIGHT: f64 = 3.0;
+
+/// Number of previously shown images that can be gone back to.
+const HISTORY_LEN: usize = 50;
+
+/// Timestamps by path, `None` for images without one.
+type Timestamps = HashMap<PathBuf, Option<i64>>;
+
+/// Timestamps that sorted sampling methods order slideshow images by.
+///
+/// Reading modification times and EXIF dates can be slow on large or network
+/// folders, so they are read by the image worker pool, and the event loop only
+/// looks them up. Images whose timestamp is unknown are sorted by path.
+#[derive(Clone, Debug, Default)]
+pub struct SortKeys(Arc<Mutex<Timestamps>>);
+
+impl SortKeys {
+    /// Whether the timestamp of the image at `path` has been read.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.lock().contains_key(path)
+    }
+
+    /// Records the timestamp of the image at `path`, or `None` if it has none.
+    pub fn insert(&self, path: PathBuf, timestamp: Option<i64>) {
+        self.lock().insert(path, timestamp);
+    }
+
+    /// Forgets images that are no longer in the slideshow.
+    pub fn retain(&self, queue: &VecDeque<PathBuf>) {
+        let queue: HashSet<&PathBuf> = queue.iter().collect();
+        self.lock().retain(|path, _| queue.contains(path));
+    }
+
+    fn get(&self, path: &Path) -> Option<i64> {
+        self.lock().get(path).copied().flatten()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Timestamps> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Images singled out by the user for every slideshow.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImagePreferences {
+    /// Images that are never shown.
+    pub blocklist: HashSet<PathBuf>,
+    /// Images shown more often by weighted sampling.
+    pub favorites: HashSet<PathBuf>,
+}
+
+impl ImagePreferences {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            blocklist: config.blocklist.clone(),
+            favorites: config.favorites.clone(),
+        }
+    }
+}
+
+/// Levels of subdirectories searched when the entry doesn't set a recursion depth.
+///
+/// System wallpaper collections are organized in subdirectories, while user
+/// directories are commonly mixed with unrelated content.
+pub fn default_recursion_depth(dir: &Path) -> usize {
+    let in_backgrounds_dir = std::env::var("XDG_DATA_DIRS").is_ok_and(|dirs| {
+        dirs.split(':')
+            .any(|data_dir| dir.starts_with(Path::new(data_dir).join("backgrounds")))
+    });
+
+    if in_backgrounds_dir { usize::MAX } else { 0 }
+}
+
+/// Lists the images in `dir`, searching up to `recursion_depth` levels of subdirectories.
+pub fn scan(dir: &Path, recursion_depth: usize) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .follow_links(true)
+        .max_depth(recursion_depth.saturating_add(1))
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !is_hidden(entry.path()))
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && loader::is_image_file(entry.path()))
+        .map(walkdir::DirEntry::into_path)
+        .collect()
+}
+
+/// Resolves symlinks in scanned images, since the current image is saved by its
+/// canonical path. Images reached through several links are kept once.
+pub fn canonicalize(images: impl IntoIterator<Item = PathBuf>) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    images
+        .into_iter()
+        .filter_map(|image| image.canonicalize().ok())
+        .filter(|image| seen.insert(image.clone()))
+        .collect()
+}
+
+/// Whether `path` inside `dir` is within `recursion_depth` and not hidden.
+///
+/// This doesn't check that the file is an image.
+pub fn is_within(dir: &Path, path: &Path, recursion_depth: usize) -> bool {
+    let Ok(relative) = path.strip_prefix(dir) else {
+        return false;
+    };
+
+    let mut levels = 0;
+    for component in relative.components() {
+        if component.as_os_str().as_encoded_bytes().starts_with(b".") {
+            return false;
+        }
+        levels += 1;
+    }
+
+    levels > 0 && levels - 1 <= recursion_depth
+}
+
+/// Sorts a freshly scanned queue for the sampling method.
+pub fn order(
+    queue: &mut VecDeque<PathBuf>,
+    sampling_method: &SamplingMethod,
+    favorites: &HashSet<PathBuf>,
+    keys: &SortKeys,
+) {
+    let images = queue.make_contiguous();
+    match sampling_method {
+        SamplingMethod::Alphanumeric
+        | SamplingMethod::Modified(_)
+        | SamplingMethod::DateTaken(_) => {
+            images.sort_by_cached_key(|path| sort_key(path, sampling_method, keys));
+        }
+        SamplingMethod::Random | SamplingMethod::Shuffle => images.shuffle(&mut rng()),
+        SamplingMethod::Weighted => {
+            // Weighted random permutation: each image draws `u^(1 / weight)`, highest first.
+            let mut rng = rng();
+            images.sort_by_cached_key(|path| {
+                let draw = rng.random::<f64>().powf(1.0 / weight(path, favorites));
+                std::cmp::Reverse((draw * f64::from(u32::MAX)) as u64)
+            });
+        }
+    }
+}
+
+/// Arranges a shuffled queue to continue the shuffle round saved in `bag`.
+///
+/// Images of the bag come first in their saved order, followed by the images
+/// already shown this round, and `current` last. Images that aren't in the bag
+/// are treated as shown, so they appear in the next round. Returns the number
+/// of images at the front that remain to be shown in this round.
+pub fn resume_shuffle(
+    queue: &mut VecDeque<PathBuf>,
+    bag: &[PathBuf],
+    current: Option<&Path>,
+) -> usize {
+    let mut rest: Vec<PathBuf> = queue.drain(..).collect();
+    rest.shuffle(&mut rng());
+
+    let current = current
+        .and_then(|current| rest.iter().position(|path| path == current))
+        .map(|index| rest.swap_remove(index));
+
+    for path in bag {
+        if let Some(index) = rest.iter().position(|queued| queued == path) {
+            queue.push_back(rest.swap_remove(index));
+        }
+    }
+
+    let mut unshown = queue.len();
+    if unshown == 0 {
+        // The round is complete, so every image is unshown again.
+        unshown = rest.len();
+    }
+
+    queue.extend(rest);
+
+    match current {
+        Some(current) => queue.push_back(current),
+        // Without a current image, the first unshown image becomes the current one.
+        None => {
+            if let Some(first) = queue.pop_front() {
+                queue.push_back(first);
+                unshown = unshown.saturating_sub(1);
+            }
+        }
+    }
+
+    unshown
+}
+
+/// Starts a new shuffle round, returning the number of unshown images.
+///
+/// The current image stays last, so it isn't shown twice in a row.
+pub fn reshuffle(queue: &mut VecDeque<PathBuf>) -> usize {
+    let Some(current) = queue.pop_back() else {
+        return 0;
+    };
+
+    queue.make_contiguous().shuffle(&mut rng());
+    queue.push_back(current);
+    queue.len() - 1
+}
+
+/// Moves a weighted random pick to the front of the queue, to be shown next.
+///
+/// The current image at the back of the queue is never picked.
+pub fn pick_weighted(queue: &mut VecDeque<PathBuf>, favorites: &HashSet<PathBuf>) {
+    let candidates = queue.len().saturating_sub(1);
+    if candidates < 2 {
+        return;
+    }
+
+    let weights: Vec<f64> = queue
+        .iter()
+        .take(candidates)
+        .map(|path| weight(path, favorites))
+        .collect();
+
+    let mut draw = rng().random::<f64>() * weights.iter().sum::<f64>();
+    let index = weights
+        .iter()
+        .position(|weight| {
+            draw -= weight;
+            draw < 0.0
+        })
+        .unwrap_or(candidates - 1);
+
+    if let Some(path) = queue.remove(index) {
+        queue.push_front(path);
+    }
+}
+
+/// Inserts a new image into a rotating queue whose last entry is the current image.
+///
+/// In sorted orders the image is placed among its neighbours, so it shows up
+/// when the rotation reaches its position. In random orders it is placed at a
+/// random position before the current image.
+pub fn insert(
+    queue: &mut VecDeque<PathBuf>,
+    path: PathBuf,
+    sampling_method: &SamplingMethod,
+    keys: &SortKeys,
+) {
+    if queue.contains(&path) {
+        return;
+    }
+
+    let Some(current) = queue
+        .back()
+        .map(|current| sort_key(current, sampling_method, keys))
+    else {
+        queue.push_back(path);
+        return;
+    };
+
+    let index = match sampling_method {
+        // The queue is sorted, but rotated so that it begins after the current image.
+        SamplingMethod::Alphanumeric
+        | SamplingMethod::Modified(_)
+        | SamplingMethod::DateTaken(_) => {
+            let rank = |path: &Path| {
+                let key = sort_key(path, sampling_method, keys);
+                (key <= current, key)
+            };
+            let new_rank = rank(&path);
+            queue.partition_point(|queued| rank(queued) < new_rank)
+        }
+        SamplingMethod::Random | SamplingMethod::Shuffle | SamplingMethod::Weighted => {
+            rng().random_range(0..queue.len())
+        }
+    };
+
+    queue.insert(index, path);
+}
+
+/// Sorts the queue of a sorted sampling method again once more timestamps are
+/// known, keeping the current image last so that the rotation continues after it.
+pub fn reorder(queue: &mut VecDeque<PathBuf>, sampling_method: &SamplingMethod, keys: &SortKeys) {
+    let Some(current) = queue
+        .back()
+        .map(|current| sort_key(current, sampling_method, keys))
+    else {
+        return;
+    };
+
+    queue.make_contiguous().sort_by_cached_key(|path| {
+        let key = sort_key(path, sampling_method, keys);
+        (key <= current, key)
+    });
+}
+
+/// Inserts a new image at a random position among the `unshown` images at the
+/// front of a shuffled queue, so it is shown in the current round.
+pub fn insert_unshown(queue: &mut VecDeque<PathBuf>, unshown: &mut usize, path: PathBuf) {
+    if queue.contains(&path) {
+        return;
+    }
+
+    let index = rng().random_range(0..=(*unshown).min(queue.len().saturating_sub(1)));
+    queue.insert(index, path);
+    *unshown += 1;
+}
+
+/// Removes `path` and any images inside it from the queue.
+pub fn remove(queue: &mut VecDeque<PathBuf>, unshown: &mut usize, path: &Path) {
+    retain(queue, unshown, |queued| !queued.starts_with(path));
+}
+
+/// Keeps only the images for which `keep` returns true, updating the number of
+/// `unshown` images at the front of a shuffled queue.
+pub fn retain(queue: &mut VecDeque<PathBuf>, unshown: &mut usize, keep: impl Fn(&Path) -> bool) {
+    let removed = queue
+        .iter()
+        .take(*unshown)
+        .filter(|queued| !keep(queued))
+        .count();
+
+    *unshown -= removed;
+    queue.retain(|queued| keep(queued));
+}
+
+/// Removes an image that is blocked or can't be shown from the queue and the history.
+pub fn forget(
+    queue: &mut VecDeque<PathBuf>,
+    unshown: &mut usize,
+    history: &mut Vec<PathBuf>,
+    image: &Path,
+) {
+    retain(queue, unshown, |queued| queued != image);
+    history.retain(|shown| shown != image);
+}
+
+/// Adds the image replaced by a rotation to the history, which keeps the most
+/// recent [`HISTORY_LEN`] images.
+pub fn remember(history: &mut Vec<PathBuf>, image: PathBuf) {
+    history.push(image);
+    if history.len() > HISTORY_LEN {
+        history.remove(0);
+    }
+}
+
+/// Makes the most recently shown image in the queue current again, with the
+/// replaced image coming up next, and returns it.
+///
+/// Images that were removed from the queue since they were shown are dropped
+/// from the history on the way.
+pub fn go_back(
+    queue: &mut VecDeque<PathBuf>,
+    unshown: &mut usize,
+    history: &mut Vec<PathBuf>,
+    shuffled: bool,
+) -> Option<PathBuf> {
+    let previous = std::iter::from_fn(|| history.pop()).find(|shown| queue.contains(shown))?;
+
+    if let Some(current) = queue.pop_back() {
+        queue.push_front(current);
+        if shuffled {
+            *unshown += 1;
+        }
+    }
+
+    retain(queue, unshown, |queued| *queued != previous);
+    queue.push_back(previous.clone());
+    Some(previous)
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+}
+
+fn weight(path: &Path, favorites: &HashSet<PathBuf>) -> f64 {
+    if favorites.contains(path) {
+        FAVORITE_WEIGHT
+    } else {
+        1.0
+    }
+}
+
+/// Whether the sampling method sorts images by a timestamp read from their files.
+pub fn is_sorted_by_date(sampling_method: &SamplingMethod) -> bool {
+    matches!(
+        sampling_method,
+        SamplingMethod::Modified(_) | SamplingMethod::DateTaken(_)
+    )
+}
+
+/// Reads the timestamp that the sampling method sorts the image at `path` by.
+pub fn timestamp(path: &Path, sampling_method: &SamplingMethod) -> Option<i64> {
+    match sampling_method {
+        SamplingMethod::Modified(_) => modified(path),
+        SamplingMethod::DateTaken(_) => date_taken(path).or_else(|| modified(path)),
+        _ => None,
+    }
+}
+
+/// Sort key of sorted sampling methods, with the path breaking ties between dates.
+fn sort_key(path: &Path, sampling_method: &SamplingMethod, keys: &SortKeys) -> (i64, String) {
+    let order = match sampling_method {
+        SamplingMethod::Modified(order) | SamplingMethod::DateTaken(order) => order,
+        _ => &DateOrder::OldestFirst,
+    };
+
+    let timestamp = keys.get(path).unwrap_or_default();
+    let timestamp = match order {
+        DateOrder::NewestFirst => -timestamp,
+        DateOrder::OldestFirst => timestamp,
+    };
+
+    (timestamp, path.to_string_lossy().into_owned())
+}
+
+/// Modification time in seconds since the Unix epoch.
+fn modified(path: &Path) -> Option<i64> {
+    let modified = path.metadata().ok()?.modified().ok()?;
+    let seconds = match modified.duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    };
+    Some(seconds)
+}
+
+/// EXIF date the photo was taken, in seconds since the Unix epoch.
+///
+/// Without a recorded time zone offset, the local camera time is read as UTC.
+fn date_taken(path: &Path) -> Option<i64> {
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+    let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)?;
+    let exif::Value::Ascii(ref values) = field.value else {
+        return None;
+    };
+    let mut date = exif::DateTime::from_ascii(values.first()?).ok()?;
+
+    if let Some(offset) = exif
+        .get_field(exif::Tag::OffsetTimeOriginal, exif::In::PRIMARY)
+        .and_then(|field| match field.value {
+            exif::Value::Ascii(ref values) => values.first().cloned(),
+            _ => None,
+        })
+    {
+        let _ = date.parse_offset(&offset);
+    }
+
+    let days = days_from_civil(
+        i64::from(date.year),
+        i64::from(date.month),
+        i64::from(date.day),
+    );
+    let seconds = days * 86_400
+        + i64::from(date.hour) * 3_600
+        + i64::from(date.minute) * 60
+        + i64::from(date.second);
+
+    Some(seconds - i64::from(date.offset.unwrap_or(0)) * 60)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        HISTORY_LEN, SortKeys, canonicalize, days_from_civil, forget, go_back, insert, is_within,
+        pick_weighted, remember, remove, reorder, resume_shuffle, scan,
+    };
+    use glowberry_config::{DateOrder, SamplingMethod};
+    use std::{
+        collections::{HashSet, VecDeque},
+        path::{Path, PathBuf},
+    };
+
+    fn queue(paths: &[&str]) -> VecDeque<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn inserts_in_rotated_alphanumeric_order() {
+        // The current image is "c", so the rotation continues with "d".
+        let mut images = queue(&["/w/d", "/w/f", "/w/a", "/w/c"]);
+
+        let keys = SortKeys::default();
+        insert(
+            &mut images,
+            "/w/e".into(),
+            &SamplingMethod::Alphanumeric,
+            &keys,
+        );
+        insert(
+            &mut images,
+            "/w/b".into(),
+            &SamplingMethod::Alphanumeric,
+            &keys,
+        );
+        insert(
+            &mut images,
+            "/w/g".into(),
+            &SamplingMethod::Alphanumeric,
+            &keys,
+        );
+
+        assert_eq!(
+            images,
+            queue(&["/w/d", "/w/e", "/w/f", "/w/g", "/w/a", "/w/b", "/w/c"])
+        );
+    }
+
+    #[test]
+    fn random_insertion_keeps_the_current_image_last() {
+        let mut images = queue(&["/w/a", "/w/b"]);
+
+        let keys = SortKeys::default();
+        insert(&mut images, "/w/c".into(), &SamplingMethod::Random, &keys);
+        insert(&mut images, "/w/c".into(), &SamplingMethod::Random, &keys);
+
+        assert_eq!(images.len(), 3);
+        assert_eq!(images.back(), Some(&PathBuf::from("/w/b")));
+    }
+
+    #[test]
+    fn reorders_by_dates_read_later() {
+        // Sorted by path while the dates were unknown, with "b" shown.
+        let mut images = queue(&["/w/c", "/w/d", "/w/a", "/w/b"]);
+        let keys = SortKeys::default();
+        for (path, date) in [("/w/a", 40), ("/w/b", 20), ("/w/c", 10), ("/w/d", 30)] {
+            keys.insert(path.into(), Some(date));
+        }
+
+        reorder(
+            &mut images,
+            &SamplingMethod::Modified(DateOrder::OldestFirst),
+            &keys,
+        );
+
+        assert_eq!(images, queue(&["/w/d", "/w/a", "/w/c", "/w/b"]));
+    }
+
+    #[test]
+    fn removes_images_inside_removed_directories() {
+        let mut images = queue(&["/w/a", "/w/sub/b", "/w/subway"]);
+        let mut unshown = 2;
+
+        remove(&mut images, &mut unshown, Path::new("/w/sub"));
+
+        assert_eq!(images, queue(&["/w/a", "/w/subway"]));
+        assert_eq!(unshown, 1);
+    }
+
+    #[test]
+    fn resolves_images_in_linked_directories() {
+        let dir = std::env::temp_dir().join(format!("glowberry-slideshow-{}", std::process::id()));
+        let photos = dir.join("photos");
+        std::fs::create_dir_all(&photos).unwrap();
+        std::fs::write(photos.join("a.png"), b"\x89PNG\r\n\x1a\n").unwrap();
+        std::os::unix::fs::symlink(&photos, dir.join("linked")).unwrap();
+
+        let images = canonicalize(scan(&dir, 1));
+        let expected = photos.join("a.png").canonicalize().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(images, vec![expected]);
+    }
+
+    #[test]
+    fn history_keeps_the_most_recent_images() {
+        let mut history = Vec::new();
+        for idx in 0..HISTORY_LEN + 10 {
+            remember(&mut history, PathBuf::from(format!("/w/{idx}")));
+        }
+
+        assert_eq!(history.len(), HISTORY_LEN);
+        assert_eq!(history.first(), Some(&PathBuf::from("/w/10")));
+        assert_eq!(
+            history.last(),
+            Some(&PathBuf::from(format!("/w/{}", HISTORY_LEN + 9)))
+        );
+    }
+
+    #[test]
+    fn goes_back_to_the_image_before_a_rotation() {
+        // "a" is shown, then the slideshow rotates to "b".
+        let mut images = queue(&["/w/c", "/w/a", "/w/b"]);
+        let mut history = vec![PathBuf::from("/w/a")];
+        let mut unshown = 0;
+
+        let previous = go_back(&mut images, &mut unshown, &mut history, false);
+
+        assert_eq!(previous, Some(PathBuf::from("/w/a")));
+        assert_eq!(images, queue(&["/w/b", "/w/c", "/w/a"]));
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn going_back_skips_blocked_images() {
+        let mut images = queue(&["/w/a", "/w/b", "/w/c"]);
+        let mut history = vec![PathBuf::from("/w/a"), PathBuf::from("/w/b")];
+        let mut unshown = 0;
+
+        forget(&mut images, &mut unshown, &mut history, Path::new("/w/b"));
+        assert_eq!(images, queue(&["/w/a", "/w/c"]));
+
+        let previous = go_back(&mut images, &mut unshown, &mut history, false);
+        assert_eq!(previous, Some(PathBuf::from("/w/a")));
+        assert_eq!(images, queue(&["/w/c", "/w/a"]));
+
+        // Only blocked images are left to go back to.
+        let mut history = vec![PathBuf::from("/w/b")];
+        assert_eq!(
+            go_back(&mut images, &mut unshown, &mut history, false),
+            None
+        );
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn limits_depth_and_skips_hidden_paths() {
+        let dir = Path::new("/w");
+
+        assert!(is_within(dir, Path::new("/w/a.png"), 0));
+        assert!(!is_within(dir, Path::new("/w/sub/a.png"), 0));
+        assert!(is_within(dir, Path::new("/w/sub/a.png"), 1));
+        assert!(!is_within(dir, Path::new("/w/.thumbnails/a.png"), 1));
+        assert!(!is_within(dir, Path::new("/w/.a.png"), 0));
+        assert!(!is_within(dir, Path::new("/elsewhere/a.png"), 0));
+    }
+
+    #[test]
+    fn resumes_the_saved_shuffle_round() {
+        let mut images = queue(&["/w/a", "/w/b", "/w/c", "/w/d", "/w/e"]);
+        let bag = [
+            PathBuf::from("/w/d"),
+            PathBuf::from("/w/gone"),
+            PathBuf::from("/w/b"),
+        ];
+
+        let unshown = resume_shuffle(&mut images, &bag, Some(Path::new("/w/a")));
+
+        assert_eq!(unshown, 2);
+        assert_eq!(images.len(), 5);
+        assert_eq!(images[0], PathBuf::from("/w/d"));
+        assert_eq!(images[1], PathBuf::from("/w/b"));
+        assert_eq!(images.back(), Some(&PathBuf::from("/w/a")));
+    }
+
+    #[test]
+    fn starts_a_new_round_when_the_bag_is_empty() {
+        let mut images = queue(&["/w/a", "/w/b", "/w/c"]);
+
+        let unshown = resume_shuffle(&mut images, &[], None);
+
+        assert_eq!(unshown, 2);
+        assert_eq!(images.len(), 3);
+    }
+
+    #[test]
+    fn weighted_pick_never_repeats_the_current_image() {
+        let favorites = HashSet::from([PathBuf::from("/w/c")]);
+
+        for _ in 0..32 {
+            let mut images = queue(&["/w/a", "/w/b", "/w/c"]);
+            pick_weighted(&mut images, &favorites);
+
+            assert_eq!(images.back(), Some(&PathBuf::from("/w/c")));
+            assert_eq!(images.len(), 3);
+        }
+    }
+
+    #[test]
+    fn converts_dates_to_unix_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+    }
+}
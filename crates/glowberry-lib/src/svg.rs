@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Rasterization of SVG wallpapers with resvg.
+//!
+//! Vector images are rendered directly at each buffer size rather than decoded
+//! once and scaled, so they stay sharp on every output.
+
+use crate::loader;
+use eyre::{WrapErr, eyre};
+use glowberry_config::ScalingMode;
+use image::{DynamicImage, RgbImage};
+use resvg::{tiny_skia, usvg};
+use std::{
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+/// Whether `path` names an SVG file.
+pub fn is_svg(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg") || ext.eq_ignore_ascii_case("svgz"))
+}
+
+/// Renders an SVG into a `width` x `height` buffer, placed according to `scaling_mode`.
+pub fn render(
+    path: &Path,
+    scaling_mode: &ScalingMode,
+    width: u32,
+    height: u32,
+) -> eyre::Result<DynamicImage> {
+    let tree = parse(path)?;
+    let size = tree.size();
+
+    let scale_x = width as f32 / size.width();
+    let scale_y = height as f32 / size.height();
+
+    let (scale_x, scale_y, background) = match scaling_mode {
+        ScalingMode::Fit(color) => {
+            let scale = scale_x.min(scale_y);
+            (scale, scale, *color)
+        }
+        ScalingMode::Zoom => {
+            let scale = scale_x.max(scale_y);
+            (scale, scale, [0.0; 3])
+        }
+        ScalingMode::Stretch => (scale_x, scale_y, [0.0; 3]),
+    };
+
+    // Center the image, cropping or padding the excess evenly.
+    let transform = tiny_skia::Transform::from_row(
+        scale_x,
+        0.0,
+        0.0,
+        scale_y,
+        (width as f32 - size.width() * scale_x) / 2.0,
+        (height as f32 - size.height() * scale_y) / 2.0,
+    );
+
+    rasterize(&tree, width, height, transform, background)
+}
+
+/// Renders an SVG at its intrinsic size, or large enough to cover `target` if given.
+pub fn decode(
+    path: &Path,
+    target: Option<(u32, u32)>,
+    max_pixels: u64,
+) -> eyre::Result<DynamicImage> {
+    let tree = parse(path)?;
+    let size = tree.size();
+
+    let scale = target.map_or(1.0, |(width, height)| {
+        (width as f32 / size.width()).max(height as f32 / size.height())
+    });
+
+    let width = (size.width() * scale).ceil().max(1.0) as u32;
+    let height = (size.height() * scale).ceil().max(1.0) as u32;
+    loader::check_pixel_limit(width, height, max_pixels)?;
+
+    let transform = tiny_skia::Transform::from_scale(scale, scale);
+    rasterize(&tree, width, height, transform, [0.0; 3])
+}
+
+fn parse(path: &Path) -> eyre::Result<usvg::Tree> {
+    // Loading system fonts is slow, so the database is shared by every render.
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+
+    let data = std::fs::read(path).wrap_err("failed to read svg file")?;
+
+    let options = usvg::Options {
+        resources_dir: path.parent().map(Path::to_path_buf),
+        fontdb: FONTS
+            .get_or_init(|| {
+                let mut fonts = usvg::fontdb::Database::new();
+                fonts.load_system_fonts();
+                Arc::new(fonts)
+            })
+            .clone(),
+        ..Default::default()
+    };
+
+    usvg::Tree::from_data(&data, &options).map_err(|why| eyre!("failed to parse svg: {why}"))
+}
+
+/// Renders the tree onto an opaque background.
+fn rasterize(
+    tree: &usvg::Tree,
+    width: u32,
+    height: u32,
+    transform: tiny_skia::Transform,
+    background: [f32; 3],
+) -> eyre::Result<DynamicImage> {
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| eyre!("invalid svg buffer size {width}x{height}"))?;
+
+    let [r, g, b] = background.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    pixmap.fill(tiny_skia::Color::from_rgba8(r, g, b, 255));
+
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+
+    // The background is opaque, so the premultiplied pixels equal the straight ones.
+    let pixels = pixmap
+        .data()
+        .chunks_exact(4)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+
+    RgbImage::from_raw(width, height, pixels)
+        .map(DynamicImage::ImageRgb8)
+        .ok_or_else(|| eyre!("svg buffer does not match its dimensions"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use glowberry_config::ScalingMode;
+    use std::fs;
+
+    #[test]
+    fn fit_pads_with_the_background_color() {
+        let path = std::env::temp_dir().join(format!("glowberry-svg-{}.svg", std::process::id()));
+        fs::write(
+            &path,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+                <rect width="10" height="10" fill="white"/>
+            </svg>"#,
+        )
+        .unwrap();
+
+        let image = render(&path, &ScalingMode::Fit([1.0, 0.0, 0.0]), 40, 20)
+            .unwrap()
+            .to_rgb8();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!((image.width(), image.height()), (40, 20));
+        assert_eq!(image[(0, 10)].0, [255, 0, 0]);
+        assert_eq!(image[(20, 10)].0, [255, 255, 255]);
+    }
+}
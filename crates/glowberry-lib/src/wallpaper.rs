@@ -1,30 +1,42 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::{colored, draw, engine::GlowBerry, engine::GlowBerryLayer, scaler};
-use cosmic_config::CosmicConfigEntry;
-use eyre::eyre;
-use glowberry_config::{
-    Color, Entry, SamplingMethod, ScalingMode, ShaderSource, Source, state::State,
+use crate::{
+    aspect, colored, draw,
+    engine::GlowBerry,
+    engine::GlowBerryLayer,
+    image_worker::{DimensionsJob, ImageJob, ImageResult, ImageWorker, ScaledImages, SortKeysJob},
+    loader, schedule,
+    slideshow::{self, ImagePreferences},
 };
-use image::{DynamicImage, ImageReader};
-use jxl_oxide::integration::JxlDecoder;
+use cosmic_config::CosmicConfigEntry;
+use glowberry_config::{Color, Entry, SamplingMethod, ShaderSource, Source, state::State};
+use image::DynamicImage;
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
-use rand::{rng, seq::SliceRandom};
 use sctk::reexports::{
     calloop::{
         self, RegistrationToken,
         timer::{TimeoutAction, Timer},
     },
     client::QueueHandle,
+    protocols::wp::single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1,
 };
 use std::{
-    collections::VecDeque,
-    fs::{self, File},
-    path::PathBuf,
-    time::{Duration, Instant},
+    borrow::Cow,
+    collections::{HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
 };
 use tracing::error;
-use walkdir::WalkDir;
+
+/// How long before a slideshow rotation the next image is prefetched.
+const PREFETCH_LEAD: Duration = Duration::from_secs(10);
+
+/// Number of previously shown slideshow images remembered per output.
 
 // TODO filter images by whether they seem to match dark / light mode
 // Alternatively only load from light / dark subdirectories given a directory source when this is active
@@ -36,10 +48,80 @@ pub struct Wallpaper {
     pub image_queue: VecDeque<PathBuf>,
     loop_handle: calloop::LoopHandle<'static, GlowBerry>,
     queue_handle: QueueHandle<GlowBerry>,
+    single_pixel_buffer_manager:
+        Option<wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1>,
     current_source: Option<Source>,
     // Cache of source image, if `current_source` is a `Source::Path`
-    current_image: Option<image::DynamicImage>,
+    current_image: Option<Arc<DynamicImage>>,
+    // Scaled images received from the image worker, waiting to be drawn
+    scaled_images: ScaledImages,
+    image_worker: ImageWorker,
+    pending_job: Option<PendingJob>,
+    // Next slideshow image, decoded and scaled ahead of the rotation
+    prefetch_job: Option<PendingJob>,
+    prefetched: Option<Prefetched>,
+    // Images shown instead of the current one on layers it doesn't suit
+    alternates: Vec<Alternate>,
+    image_dimensions: aspect::Dimensions,
+    // Job reading the sizes of upcoming images, and the images each layer showed
+    // instead of the current one when it was last drawn
+    dimensions_job: Option<u64>,
+    shown_alternates: Vec<Option<PathBuf>>,
+    // Dates that sorted slideshows order images by, and the job reading them
+    sort_keys: slideshow::SortKeys,
+    sort_keys_job: Option<u64>,
     timer_token: Option<RegistrationToken>,
+    prefetch_token: Option<RegistrationToken>,
+    // Watches the source directory for added and removed images
+    watcher: Option<RecommendedWatcher>,
+    // Previously shown slideshow images, oldest first
+    history: Vec<PathBuf>,
+    // Blocked and favorite images
+    preferences: ImagePreferences,
+    // Images that failed to decode, which are left out until they are written again
+    failed: HashSet<PathBuf>,
+    // Whether gradients are rendered by the GPU
+    gpu_gradients: bool,
+    // Images at the front of the queue not yet shown in this shuffle round
+    unshown: usize,
+    // When the slideshow last rotated, and when it rotates next
+    last_rotation: SystemTime,
+    next_rotation: Option<SystemTime>,
+    // Item of a playlist source that is shown, and the timer moving on to the next item
+    playlist_index: usize,
+    playlist_token: Option<RegistrationToken>,
+    source_tx: calloop::channel::SyncSender<(String, notify::Event)>,
+}
+
+/// A slideshow image prepared ahead of its rotation.
+#[derive(Debug)]
+struct Prefetched {
+    path: PathBuf,
+    source: Option<Arc<DynamicImage>>,
+    scaled: ScaledImages,
+}
+
+/// A slideshow image shown on layers whose aspect ratio the current image doesn't suit.
+#[derive(Debug)]
+struct Alternate {
+    path: PathBuf,
+    scaled: ScaledImages,
+    job: Option<PendingJob>,
+}
+
+/// An image job submitted to the worker pool that has not finished yet.
+#[derive(Debug)]
+struct PendingJob {
+    id: u64,
+    path: PathBuf,
+    sizes: Vec<(u32, u32)>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PendingJob {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
 }
 
 impl Drop for Wallpaper {
@@ -47,6 +129,26 @@ impl Drop for Wallpaper {
         if let Some(token) = self.timer_token.take() {
             self.loop_handle.remove(token);
         }
+
+        if let Some(token) = self.prefetch_token.take() {
+            self.loop_handle.remove(token);
+        }
+
+        if let Some(token) = self.playlist_token.take() {
+            self.loop_handle.remove(token);
+        }
+
+        for pending in [self.pending_job.take(), self.prefetch_job.take()]
+            .into_iter()
+            .flatten()
+            .chain(
+                self.alternates
+                    .drain(..)
+                    .filter_map(|alternate| alternate.job),
+            )
+        {
+            pending.cancel();
+        }
     }
 }
 
@@ -54,32 +156,162 @@ impl Wallpaper {
     pub fn new(
         entry: Entry,
         queue_handle: QueueHandle<GlowBerry>,
+        single_pixel_buffer_manager: Option<
+            wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1,
+        >,
+        image_worker: ImageWorker,
+        preferences: ImagePreferences,
         loop_handle: calloop::LoopHandle<'static, GlowBerry>,
         source_tx: calloop::channel::SyncSender<(String, notify::Event)>,
     ) -> Self {
+        let state = State::state()
+            .ok()
+            .map(|state| State::get_entry(&state).unwrap_or_default())
+            .unwrap_or_default();
+        let history = state.history(&entry.output).to_vec();
+        let last_rotation = state.last_rotation(&entry.output);
+        let playlist_index = playlist_position(&entry.source, current_image(&entry.output));
+
         let mut wallpaper = Wallpaper {
             entry,
             layers: Vec::new(),
             current_source: None,
             current_image: None,
+            scaled_images: Vec::new(),
+            image_worker,
+            pending_job: None,
+            prefetch_job: None,
+            prefetched: None,
+            alternates: Vec::new(),
+            image_dimensions: aspect::Dimensions::default(),
+            dimensions_job: None,
+            shown_alternates: Vec::new(),
+            sort_keys: slideshow::SortKeys::default(),
+            sort_keys_job: None,
+            prefetch_token: None,
             image_queue: VecDeque::default(),
             timer_token: None,
+            watcher: None,
+            history,
+            preferences,
+            failed: HashSet::new(),
+            gpu_gradients: false,
+            unshown: 0,
+            last_rotation: last_rotation.unwrap_or_else(SystemTime::now),
+            next_rotation: None,
+            playlist_index,
+            playlist_token: None,
+            source_tx,
             loop_handle,
             queue_handle,
+            single_pixel_buffer_manager,
         };
 
         wallpaper.load_images();
+        if last_rotation.is_none() && wallpaper.image_queue.len() > 1 {
+            wallpaper.save_last_rotation();
+        }
         wallpaper.register_timer();
-        wallpaper.watch_source(source_tx);
+        wallpaper.register_playlist_timer();
+        wallpaper.watch_source();
         wallpaper
     }
 
+    /// The source that is shown: the current item of a playlist, or else the configured source.
+    pub fn active_source(&self) -> &Source {
+        playlist_item(&self.entry.source, self.playlist_index)
+    }
+
+    /// Moves on to the next item of a playlist source.
+    ///
+    /// The caller is responsible for switching the layers between shader and
+    /// buffer rendering if the kind of source changed, and for drawing the item.
+    pub fn next_playlist_item(&mut self) {
+        let Source::Playlist(items) = &self.entry.source else {
+            return;
+        };
+
+        if items.is_empty() {
+            return;
+        }
+
+        self.playlist_index = (self.playlist_index + 1) % items.len();
+        tracing::debug!(
+            output = self.entry.output,
+            index = self.playlist_index,
+            "showing next playlist item"
+        );
+
+        for token in [self.timer_token.take(), self.prefetch_token.take()]
+            .into_iter()
+            .flatten()
+        {
+            self.loop_handle.remove(token);
+        }
+
+        self.prefetched = None;
+        if let Some(pending) = self.prefetch_job.take() {
+            pending.cancel();
+        }
+
+        self.current_source = None;
+        self.clear_image();
+        self.load_images();
+        self.register_timer();
+        self.watch_source();
+    }
+
+    /// How long the current playlist item is shown, or `None` if the source isn't a playlist.
+    pub fn playlist_item_duration(&self) -> Option<Duration> {
+        let Source::Playlist(items) = &self.entry.source else {
+            return None;
+        };
+
+        if items.len() < 2 {
+            return None;
+        }
+
+        let secs = items
+            .get(self.playlist_index)
+            .and_then(|item| item.duration)
+            .unwrap_or(self.entry.rotation_frequency);
+
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    fn register_playlist_timer(&mut self) {
+        let Some(duration) = self.playlist_item_duration() else {
+            return;
+        };
+
+        let output = self.entry.output.clone();
+        self.playlist_token = self
+            .loop_handle
+            .insert_source(
+                Timer::from_duration(duration),
+                move |_, _, state: &mut GlowBerry| match state.next_playlist_item(&output) {
+                    Some(duration) => TimeoutAction::ToDuration(duration),
+                    None => TimeoutAction::Drop,
+                },
+            )
+            .ok();
+    }
+
     pub fn save_state(&self) -> Result<(), cosmic_config::Error> {
-        let Some(cur_source) = self.current_source.clone() else {
+        if self.current_source.is_none() {
             return Ok(());
-        };
+        }
         let state_helper = State::state()?;
         let mut state = State::get_entry(&state_helper).unwrap_or_default();
+        self.record_current(&mut state);
+        state.write_entry(&state_helper)
+    }
+
+    /// Records the current source as the wallpaper of each layer's output.
+    fn record_current(&self, state: &mut State) {
+        let Some(cur_source) = self.current_source.clone() else {
+            return;
+        };
         for l in &self.layers {
             let name = l.output_info.name.clone().unwrap_or_default();
             if let Some((_, source)) = state
@@ -92,96 +324,121 @@ impl Wallpaper {
                 state.wallpapers.push((name, cur_source.clone()))
             }
         }
-        state.write_entry(&state_helper)
     }
 
     #[allow(clippy::too_many_lines)]
     pub fn draw(&mut self) {
         let start = Instant::now();
-        let mut cur_resized_img: Option<DynamicImage> = None;
+        let mut cur_resized_img: Option<Cow<'_, DynamicImage>> = None;
+        let mut cur_alternate: Option<&PathBuf> = None;
+        // Buffer sizes that still need a scaled image from the worker pool
+        let mut missing_sizes: Vec<(u32, u32)> = Vec::new();
+        let mut missing_alternates: Vec<(PathBuf, Vec<(u32, u32)>)> = Vec::new();
 
-        for layer in self.layers.iter_mut().filter(|layer| layer.needs_redraw) {
-            let Some(pool) = layer.pool.as_mut() else {
+        let layer_images = self.alternate_images();
+        self.shown_alternates.resize(self.layers.len(), None);
+        for ((layer, alternate), shown) in self
+            .layers
+            .iter_mut()
+            .zip(&layer_images)
+            .zip(&mut self.shown_alternates)
+            .filter(|((layer, _), _)| layer.needs_redraw)
+        {
+            let Some((layer_width, layer_height)) = layer.size else {
                 continue;
             };
 
-            let Some(fractional_scale) = layer.fractional_scale else {
+            // Solid colors don't need a full-size buffer: attach a single pixel and
+            // let the viewport scale it to the output.
+            if let (Some(Source::Color(Color::Single(color))), Some(manager)) = (
+                self.current_source.as_ref(),
+                self.single_pixel_buffer_manager.as_ref(),
+            ) {
+                draw::single_pixel(
+                    manager,
+                    &layer.layer,
+                    &layer.viewport,
+                    &self.queue_handle,
+                    *color,
+                    (layer_width, layer_height),
+                );
+                layer.needs_redraw = false;
+                tracing::debug!(source = ?self.current_source, "single-pixel wallpaper draw");
+                continue;
+            }
+
+            let Some(pool) = layer.pool.as_mut() else {
                 continue;
             };
 
-            let Some((layer_width, layer_height)) = layer.size else {
+            let Some(fractional_scale) = layer.fractional_scale else {
                 continue;
             };
 
-            let width = layer_width * fractional_scale / 120;
-            let height = layer_height * fractional_scale / 120;
+            let (width, height) = buffer_size(
+                playlist_item(&self.entry.source, self.playlist_index),
+                layer_width * fractional_scale / 120,
+                layer_height * fractional_scale / 120,
+            );
 
             if cur_resized_img
                 .as_ref()
                 .map_or(true, |img| img.width() != width || img.height() != height)
+                || cur_alternate != alternate.as_ref()
             {
+                cur_alternate = alternate.as_ref();
+
                 let Some(source) = self.current_source.as_ref() else {
                     tracing::info!("No source for wallpaper");
                     continue;
                 };
 
                 cur_resized_img = match source {
-                    Source::Path(path) => {
-                        if self.current_image.is_none() {
-                            self.current_image = Some(match path.extension() {
-                                Some(ext) if ext == "jxl" => match decode_jpegxl(&path) {
-                                    Ok(image) => image,
-                                    Err(why) => {
-                                        tracing::warn!(
-                                            ?why,
-                                            "jpegl-xl image decode failed: {}",
-                                            path.display()
-                                        );
-                                        continue;
-                                    }
-                                },
-
-                                _ => match ImageReader::open(&path) {
-                                    Ok(img) => {
-                                        match img
-                                            .with_guessed_format()
-                                            .ok()
-                                            .and_then(|f| f.decode().ok())
-                                        {
-                                            Some(img) => img,
-                                            None => {
-                                                tracing::warn!(
-                                                    "could not decode image: {}",
-                                                    path.display()
-                                                );
-                                                continue;
-                                            }
+                    Source::Path(_) => {
+                        let scaled = match alternate {
+                            Some(path) => self
+                                .alternates
+                                .iter()
+                                .find(|alternate| alternate.path == *path)
+                                .map(|alternate| &alternate.scaled),
+                            None => Some(&self.scaled_images),
+                        };
+
+                        let Some((_, image)) = scaled
+                            .into_iter()
+                            .flatten()
+                            .find(|(size, _)| *size == (width, height))
+                        else {
+                            // Decoding and scaling happen on the worker pool; the layer
+                            // is drawn once the image arrives.
+                            let sizes = match alternate {
+                                Some(path) => {
+                                    match missing_alternates.iter().position(|(p, _)| p == path) {
+                                        Some(index) => &mut missing_alternates[index].1,
+                                        None => {
+                                            missing_alternates.push((path.clone(), Vec::new()));
+                                            &mut missing_alternates.last_mut().unwrap().1
                                         }
                                     }
-                                    Err(_) => continue,
-                                },
-                            });
-                        }
-                        let img = self.current_image.as_ref().unwrap();
-
-                        match self.entry.scaling_mode {
-                            ScalingMode::Fit(color) => {
-                                Some(scaler::fit(img, &color, width, height))
+                                }
+                                None => &mut missing_sizes,
+                            };
+                            if !sizes.contains(&(width, height)) {
+                                sizes.push((width, height));
                             }
+                            continue;
+                        };
 
-                            ScalingMode::Zoom => Some(scaler::zoom(img, width, height)),
-
-                            ScalingMode::Stretch => Some(scaler::stretch(img, width, height)),
-                        }
+                        Some(Cow::Borrowed(&**image))
                     }
 
-                    Source::Color(Color::Single([r, g, b])) => Some(image::DynamicImage::from(
-                        colored::single([*r, *g, *b], width, height),
+                    Source::Color(Color::Single([r, g, b])) => Some(Cow::Owned(
+                        DynamicImage::from(colored::single([*r, *g, *b], width, height)),
                     )),
 
                     Source::Color(Color::Gradient(gradient)) => {
                         match colored::gradient(gradient, width, height) {
-                            Ok(buffer) => Some(image::DynamicImage::from(buffer)),
+                            Ok(buffer) => Some(Cow::Owned(DynamicImage::from(buffer))),
                             Err(why) => {
                                 tracing::error!(
                                     ?gradient,
@@ -198,11 +455,14 @@ impl Wallpaper {
                         tracing::warn!("Shader source in CPU draw path - this should not happen");
                         None
                     }
+
+                    // The current source is a playlist item, never a playlist.
+                    Source::Playlist(_) => None,
                 };
             }
 
             let Some(image) = cur_resized_img.as_ref() else {
-                tracing::debug!(source = ?self.entry.source, "Skipping CPU draw without image");
+                tracing::debug!(source = ?self.current_source, "Skipping CPU draw without image");
                 continue;
             };
             let buffer_result =
@@ -219,10 +479,11 @@ impl Wallpaper {
                         (layer_width, layer_height),
                     );
                     layer.needs_redraw = false;
+                    shown.clone_from(alternate);
 
                     let elapsed = Instant::now().duration_since(start);
 
-                    tracing::debug!(?elapsed, source = ?self.entry.source, "wallpaper draw");
+                    tracing::debug!(?elapsed, source = ?self.current_source, "wallpaper draw");
                 }
 
                 Err(why) => {
@@ -230,124 +491,700 @@ impl Wallpaper {
                 }
             }
         }
+
+        // Keep scaled images only for sizes still used by a layer, so that they are
+        // released from the image store once no output shows them.
+        let layer_sizes: Vec<_> = self
+            .layers
+            .iter()
+            .zip(&layer_images)
+            .filter(|(_, alternate)| alternate.is_none())
+            .filter_map(|(layer, _)| layer_buffer_size(self.active_source(), layer))
+            .collect();
+        self.scaled_images
+            .retain(|(size, _)| layer_sizes.contains(size));
+
+        self.alternates.retain(|alternate| {
+            let shown = layer_images
+                .iter()
+                .flatten()
+                .any(|path| *path == alternate.path);
+            if !shown && let Some(pending) = &alternate.job {
+                pending.cancel();
+            }
+            shown
+        });
+
+        if let (false, Some(Source::Path(path))) =
+            (missing_sizes.is_empty(), self.current_source.clone())
+            && !self.failed.contains(&path)
+        {
+            let target = self.decode_target(&layer_images, None);
+            self.request_image(path, missing_sizes, target);
+        }
+
+        for (path, sizes) in missing_alternates {
+            let target = self.decode_target(&layer_images, Some(&path));
+            self.request_alternate(path, sizes, target);
+        }
+    }
+
+    /// The size to decode an image at: the largest buffer of the layers showing it,
+    /// so that every layer can be scaled from the same decoded image.
+    ///
+    /// `alternate` is the image shown instead of the current one, if any.
+    fn decode_target(
+        &self,
+        layer_images: &[Option<PathBuf>],
+        alternate: Option<&PathBuf>,
+    ) -> (u32, u32) {
+        self.layers
+            .iter()
+            .zip(layer_images)
+            .filter(|(_, image)| image.as_ref() == alternate)
+            .filter_map(|(layer, _)| layer_buffer_size(self.active_source(), layer))
+            .fold((0, 0), |(w, h), (width, height)| {
+                (w.max(width), h.max(height))
+            })
+    }
+
+    /// The image shown on each layer instead of the current one, if the current image
+    /// doesn't suit the aspect ratio of the layer.
+    fn alternate_images(&mut self) -> Vec<Option<PathBuf>> {
+        let current = match &self.current_source {
+            Some(Source::Path(path)) if self.image_queue.len() > 1 => path,
+            _ => return self.layers.iter().map(|_| None).collect(),
+        };
+
+        let current = current.clone();
+        self.request_dimensions(&current);
+
+        let dimensions = &self.image_dimensions;
+        self.layers
+            .iter()
+            .map(|layer| {
+                aspect::pick_for_output(&self.image_queue, &current, layer.size?, |path| {
+                    dimensions.get(path)
+                })
+            })
+            .collect()
+    }
+
+    /// Reads the sizes of `current` and the upcoming images on the worker pool,
+    /// unless they are known or being read.
+    ///
+    /// Until then, images of unknown size are shown on every layer.
+    fn request_dimensions(&mut self, current: &Path) {
+        static NEXT_DIMENSIONS_ID: AtomicU64 = AtomicU64::new(0);
+
+        if self.dimensions_job.is_some() {
+            return;
+        }
+
+        let paths: Vec<PathBuf> = std::iter::once(current)
+            .chain(
+                self.image_queue
+                    .iter()
+                    .take(aspect::SEARCH_LEN)
+                    .map(PathBuf::as_path),
+            )
+            .filter(|path| !self.image_dimensions.contains(path))
+            .map(Path::to_path_buf)
+            .collect();
+
+        if paths.is_empty() {
+            return;
+        }
+
+        let id = NEXT_DIMENSIONS_ID.fetch_add(1, Ordering::Relaxed);
+        self.dimensions_job = Some(id);
+        self.image_worker.submit_dimensions(DimensionsJob {
+            id,
+            paths,
+            dimensions: self.image_dimensions.clone(),
+        });
+    }
+
+    pub fn is_pending_dimensions(&self, id: u64) -> bool {
+        self.dimensions_job == Some(id)
+    }
+
+    /// Redraws the layers whose image changes now that more image sizes are known.
+    pub fn dimensions_read(&mut self) {
+        self.dimensions_job = None;
+
+        if !matches!(self.current_source, Some(Source::Path(_))) {
+            return;
+        }
+
+        let alternates = self.alternate_images();
+        let mut changed = false;
+        for (idx, layer) in self.layers.iter_mut().enumerate() {
+            if self.shown_alternates.get(idx) != alternates.get(idx) {
+                layer.needs_redraw = true;
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.draw();
+        }
+    }
+
+    /// Submits a job to decode and scale an alternate image unless an equivalent job is pending.
+    fn request_alternate(&mut self, path: PathBuf, mut sizes: Vec<(u32, u32)>, target: (u32, u32)) {
+        let index = match self.alternates.iter().position(|a| a.path == path) {
+            Some(index) => index,
+            None => {
+                self.alternates.push(Alternate {
+                    path: path.clone(),
+                    scaled: Vec::new(),
+                    job: None,
+                });
+                self.alternates.len() - 1
+            }
+        };
+
+        if let Some(pending) = self.alternates[index].job.take() {
+            if sizes.iter().all(|size| pending.sizes.contains(size)) {
+                self.alternates[index].job = Some(pending);
+                return;
+            }
+
+            for size in &pending.sizes {
+                if !sizes.contains(size) {
+                    sizes.push(*size);
+                }
+            }
+
+            pending.cancel();
+        }
+
+        self.alternates[index].job = Some(self.submit_job(path, None, sizes, target));
+    }
+
+    /// Submits a job to decode and scale `path` unless an equivalent job is pending.
+    fn request_image(&mut self, path: PathBuf, mut sizes: Vec<(u32, u32)>, target: (u32, u32)) {
+        if let Some(pending) = self.pending_job.take() {
+            if pending.path == path {
+                if sizes.iter().all(|size| pending.sizes.contains(size)) {
+                    self.pending_job = Some(pending);
+                    return;
+                }
+
+                // The replacement job also covers the sizes of the superseded one.
+                for size in &pending.sizes {
+                    if !sizes.contains(size) {
+                        sizes.push(*size);
+                    }
+                }
+            }
+
+            pending.cancel();
+        }
+
+        let source = self.current_image.clone();
+        self.pending_job = Some(self.submit_job(path, source, sizes, target));
+    }
+
+    fn submit_job(
+        &self,
+        path: PathBuf,
+        source: Option<Arc<DynamicImage>>,
+        sizes: Vec<(u32, u32)>,
+        target: (u32, u32),
+    ) -> PendingJob {
+        static NEXT_JOB_ID: AtomicU64 = AtomicU64::new(0);
+
+        let pending = PendingJob {
+            id: NEXT_JOB_ID.fetch_add(1, Ordering::Relaxed),
+            path: path.clone(),
+            sizes: sizes.clone(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+
+        self.image_worker.submit(ImageJob {
+            id: pending.id,
+            path,
+            source,
+            scaling_mode: self.entry.scaling_mode.clone(),
+            filter_method: self.entry.filter_method.clone(),
+            sizes,
+            target,
+            cancelled: pending.cancelled.clone(),
+        });
+
+        pending
+    }
+
+    /// Decodes and scales the next slideshow image for every layer ahead of the rotation.
+    fn prefetch(&mut self) {
+        let Some(next) = self.image_queue.front().cloned() else {
+            return;
+        };
+
+        if matches!(&self.current_source, Some(Source::Path(path)) if *path == next)
+            || self.prefetched.as_ref().is_some_and(|p| p.path == next)
+            || self.prefetch_job.as_ref().is_some_and(|p| p.path == next)
+        {
+            return;
+        }
+
+        let mut sizes = Vec::new();
+        self.request_dimensions(&next);
+        let source = playlist_item(&self.entry.source, self.playlist_index);
+        let dimensions = &self.image_dimensions;
+        for layer in &self.layers {
+            // Layers that the image doesn't suit show another one.
+            if let Some(size) = layer.size
+                && aspect::pick_for_output(&self.image_queue, &next, size, |path| {
+                    dimensions.get(path)
+                })
+                .is_some()
+            {
+                continue;
+            }
+
+            if let Some(size) = layer_buffer_size(source, layer)
+                && !sizes.contains(&size)
+            {
+                sizes.push(size);
+            }
+        }
+
+        if sizes.is_empty() {
+            return;
+        }
+
+        // Only one image is prefetched at a time.
+        self.prefetched = None;
+        if let Some(pending) = self.prefetch_job.take() {
+            pending.cancel();
+        }
+
+        // The sizes are those of every layer that will show the image.
+        let target = sizes.iter().fold((0, 0), |(w, h), (width, height)| {
+            (w.max(*width), h.max(*height))
+        });
+
+        tracing::debug!(path = ?next, "prefetching next slideshow image");
+        self.prefetch_job = Some(self.submit_job(next, None, sizes, target));
+    }
+
+    /// Uses the prefetched image, or its pending job, if it is the new current image.
+    fn take_prefetched(&mut self, path: &PathBuf) {
+        if let Some(prefetched) = self.prefetched.take()
+            && prefetched.path == *path
+        {
+            self.current_image = prefetched.source;
+            self.scaled_images = prefetched.scaled;
+            return;
+        }
+
+        if let Some(pending) = self.prefetch_job.take() {
+            if pending.path == *path {
+                self.pending_job = Some(pending);
+            } else {
+                pending.cancel();
+            }
+        }
+    }
+
+    /// Whether `job_id` is an image job this wallpaper is waiting for.
+    pub fn is_pending_job(&self, job_id: u64) -> bool {
+        [&self.pending_job, &self.prefetch_job]
+            .into_iter()
+            .flatten()
+            .chain(self.alternates.iter().filter_map(|a| a.job.as_ref()))
+            .any(|pending| pending.id == job_id)
+    }
+
+    /// Draws the layers waiting for a finished image job, or keeps a prefetched image.
+    pub fn image_loaded(&mut self, result: ImageResult) {
+        if let Some(alternate) = self.alternates.iter_mut().find(|alternate| {
+            alternate
+                .job
+                .as_ref()
+                .is_some_and(|pending| pending.id == result.id)
+        }) {
+            alternate.job = None;
+
+            match result.image {
+                Ok((_, scaled)) => {
+                    for (size, image) in scaled {
+                        alternate.scaled.retain(|(s, _)| *s != size);
+                        alternate.scaled.push((size, image));
+                    }
+                    self.draw();
+                }
+
+                Err(why) => {
+                    tracing::warn!(?why, "could not decode image: {}", result.path.display());
+                    // The layers pick another image, or show the current one.
+                    self.image_failed(&result.path);
+                    self.draw();
+                }
+            }
+
+            return;
+        }
+
+        if self
+            .prefetch_job
+            .as_ref()
+            .is_some_and(|pending| pending.id == result.id)
+        {
+            self.prefetch_job = None;
+
+            match result.image {
+                Ok((source, scaled)) => {
+                    self.prefetched = Some(Prefetched {
+                        path: result.path,
+                        source,
+                        scaled,
+                    });
+                }
+
+                Err(why) => {
+                    tracing::warn!(?why, "could not prefetch image: {}", result.path.display());
+                    self.image_failed(&result.path);
+                }
+            }
+
+            return;
+        }
+
+        if self
+            .pending_job
+            .as_ref()
+            .is_none_or(|pending| pending.id != result.id)
+        {
+            return;
+        }
+
+        self.pending_job = None;
+
+        match result.image {
+            Ok((source, scaled)) => {
+                if source.is_some() {
+                    self.current_image = source;
+                }
+                for (size, image) in scaled {
+                    self.scaled_images.retain(|(s, _)| *s != size);
+                    self.scaled_images.push((size, image));
+                }
+                self.draw();
+            }
+
+            Err(why) => {
+                tracing::warn!(?why, "could not decode image: {}", result.path.display());
+                self.image_failed(&result.path);
+
+                // A slideshow moves on to the next image, while a single image stays
+                // undrawn instead of being decoded again on every draw.
+                if self.advance(false) {
+                    self.restart_timer();
+                }
+            }
+        }
+    }
+
+    /// Leaves an image that failed to decode out of the slideshow.
+    fn image_failed(&mut self, path: &Path) {
+        self.failed.insert(path.to_path_buf());
+        slideshow::forget(
+            &mut self.image_queue,
+            &mut self.unshown,
+            &mut self.history,
+            path,
+        );
+        self.save_shuffle_bag();
     }
 
     pub fn load_images(&mut self) {
         let mut image_queue = VecDeque::new();
-        let xdg_data_dirs: Vec<String> = match std::env::var("XDG_DATA_DIRS") {
-            Ok(raw_xdg_data_dirs) => raw_xdg_data_dirs
-                .split(':')
-                .map(|s| format!("{}/backgrounds/", s))
-                .collect(),
-            Err(_) => Vec::new(),
-        };
 
-        match self.entry.source {
-            Source::Path(ref source) => {
+        match self.active_source().clone() {
+            Source::Path(source) => {
                 tracing::debug!(?source, "loading images");
 
                 if let Ok(source) = source.canonicalize() {
                     if source.is_dir() {
-                        if xdg_data_dirs
-                            .iter()
-                            .any(|xdg_data_dir| source.starts_with(xdg_data_dir))
-                        {
-                            // Store paths of wallpapers to be used for the slideshow.
-                            for img_path in WalkDir::new(source)
-                                .follow_links(true)
+                        // Store paths of wallpapers to be used for the slideshow.
+                        let depth = self.recursion_depth(&source);
+                        image_queue.extend(
+                            slideshow::canonicalize(slideshow::scan(&source, depth))
                                 .into_iter()
-                                .filter_map(Result::ok)
-                                .filter(|p| p.path().is_file())
-                            {
-                                image_queue.push_front(img_path.path().into());
-                            }
-                        } else if let Ok(dir) = source.read_dir() {
-                            for entry in dir.filter_map(Result::ok) {
-                                let Ok(path) = entry.path().canonicalize() else {
-                                    continue;
-                                };
-
-                                if path.is_file() {
-                                    image_queue.push_front(path);
-                                }
-                            }
-                        }
+                                .filter(|path| {
+                                    !self.preferences.blocklist.contains(path)
+                                        && !self.failed.contains(path)
+                                }),
+                        );
                     } else if source.is_file() {
+                        // A single image is decoded again whenever it is reloaded.
+                        self.failed.remove(&source);
                         image_queue.push_front(source);
                     }
                 }
 
-                if image_queue.len() > 1 {
-                    let image_slice = image_queue.make_contiguous();
-                    match self.entry.sampling_method {
-                        SamplingMethod::Alphanumeric => {
-                            image_slice
-                                .sort_by(|a, b| a.to_string_lossy().cmp(&b.to_string_lossy()));
-                        }
-                        SamplingMethod::Random => image_slice.shuffle(&mut rng()),
+                self.unshown = 0;
+
+                if image_queue.len() > 1 && self.entry.sampling_method == SamplingMethod::Shuffle {
+                    // Continue the shuffle round that was interrupted by the last exit.
+                    let current = match current_image(&self.entry.output) {
+                        Some(Source::Path(path)) => Some(path),
+                        _ => None,
                     };
 
-                    // If a wallpaper from this slideshow was previously set, resume with that wallpaper.
-                    if let Some(Source::Path(last_path)) = current_image(&self.entry.output) {
-                        if image_queue.contains(&last_path) {
-                            while let Some(path) = image_queue.pop_front() {
-                                if path == last_path {
-                                    image_queue.push_front(path);
-                                    break;
-                                }
+                    self.unshown = slideshow::resume_shuffle(
+                        &mut image_queue,
+                        &shuffle_bag(&self.entry.output),
+                        current.as_deref(),
+                    );
+                    self.current_source = image_queue.back().cloned().map(Source::Path);
+                } else {
+                    if image_queue.len() > 1 {
+                        slideshow::order(
+                            &mut image_queue,
+                            &self.entry.sampling_method,
+                            &self.preferences.favorites,
+                            &self.sort_keys,
+                        );
+
+                        // If a wallpaper from this slideshow was previously set, resume with that wallpaper.
+                        if let Some(Source::Path(last_path)) = current_image(&self.entry.output) {
+                            if image_queue.contains(&last_path) {
+                                while let Some(path) = image_queue.pop_front() {
+                                    if path == last_path {
+                                        image_queue.push_front(path);
+                                        break;
+                                    }
 
-                                image_queue.push_back(path);
+                                    image_queue.push_back(path);
+                                }
                             }
                         }
                     }
-                }
 
-                image_queue.pop_front().map(|current_image_path| {
-                    self.current_source = Some(Source::Path(current_image_path.clone()));
-                    image_queue.push_back(current_image_path);
-                });
+                    image_queue.pop_front().map(|current_image_path| {
+                        self.current_source = Some(Source::Path(current_image_path.clone()));
+                        image_queue.push_back(current_image_path);
+                    });
+                }
             }
 
-            Source::Color(ref c) => {
-                self.current_source = Some(Source::Color(c.clone()));
+            Source::Color(c) => {
+                self.current_source = Some(Source::Color(c));
             }
 
-            Source::Shader(ref shader) => {
+            Source::Shader(shader) => {
                 // Shader wallpapers are handled by the GPU renderer
                 // Just set the source, GPU initialization happens in GlowBerry::init_gpu_layer
-                self.current_source = Some(Source::Shader(shader.clone()));
+                self.current_source = Some(Source::Shader(shader));
                 tracing::info!("Shader wallpaper source configured");
             }
+
+            Source::Playlist(_) => {
+                tracing::warn!(output = self.entry.output, "playlists can't be nested");
+            }
         };
         if let Err(err) = self.save_state() {
             error!("{err}");
         }
+        self.image_dimensions.retain(&image_queue);
+        self.sort_keys.retain(&image_queue);
         self.image_queue = image_queue;
+        self.save_shuffle_bag();
+        self.request_sort_keys();
+    }
+
+    /// Reads the timestamps of slideshow images on the worker pool, unless they
+    /// are known or the sampling method doesn't sort by them.
+    ///
+    /// Until then, the images are sorted by path.
+    fn request_sort_keys(&mut self) {
+        static NEXT_SORT_KEYS_ID: AtomicU64 = AtomicU64::new(0);
+
+        if !slideshow::is_sorted_by_date(&self.entry.sampling_method) {
+            return;
+        }
+
+        let paths: Vec<PathBuf> = self
+            .image_queue
+            .iter()
+            .filter(|path| !self.sort_keys.contains(path))
+            .cloned()
+            .collect();
+
+        if paths.is_empty() {
+            return;
+        }
+
+        // A pending job is superseded, and its timestamps are still recorded.
+        let id = NEXT_SORT_KEYS_ID.fetch_add(1, Ordering::Relaxed);
+        self.sort_keys_job = Some(id);
+        self.image_worker.submit_sort_keys(SortKeysJob {
+            id,
+            paths,
+            sampling_method: self.entry.sampling_method,
+            keys: self.sort_keys.clone(),
+        });
+    }
+
+    pub fn is_pending_sort_keys(&self, id: u64) -> bool {
+        self.sort_keys_job == Some(id)
+    }
+
+    /// Sorts the slideshow again now that the timestamps of its images are known.
+    pub fn sort_keys_read(&mut self) {
+        self.sort_keys_job = None;
+        slideshow::reorder(
+            &mut self.image_queue,
+            &self.entry.sampling_method,
+            &self.sort_keys,
+        );
+    }
+
+    /// Updates the slideshow queue for a change in the watched source directory.
+    pub fn source_changed(&mut self, event: &notify::Event) {
+        use notify::{
+            EventKind,
+            event::{AccessKind, AccessMode, ModifyKind, RenameMode},
+        };
+
+        let Source::Path(source) = self.active_source() else {
+            return;
+        };
+
+        let Ok(dir) = source.canonicalize() else {
+            return;
+        };
+
+        if !dir.is_dir() {
+            return;
+        }
+
+        let depth = self.recursion_depth(&dir);
+
+        match event.kind {
+            // Files are checked again once written, as they may still be empty when created.
+            EventKind::Create(_)
+            | EventKind::Access(AccessKind::Close(AccessMode::Write))
+            | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                for path in &event.paths {
+                    if !slideshow::is_within(&dir, path, depth) {
+                        continue;
+                    }
+
+                    let images = if path.is_dir() {
+                        // A directory moved into the source brings its images along.
+                        slideshow::canonicalize(
+                            slideshow::scan(path, usize::MAX)
+                                .into_iter()
+                                .filter(|image| slideshow::is_within(&dir, image, depth)),
+                        )
+                    } else if loader::is_image_file(path) {
+                        slideshow::canonicalize([path.clone()])
+                    } else {
+                        Vec::new()
+                    };
+
+                    for image in images {
+                        if self.preferences.blocklist.contains(&image) {
+                            continue;
+                        }
+
+                        // A rewritten image is decoded again.
+                        self.failed.remove(&image);
+
+                        if self.entry.sampling_method == SamplingMethod::Shuffle {
+                            slideshow::insert_unshown(
+                                &mut self.image_queue,
+                                &mut self.unshown,
+                                image,
+                            );
+                        } else {
+                            slideshow::insert(
+                                &mut self.image_queue,
+                                image,
+                                &self.entry.sampling_method,
+                                &self.sort_keys,
+                            );
+                        }
+                    }
+
+                    self.save_shuffle_bag();
+                }
+
+                // New images are moved to their place once their dates are read.
+                self.request_sort_keys();
+            }
+
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                for path in &event.paths {
+                    slideshow::remove(&mut self.image_queue, &mut self.unshown, path);
+                }
+
+                self.save_shuffle_bag();
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Levels of subdirectories of `dir` to search for slideshow images.
+    fn recursion_depth(&self, dir: &Path) -> usize {
+        self.entry
+            .recursion_depth
+            .unwrap_or_else(|| slideshow::default_recursion_depth(dir))
     }
 
-    /// Check if this wallpaper uses a shader source.
+    /// Check if this wallpaper is rendered by a shader on the GPU.
     pub fn is_shader(&self) -> bool {
-        matches!(self.entry.source, Source::Shader(_))
+        match self.active_source() {
+            Source::Shader(_) => true,
+            Source::Color(Color::Gradient(_)) => self.gpu_gradients,
+            _ => false,
+        }
+    }
+
+    /// Set whether gradients are rendered by the GPU.
+    ///
+    /// The caller is responsible for switching the layers to match.
+    pub fn set_gpu_gradients(&mut self, enabled: bool) {
+        self.gpu_gradients = enabled;
+    }
+
+    /// Size in bytes of the SHM pool needed to draw a layer of `width` x `height` physical pixels.
+    pub fn shm_pool_len(&self, width: u32, height: u32) -> usize {
+        let (width, height) = buffer_size(self.active_source(), width, height);
+        width as usize * height as usize * 4
     }
 
-    /// Get the shader source if this is a shader wallpaper.
-    pub fn shader_source(&self) -> Option<&ShaderSource> {
-        match &self.entry.source {
-            Source::Shader(s) => Some(s),
+    /// Get the shader source if this wallpaper is rendered by a shader.
+    pub fn shader_source(&self) -> Option<ShaderSource> {
+        match self.active_source() {
+            Source::Shader(s) => Some(s.clone()),
+            Source::Color(Color::Gradient(gradient)) if self.gpu_gradients => {
+                Some(colored::gradient_shader(gradient))
+            }
             _ => None,
         }
     }
 
-    fn watch_source(&self, tx: calloop::channel::SyncSender<(String, notify::Event)>) {
+    fn watch_source(&mut self) {
+        self.watcher = None;
+
         // Only watch file sources for changes
-        let source = match &self.entry.source {
-            Source::Path(path) => path,
+        let source = match self.active_source() {
+            Source::Path(path) => path.clone(),
             // For shader sources, we could watch the shader file for hot-reloading
             // but that's a future enhancement
-            Source::Shader(_) | Source::Color(_) => return,
+            Source::Shader(_) | Source::Color(_) | Source::Playlist(_) => return,
         };
 
         let output = self.entry.output.clone();
+        let tx = self.source_tx.clone();
         let mut watcher = match RecommendedWatcher::new(
             move |res| {
                 if let Ok(e) = res {
@@ -362,58 +1199,338 @@ impl Wallpaper {
 
         tracing::debug!(output = self.entry.output, "watching source");
 
-        if let Ok(m) = fs::metadata(source) {
+        // Watch the resolved path, so that event paths match the queued images.
+        let Ok(source) = source.canonicalize() else {
+            return;
+        };
+
+        if let Ok(m) = fs::metadata(&source) {
             if m.is_dir() {
-                let _ = watcher.watch(source, RecursiveMode::Recursive);
+                let mode = if self.recursion_depth(&source) == 0 {
+                    RecursiveMode::NonRecursive
+                } else {
+                    RecursiveMode::Recursive
+                };
+                let _ = watcher.watch(&source, mode);
             } else if m.is_file() {
-                let _ = watcher.watch(source, RecursiveMode::NonRecursive);
+                let _ = watcher.watch(&source, RecursiveMode::NonRecursive);
             }
         }
+
+        // The watcher stops when dropped.
+        self.watcher = Some(watcher);
     }
 
     fn register_timer(&mut self) {
-        let rotation_freq = self.entry.rotation_frequency;
-        let output_clone = self.entry.output.clone();
+        self.next_rotation = match schedule::next_rotation(
+            &self.entry.rotation_schedule,
+            self.entry.rotation_frequency,
+            self.last_rotation,
+        ) {
+            Ok(next) => next,
+            Err(why) => {
+                tracing::warn!(?why, output = self.entry.output, "not rotating wallpaper");
+                None
+            }
+        };
+
         // set timer for rotation
-        if rotation_freq > 0 {
-            self.timer_token = self
-                .loop_handle
-                .insert_source(
-                    Timer::from_duration(Duration::from_secs(rotation_freq)),
-                    move |_, _, state: &mut GlowBerry| {
-                        let span = tracing::debug_span!("Wallpaper::timer");
-                        let _handle = span.enter();
-
-                        let Some(item) = state
-                            .wallpapers
-                            .iter_mut()
-                            .find(|w| w.entry.output == output_clone)
-                        else {
-                            return TimeoutAction::Drop; // Drop if no item found for this timer
-                        };
+        let Some(next) = self.next_rotation else {
+            return;
+        };
 
-                        while let Some(next) = item.image_queue.pop_front() {
-                            item.current_source = Some(Source::Path(next.clone()));
-                            if let Err(err) = item.save_state() {
-                                error!("{err}");
-                            }
+        let output_clone = self.entry.output.clone();
+        self.timer_token = self
+            .loop_handle
+            .insert_source(
+                Timer::from_duration(schedule::timer_wait(next)),
+                move |_, _, state: &mut GlowBerry| {
+                    let span = tracing::debug_span!("Wallpaper::timer");
+                    let _handle = span.enter();
 
-                            item.image_queue.push_back(next);
-                            item.clear_image();
-                            item.draw();
+                    let Some(item) = state
+                        .wallpapers
+                        .iter_mut()
+                        .find(|w| w.entry.output == output_clone)
+                    else {
+                        return TimeoutAction::Drop; // Drop if no item found for this timer
+                    };
 
-                            return TimeoutAction::ToDuration(Duration::from_secs(rotation_freq));
-                        }
+                    let Some(next) = item.next_rotation else {
+                        return TimeoutAction::Drop;
+                    };
+
+                    // The timer wakes up early to notice time spent in suspend.
+                    if SystemTime::now() < next {
+                        return TimeoutAction::ToDuration(schedule::timer_wait(next));
+                    }
+
+                    if !item.advance(true) {
+                        return TimeoutAction::Drop;
+                    }
+
+                    match item.next_rotation {
+                        Some(next) => TimeoutAction::ToDuration(schedule::timer_wait(next)),
+                        None => TimeoutAction::Drop,
+                    }
+                },
+            )
+            .ok();
 
-                        TimeoutAction::Drop
-                    },
-                )
-                .ok();
+        self.schedule_prefetch();
+    }
+
+    /// Shows the next slideshow image and restarts the rotation timer.
+    pub fn next(&mut self) {
+        if self.advance(true) {
+            self.restart_timer();
+        }
+    }
+
+    /// Goes back to the most recently shown slideshow image.
+    pub fn previous(&mut self) {
+        let shuffled = self.entry.sampling_method == SamplingMethod::Shuffle;
+        match slideshow::go_back(
+            &mut self.image_queue,
+            &mut self.unshown,
+            &mut self.history,
+            shuffled,
+        ) {
+            Some(previous) => {
+                self.show(previous, false);
+                self.restart_timer();
+            }
+
+            // The history only held images that are no longer in the slideshow.
+            None => self.save_history(),
+        }
+    }
+
+    /// Removes the current image from the slideshow and moves on to the next one.
+    ///
+    /// Returns the blocked image, which the caller should add to the config blocklist.
+    pub fn block_current(&mut self) -> Option<PathBuf> {
+        let Some(Source::Path(current)) = self.current_source.clone() else {
+            return None;
+        };
+
+        // A single image has nothing to be replaced by.
+        if !self.image_queue.iter().any(|path| *path != current) {
+            return None;
+        }
+
+        self.preferences.blocklist.insert(current.clone());
+        slideshow::forget(
+            &mut self.image_queue,
+            &mut self.unshown,
+            &mut self.history,
+            &current,
+        );
+
+        if self.advance(false) {
+            self.restart_timer();
+        }
+
+        Some(current)
+    }
+
+    /// Replaces the blocked and favorite images, removing newly blocked images
+    /// from the slideshow.
+    ///
+    /// Images removed from the blocklist return on the next reload of the source.
+    pub fn set_preferences(&mut self, preferences: ImagePreferences) {
+        slideshow::retain(&mut self.image_queue, &mut self.unshown, |path| {
+            !preferences.blocklist.contains(path)
+        });
+        self.preferences = preferences;
+        self.save_shuffle_bag();
+    }
+
+    /// The slideshow image that is currently shown.
+    pub fn current_image_path(&self) -> Option<&Path> {
+        match &self.current_source {
+            Some(Source::Path(path)) if !self.image_queue.is_empty() => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Shows the next image in the queue, returning whether there was one.
+    fn advance(&mut self, remember_current: bool) -> bool {
+        let Some(next) = self.image_queue.pop_front() else {
+            return false;
+        };
+
+        self.image_queue.push_back(next.clone());
+        self.unshown = self.unshown.saturating_sub(1);
+
+        // Decide on the image after this one now, so that it can be prefetched.
+        match self.entry.sampling_method {
+            SamplingMethod::Shuffle if self.unshown == 0 => {
+                self.unshown = slideshow::reshuffle(&mut self.image_queue);
+            }
+            SamplingMethod::Weighted => {
+                slideshow::pick_weighted(&mut self.image_queue, &self.preferences.favorites);
+            }
+            _ => {}
+        }
+
+        self.show(next, remember_current);
+        true
+    }
+
+    /// Draws `path` as the current image, adding the replaced image to the history.
+    fn show(&mut self, path: PathBuf, remember_current: bool) {
+        if remember_current
+            && let Some(Source::Path(current)) = self.current_source.take()
+            && current != path
+        {
+            slideshow::remember(&mut self.history, current);
+        }
+
+        self.current_source = Some(Source::Path(path.clone()));
+        self.last_rotation = SystemTime::now();
+        self.next_rotation = schedule::next_rotation(
+            &self.entry.rotation_schedule,
+            self.entry.rotation_frequency,
+            self.last_rotation,
+        )
+        .unwrap_or_default();
+        self.save_rotation();
+
+        self.clear_image();
+        self.take_prefetched(&path);
+        self.draw();
+        self.schedule_prefetch();
+    }
+
+    fn save_history(&self) {
+        let result = State::state().and_then(|helper| {
+            let mut state = State::get_entry(&helper).unwrap_or_default();
+            state.set_history(&self.entry.output, self.history.clone());
+            state.write_entry(&helper)
+        });
+
+        if let Err(err) = result {
+            error!("failed to save slideshow history: {err}");
+        }
+    }
+
+    /// Saves the current image, the history, the shuffle bag and the rotation time
+    /// of a rotation in a single write.
+    fn save_rotation(&self) {
+        let result = State::state().and_then(|helper| {
+            let mut state = State::get_entry(&helper).unwrap_or_default();
+            self.record_current(&mut state);
+            state.set_history(&self.entry.output, self.history.clone());
+            if let Some(bag) = self.shuffle_bag() {
+                state.set_shuffle_bag(&self.entry.output, bag);
+            }
+            state.set_last_rotation(&self.entry.output, self.last_rotation);
+            state.write_entry(&helper)
+        });
+
+        if let Err(err) = result {
+            error!("failed to save slideshow state: {err}");
+        }
+    }
+
+    /// The remaining images of the shuffle round, if the slideshow is shuffled.
+    fn shuffle_bag(&self) -> Option<Vec<PathBuf>> {
+        (self.entry.sampling_method == SamplingMethod::Shuffle).then(|| {
+            self.image_queue
+                .iter()
+                .take(self.unshown)
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// Saves the remaining images of the shuffle round, to continue it after a restart.
+    fn save_shuffle_bag(&self) {
+        let Some(bag) = self.shuffle_bag() else {
+            return;
+        };
+
+        let result = State::state().and_then(|helper| {
+            let mut state = State::get_entry(&helper).unwrap_or_default();
+            state.set_shuffle_bag(&self.entry.output, bag);
+            state.write_entry(&helper)
+        });
+
+        if let Err(err) = result {
+            error!("failed to save shuffle bag: {err}");
+        }
+    }
+
+    fn save_last_rotation(&self) {
+        let result = State::state().and_then(|helper| {
+            let mut state = State::get_entry(&helper).unwrap_or_default();
+            state.set_last_rotation(&self.entry.output, self.last_rotation);
+            state.write_entry(&helper)
+        });
+
+        if let Err(err) = result {
+            error!("failed to save slideshow rotation time: {err}");
+        }
+    }
+
+    fn restart_timer(&mut self) {
+        if let Some(token) = self.timer_token.take() {
+            self.loop_handle.remove(token);
         }
+
+        self.register_timer();
+    }
+
+    /// Schedules prefetching of the next slideshow image shortly before the rotation.
+    fn schedule_prefetch(&mut self) {
+        if let Some(token) = self.prefetch_token.take() {
+            self.loop_handle.remove(token);
+        }
+
+        let Some(next) = self.next_rotation else {
+            return;
+        };
+
+        if !matches!(self.active_source(), Source::Path(_)) {
+            return;
+        }
+
+        let now = SystemTime::now();
+        let rotation = next.duration_since(self.last_rotation).unwrap_or_default();
+        let delay = next.duration_since(now).unwrap_or_default();
+        let delay = delay.saturating_sub(PREFETCH_LEAD.min(rotation / 2));
+        let output = self.entry.output.clone();
+
+        self.prefetch_token = self
+            .loop_handle
+            .insert_source(
+                Timer::from_duration(delay),
+                move |_, _, state: &mut GlowBerry| {
+                    if let Some(item) = state
+                        .wallpapers
+                        .iter_mut()
+                        .find(|w| w.entry.output == output)
+                    {
+                        item.prefetch_token = None;
+                        item.prefetch();
+                    }
+
+                    TimeoutAction::Drop
+                },
+            )
+            .ok();
     }
 
     fn clear_image(&mut self) {
         self.current_image = None;
+        self.scaled_images.clear();
+        if let Some(pending) = self.pending_job.take() {
+            pending.cancel();
+        }
+        for pending in self.alternates.drain(..).filter_map(|a| a.job) {
+            pending.cancel();
+        }
         for l in &mut self.layers {
             l.needs_redraw = true;
         }
@@ -436,13 +1553,86 @@ fn current_image(output: &str) -> Option<Source> {
     wallpaper.map(|(_name, path)| path)
 }
 
-/// Decodes JPEG XL image files into `image::DynamicImage` via `jxl-oxide`.
-fn decode_jpegxl(path: &std::path::Path) -> eyre::Result<DynamicImage> {
-    let file = File::open(path).map_err(|why| eyre!("failed to open jxl image file: {why}"))?;
+/// The item of a playlist source at `index`, or the source itself if it isn't a playlist.
+fn playlist_item(source: &Source, index: usize) -> &Source {
+    match source {
+        Source::Playlist(items) => items.get(index).map_or(source, |item| &item.source),
+        source => source,
+    }
+}
 
-    let decoder =
-        JxlDecoder::new(file).map_err(|why| eyre!("failed to read jxl image header: {why}"))?;
+/// Index of the playlist item that showed `current` before the last exit.
+fn playlist_position(source: &Source, current: Option<Source>) -> usize {
+    let (Source::Playlist(items), Some(current)) = (source, current) else {
+        return 0;
+    };
+
+    items
+        .iter()
+        .position(|item| match (&item.source, &current) {
+            (Source::Path(dir), Source::Path(image)) => {
+                image.starts_with(dir) || dir.canonicalize().is_ok_and(|dir| image.starts_with(dir))
+            }
+            (source, current) => source == current,
+        })
+        .unwrap_or(0)
+}
+
+fn shuffle_bag(output: &str) -> Vec<PathBuf> {
+    State::state()
+        .ok()
+        .map(|state| State::get_entry(&state).unwrap_or_default())
+        .map(|state| state.shuffle_bag(output).to_vec())
+        .unwrap_or_default()
+}
 
-    image::DynamicImage::from_decoder(decoder)
-        .map_err(|why| eyre!("failed to decode jxl image: {why}"))
+/// Size of the SHM buffer used to draw `source` on a configured layer.
+fn layer_buffer_size(source: &Source, layer: &GlowBerryLayer) -> Option<(u32, u32)> {
+    let (width, height) = layer.size?;
+    let fractional_scale = layer.fractional_scale?;
+
+    Some(buffer_size(
+        source,
+        width * fractional_scale / 120,
+        height * fractional_scale / 120,
+    ))
+}
+
+/// Size of the SHM buffer used to draw `source` on `width` x `height` physical pixels.
+///
+/// Solid colors need a single pixel, which the viewport scales to the output.
+/// Gradients are drawn at full size, so that they are dithered per pixel.
+fn buffer_size(source: &Source, width: u32, height: u32) -> (u32, u32) {
+    match source {
+        Source::Color(Color::Single(_)) => (1, 1),
+        _ => (width, height),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::playlist_position;
+    use glowberry_config::{Color, PlaylistItem, Source};
+    use std::path::PathBuf;
+
+    #[test]
+    fn resumes_the_playlist_item_that_was_shown() {
+        let playlist = Source::Playlist(vec![
+            PlaylistItem {
+                source: Source::Color(Color::Single([0.0, 0.0, 0.0])),
+                duration: None,
+            },
+            PlaylistItem {
+                source: Source::Path(PathBuf::from("/w/photos")),
+                duration: Some(60),
+            },
+        ]);
+
+        let image = Source::Path(PathBuf::from("/w/photos/a.jpg"));
+        let color = Source::Color(Color::Single([0.0, 0.0, 0.0]));
+
+        assert_eq!(playlist_position(&playlist, Some(image)), 1);
+        assert_eq!(playlist_position(&playlist, Some(color)), 0);
+        assert_eq!(playlist_position(&playlist, None), 0);
+    }
 }
@@ -1,14 +1,84 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use clap::Parser;
-use glowberry_lib::engine::{BackgroundEngine, EngineConfig};
+use clap::{Parser, Subcommand};
+use glowberry_lib::{
+    config_check,
+    control::{self, ControlCommand},
+    engine::{BackgroundEngine, EngineConfig},
+    profile_archive,
+};
+use std::path::PathBuf;
 use tracing_subscriber::prelude::*;
 
 /// GlowBerry - Enhanced background service with live shader support
 #[derive(Parser, Debug)]
 #[command(name = "glowberry")]
 #[command(author, version, about, long_about = None)]
-struct Args {}
+struct Args {
+    /// Where the config is stored, instead of cosmic-config on COSMIC and files elsewhere
+    #[arg(long, global = true, value_parser = ["cosmic", "files"])]
+    config_backend: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Slideshow controls for the running daemon and profile management
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show the next slideshow image
+    Next {
+        /// Output to change, or all outputs if omitted
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Go back to the previous slideshow image
+    Previous {
+        /// Output to change, or all outputs if omitted
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Never show the current slideshow image again
+    Block {
+        /// Output to change, or all outputs if omitted
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Add the current slideshow image to the favorites, or remove it
+    Favorite {
+        /// Output to change, or all outputs if omitted
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Report problems in the configuration, such as values that fail to parse
+    CheckConfig,
+    /// Save, switch and share named snapshots of the configuration
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfileCommand {
+    /// List saved profiles, marking the active one
+    List,
+    /// Save the current configuration as a profile
+    Save { name: String },
+    /// Replace the current configuration with a profile
+    Switch { name: String },
+    /// Delete a profile
+    Delete { name: String },
+    /// Write a profile and the files it references to an archive
+    Export { name: String, archive: PathBuf },
+    /// Add the profile in an archive, extracting the files it references
+    Import {
+        archive: PathBuf,
+        /// Name of the imported profile, instead of the name in the archive
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
 
 fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
@@ -21,9 +91,78 @@ fn main() -> color_eyre::Result<()> {
 
     init_logger();
 
-    let _args = Args::parse();
+    let args = Args::parse();
+
+    if let Some(backend) = &args.config_backend {
+        // Read by every config context, including those of child processes.
+        unsafe {
+            std::env::set_var(glowberry_config::backend::BACKEND_VAR, backend);
+        }
+    }
+
+    let (command, output) = match args.command {
+        None => {
+            BackgroundEngine::run(EngineConfig::default())?;
+            return Ok(());
+        }
+        Some(Command::Next { output }) => (ControlCommand::Next, output),
+        Some(Command::Previous { output }) => (ControlCommand::Previous, output),
+        Some(Command::Block { output }) => (ControlCommand::Block, output),
+        Some(Command::Favorite { output }) => (ControlCommand::Favorite, output),
+        Some(Command::CheckConfig) => return check_config(),
+        Some(Command::Profile { command }) => return profile(command),
+    };
+
+    control::send_blocking(command, output.as_deref())?;
 
-    BackgroundEngine::run(EngineConfig::default())?;
+    Ok(())
+}
+
+fn check_config() -> color_eyre::Result<()> {
+    let diagnostics = config_check::check(&glowberry_config::context()?);
+    if diagnostics.is_empty() {
+        println!("no problems found");
+        return Ok(());
+    }
+
+    for diagnostic in &diagnostics {
+        println!("{diagnostic}");
+    }
+    Err(eyre::eyre!("found {} config problems", diagnostics.len()))
+}
+
+fn profile(command: ProfileCommand) -> color_eyre::Result<()> {
+    let context = glowberry_config::context()?;
+
+    match command {
+        ProfileCommand::List => {
+            let active = context.active_profile();
+            for profile in context.profiles()? {
+                let marker = if active.as_ref() == Some(&profile.name) {
+                    '*'
+                } else {
+                    ' '
+                };
+                println!("{marker} {}", profile.name);
+            }
+        }
+        ProfileCommand::Save { name } => context.save_profile(&name)?,
+        ProfileCommand::Switch { name } => context.switch_profile(&name)?,
+        ProfileCommand::Delete { name } => context.delete_profile(&name)?,
+        ProfileCommand::Export { name, archive } => {
+            let profile = context
+                .profiles()?
+                .into_iter()
+                .find(|profile| profile.name == name)
+                .ok_or(glowberry_config::ConfigError::UnknownProfile(name))?;
+            profile_archive::export(&profile, &archive)?;
+        }
+        ProfileCommand::Import { archive, name } => {
+            let profile = profile_archive::import(&archive, name.as_deref())?;
+            println!("imported profile {}", profile.name);
+            context.add_profile(profile)?;
+        }
+    }
 
     Ok(())
 }